@@ -1,4 +1,5 @@
 use crate::store::TicketId;
+use std::time::SystemTime;
 use ticket_fields::{TicketDescription, TicketTitle};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -23,9 +24,51 @@ pub struct TicketPatch {
     pub status: Option<Status>,
 }
 
+// An operation of a batch (see `TicketStore::apply_batch`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchOperation {
+    Create(TicketDraft),
+    Patch(TicketPatch),
+    Delete(TicketId),
+}
+
+// What an operation of a batch did, at the same position as the operation
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchOutcome {
+    Created(TicketId),
+    Patched(Ticket),
+    Deleted(Ticket),
+}
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Status {
     ToDo,
     InProgress,
     Done,
 }
+
+// An immutable record of a patch that changed a ticket
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub ticket_id: TicketId,
+    pub timestamp: SystemTime,
+    pub actor: String,
+    pub changes: Vec<FieldChange>,
+}
+
+// A changed field, with its value before and after the patch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldChange {
+    Title {
+        before: TicketTitle,
+        after: TicketTitle,
+    },
+    Description {
+        before: TicketDescription,
+        after: TicketDescription,
+    },
+    Status {
+        before: Status,
+        after: Status,
+    },
+}
@@ -1,8 +1,8 @@
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 
 // TODO: Implement the patching functionality.
-use crate::data::{Ticket, TicketDraft, TicketPatch};
-use crate::store::{TicketId, TicketStore};
+use crate::data::{BatchOperation, BatchOutcome, HistoryEntry, Ticket, TicketDraft, TicketPatch};
+use crate::store::{BatchError, TicketId, TicketNotFound, TicketStore};
 
 pub mod data;
 pub mod store;
@@ -35,12 +35,58 @@ impl TicketStoreClient {
         Ok(response_receiver.recv().unwrap())
     }
 
-    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), OverloadedError> {
-        // TODO
+    // Returns the patched ticket, or an error if there is no ticket with the patch's id
+    pub fn update(&self, ticket_patch: TicketPatch) -> Result<Result<Ticket, TicketNotFound>, OverloadedError> {
+        self.update_as(ticket_patch, ANONYMOUS)
+    }
+
+    // Same as `update`, recording `actor` as the author of the change
+    pub fn update_as(
+        &self,
+        ticket_patch: TicketPatch,
+        actor: impl Into<String>,
+    ) -> Result<Result<Ticket, TicketNotFound>, OverloadedError> {
         let (response_sender, response_receiver) = sync_channel(1);
         self.sender
             .try_send(Command::Update {
                 patch: ticket_patch,
+                actor: actor.into(),
+                response_channel: response_sender,
+            })
+            .map_err(|_| OverloadedError)?;
+        Ok(response_receiver.recv().unwrap())
+    }
+
+    // Apply the operations atomically (see `TicketStore::apply_batch`)
+    pub fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+    ) -> Result<Result<Vec<BatchOutcome>, BatchError>, OverloadedError> {
+        self.apply_batch_as(operations, ANONYMOUS)
+    }
+
+    // Same as `apply_batch`, recording `actor` as the author of the patches
+    pub fn apply_batch_as(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: impl Into<String>,
+    ) -> Result<Result<Vec<BatchOutcome>, BatchError>, OverloadedError> {
+        let (response_sender, response_receiver) = sync_channel(1);
+        self.sender
+            .try_send(Command::Batch {
+                operations,
+                actor: actor.into(),
+                response_channel: response_sender,
+            })
+            .map_err(|_| OverloadedError)?;
+        Ok(response_receiver.recv().unwrap())
+    }
+
+    pub fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, OverloadedError> {
+        let (response_sender, response_receiver) = sync_channel(1);
+        self.sender
+            .try_send(Command::History {
+                id,
                 response_channel: response_sender,
             })
             .map_err(|_| OverloadedError)?;
@@ -48,6 +94,9 @@ impl TicketStoreClient {
     }
 }
 
+// The actor recorded for updates that don't say who made them
+pub const ANONYMOUS: &str = "anonymous";
+
 #[derive(Debug, thiserror::Error)]
 #[error("The store is overloaded")]
 pub struct OverloadedError;
@@ -69,7 +118,17 @@ enum Command {
     },
     Update {
         patch: TicketPatch,
-        response_channel: SyncSender<()>,
+        actor: String,
+        response_channel: SyncSender<Result<Ticket, TicketNotFound>>,
+    },
+    History {
+        id: TicketId,
+        response_channel: SyncSender<Vec<HistoryEntry>>,
+    },
+    Batch {
+        operations: Vec<BatchOperation>,
+        actor: String,
+        response_channel: SyncSender<Result<Vec<BatchOutcome>, BatchError>>,
     },
 }
 
@@ -93,22 +152,26 @@ pub fn server(receiver: Receiver<Command>) {
             }
             Ok(Command::Update {
                 patch,
+                actor,
+                response_channel,
+            }) => {
+                // Only the fields that are `Some` in the patch are updated
+                let id = patch.id;
+                let ticket = store.patch(patch, &actor).cloned().ok_or(TicketNotFound(id));
+                let _ = response_channel.send(ticket);
+            }
+            Ok(Command::History {
+                id,
+                response_channel,
+            }) => {
+                let _ = response_channel.send(store.history(id).to_vec());
+            }
+            Ok(Command::Batch {
+                operations,
+                actor,
                 response_channel,
             }) => {
-                // todo!()
-                // get a mutable ticket, using the ticket id of the patch
-                let ticket_mut: &mut Ticket = store.get_mut(patch.id).unwrap();
-                // Only update fields that are `Some` in the patch
-                if let Some(new_title) = patch.title {
-                    ticket_mut.title = new_title;
-                }
-                if let Some(new_description) = patch.description {
-                    ticket_mut.description = new_description;
-                }
-                if let Some(new_status) = patch.status {
-                    ticket_mut.status = new_status;
-                }
-                let _ = response_channel.send(());
+                let _ = response_channel.send(store.apply_batch(operations, &actor));
             }
             Err(_) => {
                 // There are no more senders, so we can safely break
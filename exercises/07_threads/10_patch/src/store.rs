@@ -1,13 +1,30 @@
-use crate::data::{Status, Ticket, TicketDraft};
+use crate::data::{
+    BatchOperation, BatchOutcome, FieldChange, HistoryEntry, Status, Ticket, TicketDraft, TicketPatch,
+};
 use std::collections::BTreeMap;
+use std::time::SystemTime;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TicketId(u64);
 
+// A batch with operations on tickets that don't exist. None of its operations was applied.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("{} operations of the batch refer to missing tickets", .failures.len())]
+pub struct BatchError {
+    // The position of each failed operation in the batch, and the ticket it refers to
+    pub failures: Vec<(usize, TicketId)>,
+}
+
+// A patch for a ticket that doesn't exist. Nothing was changed.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("There is no ticket with id {}", .0.0)]
+pub struct TicketNotFound(pub TicketId);
+
 #[derive(Clone)]
 pub struct TicketStore {
     tickets: BTreeMap<TicketId, Ticket>,
     counter: u64,
+    history: BTreeMap<TicketId, Vec<HistoryEntry>>,
 }
 
 impl TicketStore {
@@ -15,6 +32,7 @@ impl TicketStore {
         Self {
             tickets: BTreeMap::new(),
             counter: 0,
+            history: BTreeMap::new(),
         }
     }
 
@@ -38,4 +56,123 @@ impl TicketStore {
     pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Ticket> {
         self.tickets.get_mut(&id)
     }
+
+    // Apply a patch, recording the fields it changed in the ticket's history.
+    // Returns `None` if there is no ticket with the patch's id.
+    pub fn patch(&mut self, patch: TicketPatch, actor: &str) -> Option<&Ticket> {
+        let ticket = self.tickets.get_mut(&patch.id)?;
+        let mut changes = Vec::new();
+        if let Some(title) = patch.title {
+            if title != ticket.title {
+                let before = std::mem::replace(&mut ticket.title, title.clone());
+                changes.push(FieldChange::Title { before, after: title });
+            }
+        }
+        if let Some(description) = patch.description {
+            if description != ticket.description {
+                let before = std::mem::replace(&mut ticket.description, description.clone());
+                changes.push(FieldChange::Description {
+                    before,
+                    after: description,
+                });
+            }
+        }
+        if let Some(status) = patch.status {
+            if status != ticket.status {
+                let before = std::mem::replace(&mut ticket.status, status);
+                changes.push(FieldChange::Status { before, after: status });
+            }
+        }
+        // A patch that doesn't change anything isn't worth recording
+        if !changes.is_empty() {
+            self.history.entry(patch.id).or_default().push(HistoryEntry {
+                ticket_id: patch.id,
+                timestamp: SystemTime::now(),
+                actor: actor.to_string(),
+                changes,
+            });
+        }
+        Some(ticket)
+    }
+
+    pub fn remove(&mut self, id: TicketId) -> Option<Ticket> {
+        self.tickets.remove(&id)
+    }
+
+    // Apply the operations in order, all of them or none. They are applied to the store itself,
+    // each ticket they touch being backed up first: if any of them fails, the tickets are put
+    // back as they were.
+    pub fn apply_batch(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        actor: &str,
+    ) -> Result<Vec<BatchOutcome>, BatchError> {
+        let counter = self.counter;
+        let mut backups: BTreeMap<TicketId, TicketBackup> = BTreeMap::new();
+        let mut outcomes = Vec::new();
+        let mut failures = Vec::new();
+        for (index, operation) in operations.into_iter().enumerate() {
+            let id = match &operation {
+                BatchOperation::Create(_) => TicketId(self.counter),
+                BatchOperation::Patch(patch) => patch.id,
+                BatchOperation::Delete(id) => *id,
+            };
+            backups.entry(id).or_insert_with(|| self.backup(id));
+            let outcome = match operation {
+                BatchOperation::Create(draft) => Some(BatchOutcome::Created(self.add_ticket(draft))),
+                BatchOperation::Patch(patch) => self.patch(patch, actor).cloned().map(BatchOutcome::Patched),
+                BatchOperation::Delete(id) => self.remove(id).map(BatchOutcome::Deleted),
+            };
+            match outcome {
+                Some(outcome) => outcomes.push(outcome),
+                None => failures.push((index, id)),
+            }
+        }
+        if failures.is_empty() {
+            return Ok(outcomes);
+        }
+        for (id, backup) in backups {
+            self.restore_backup(id, backup);
+        }
+        self.counter = counter;
+        Err(BatchError { failures })
+    }
+
+    // What an operation of a batch can change about a ticket
+    fn backup(&self, id: TicketId) -> TicketBackup {
+        TicketBackup {
+            ticket: self.tickets.get(&id).cloned(),
+            history: self.history.get(&id).map(Vec::len),
+        }
+    }
+
+    fn restore_backup(&mut self, id: TicketId, backup: TicketBackup) {
+        match backup.history {
+            Some(len) => self.history.entry(id).or_default().truncate(len),
+            None => {
+                self.history.remove(&id);
+            }
+        }
+        match backup.ticket {
+            Some(ticket) => {
+                self.tickets.insert(id, ticket);
+            }
+            // Created by the batch
+            None => {
+                self.tickets.remove(&id);
+            }
+        }
+    }
+
+    // The changes made to a ticket, oldest first
+    pub fn history(&self, id: TicketId) -> &[HistoryEntry] {
+        self.history.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+// A ticket as it was before a batch touched it. `ticket` is `None` for the tickets the batch creates.
+struct TicketBackup {
+    ticket: Option<Ticket>,
+    // The length of the history of the ticket, if it had one
+    history: Option<usize>,
 }
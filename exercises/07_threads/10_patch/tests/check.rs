@@ -1,5 +1,6 @@
-use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::data::{BatchOperation, BatchOutcome, FieldChange, Status, TicketDraft, TicketPatch};
 use patch::launch;
+use patch::store::TicketNotFound;
 use ticket_fields::test_helpers::{ticket_description, ticket_title};
 
 #[test]
@@ -23,9 +24,121 @@ fn works() {
         description: None,
         status: Some(Status::InProgress),
     };
-    client.update(patch).unwrap();
+    let patched = client.update(patch).unwrap().unwrap();
+    assert_eq!(patched.status, Status::InProgress);
 
     let ticket = client.get(ticket_id).unwrap().unwrap();
     assert_eq!(ticket.id, ticket_id);
     assert_eq!(ticket.status, Status::InProgress);
 }
+
+#[test]
+fn updates_are_recorded() {
+    let client = launch(5);
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    };
+    let ticket_id = client.insert(draft.clone()).unwrap();
+    assert!(client.history(ticket_id).unwrap().is_empty());
+
+    let patch = TicketPatch {
+        id: ticket_id,
+        title: Some(draft.title.clone()),
+        description: None,
+        status: Some(Status::InProgress),
+    };
+    client.update_as(patch.clone(), "alice").unwrap().unwrap();
+    // Nothing changes the second time: it isn't recorded
+    client.update(patch).unwrap().unwrap();
+
+    let history = client.history(ticket_id).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].ticket_id, ticket_id);
+    assert_eq!(history[0].actor, "alice");
+    assert_eq!(
+        history[0].changes,
+        [FieldChange::Status {
+            before: Status::ToDo,
+            after: Status::InProgress
+        }]
+    );
+
+    client
+        .update(TicketPatch {
+            id: ticket_id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap()
+        .unwrap();
+    let history = client.history(ticket_id).unwrap();
+    assert_eq!(history[1].actor, patch::ANONYMOUS);
+    assert!(history[0].timestamp <= history[1].timestamp);
+}
+
+#[test]
+fn updating_a_missing_ticket_fails() {
+    let client = launch(5);
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    };
+    let ticket_id = client.insert(draft).unwrap();
+    client
+        .apply_batch(vec![BatchOperation::Delete(ticket_id)])
+        .unwrap()
+        .unwrap();
+
+    let patch = TicketPatch {
+        id: ticket_id,
+        title: None,
+        description: None,
+        status: Some(Status::Done),
+    };
+    let err = client.update_as(patch, "alice").unwrap().unwrap_err();
+    assert_eq!(err, TicketNotFound(ticket_id));
+    assert!(client.history(ticket_id).unwrap().is_empty());
+}
+
+#[test]
+fn batches_are_atomic() {
+    let client = launch(5);
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    };
+    let first = client.insert(draft.clone()).unwrap();
+    let start = |id| TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+    };
+
+    // The third operation patches the ticket the second one deleted: nothing is applied,
+    // not even the patch before it
+    let batch = vec![
+        BatchOperation::Patch(start(first)),
+        BatchOperation::Delete(first),
+        BatchOperation::Patch(start(first)),
+        BatchOperation::Create(draft.clone()),
+    ];
+    let err = client.apply_batch(batch).unwrap().unwrap_err();
+    assert_eq!(err.failures, [(2, first)]);
+    assert_eq!(client.get(first).unwrap().unwrap().status, Status::ToDo);
+    assert!(client.history(first).unwrap().is_empty());
+
+    let batch = vec![BatchOperation::Patch(start(first)), BatchOperation::Create(draft)];
+    let outcomes = client.apply_batch_as(batch, "alice").unwrap().unwrap();
+    let BatchOutcome::Patched(ticket) = &outcomes[0] else {
+        panic!("unexpected outcome: {:?}", outcomes[0]);
+    };
+    assert_eq!(ticket.status, Status::InProgress);
+    let BatchOutcome::Created(second) = outcomes[1] else {
+        panic!("unexpected outcome: {:?}", outcomes[1]);
+    };
+    assert!(client.get(second).unwrap().is_some());
+    assert_eq!(client.history(first).unwrap()[0].actor, "alice");
+}
@@ -1,53 +1,605 @@
-use axum::{Json, extract::Path, http::StatusCode, Extension, response::IntoResponse};
-use std::sync::{Arc, RwLock};
+use axum::{
+    body::Bytes,
+    extract::rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection},
+    extract::ws::{close_code, rejection::WebSocketUpgradeRejection, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    response::IntoResponse,
+    Extension, Json,
+};
+use futures_util::StreamExt;
 
-use crate::store::{TicketStore, TicketId};
-use crate::data::{TicketDraft, TicketPatch};
+use crate::batch::{BatchRequest, BatchResponse, MAX_BATCH_SIZE};
+use crate::bulk::{ExportParams, ImportParams};
+use crate::comment::{CommentDraft, CommentEdit, CommentId};
+use crate::data::{TicketDraft, TicketPatch, TicketQuery};
+use crate::error::ApiError;
+use crate::events::{EventsParams, Subscription};
+use crate::history::Actor;
+use crate::link::{LinkKind, TicketLink};
+use crate::patch::PatchDocument;
+use crate::repository::{RepositoryError, TicketRepository};
+use crate::search::{SearchParams, SearchQuery};
+use crate::server::ShutdownSignal;
+use crate::store::{TicketId, TicketStoreError};
+use crate::user::{User, UserId};
+use crate::webhook::{DeliveryId, WebhookDraft, WebhookId, Webhooks};
+use ticket_fields::TicketLabel;
+
+// The handlers are generic over the storage backend (see `TicketRepository`):
+// the server picks the concrete type when building the router
 
 // Handler for POST /tickets - add a new ticket
-pub async fn add_ticket(
-    Extension(store): Extension<Arc<RwLock<TicketStore>>>,
-    Json(draft): Json<TicketDraft>, 
+pub async fn add_ticket<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    // Taking the rejection lets us turn invalid payloads into an `ApiError` (422)
+    // instead of axum's default plain-text response
+    payload: Result<Json<TicketDraft>, JsonRejection>,
 // the function returns some type that implements the specified trait (IntoResponse), without exposing the exact type
-) -> impl IntoResponse {
-    let ticket_id: TicketId = tokio::spawn(async move {
-        store.write().unwrap().add_ticket(draft)
-    })
-    .await
-    .expect("Created new ticket failed");
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(draft) = payload?;
+
+    let ticket_id: TicketId = repository.insert(draft).await?;
+
+    Ok((StatusCode::CREATED, Json(ticket_id)))
+}
+
+// Handler for GET /tickets - list tickets, filtered and paginated by the query parameters
+pub async fn list_tickets<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    query: Result<Query<TicketQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(query) = query?;
+
+    let page = repository.list(query).await?;
+
+    Ok((StatusCode::OK, Json(page)))
+}
+
+// Handler for GET /tickets/search?q= - full-text search over the titles and descriptions,
+// the most relevant tickets first
+pub async fn search_tickets<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    params: Result<Query<SearchParams>, QueryRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(params) = params?;
+    let query = SearchQuery::try_from(params.q).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+    let hits = repository.search(query, params.limit).await?;
+
+    Ok((StatusCode::OK, Json(hits)))
+}
+
+// Handler for POST /tickets/batch - apply a list of create/patch/delete operations atomically.
+// If any of them fails, nothing changes and the failures are reported (409).
+pub async fn apply_batch<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    headers: HeaderMap,
+    payload: Result<Json<BatchRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = payload?;
+    if request.operations.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::Validation(format!(
+            "A batch can hold at most {} operations, not {}",
+            MAX_BATCH_SIZE,
+            request.operations.len()
+        )));
+    }
+
+    let outcomes = repository.apply_batch(request.operations, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, Json(BatchResponse { outcomes })))
+}
+
+// Handler for POST /tickets/import?format=&ids= - add the tickets of a JSON Lines (by default)
+// or CSV body. The records that can't be imported are listed in the report, with their line.
+pub async fn import_tickets<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    params: Result<Query<ImportParams>, QueryRejection>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(params) = params?;
+    let body = body?;
+
+    let report = repository.import(params, body.to_vec()).await?;
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+// Handler for GET /tickets/export?format= - every ticket, archived ones included
+pub async fn export_tickets<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    params: Result<Query<ExportParams>, QueryRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(params) = params?;
+
+    let body = repository.export(params.format).await?;
+
+    Ok((StatusCode::OK, [(header::CONTENT_TYPE, params.format.content_type())], body))
+}
+
+// Handler for GET /tickets/events?status=&label=&since= - the changes to the tickets, as server-sent
+// events (see `events`). The id of each event is its sequence number: browsers that reconnect send
+// the last one they got in `Last-Event-ID`, and the stream resumes right after it.
+pub async fn stream_events<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    Extension(shutdown): Extension<ShutdownSignal>,
+    params: Result<Query<EventsParams>, QueryRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(params) = params?;
+    let since = match params.since {
+        Some(since) => Some(since),
+        None => last_event_id(&headers)?,
+    };
+
+    let subscription = repository.events().await?.subscribe(since, params.filter())?;
+
+    let events = futures_util::stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        let sse = Event::default()
+            .id(event.seq.to_string())
+            .event(event.event.kind())
+            .json_data(&event);
+        Some((sse, subscription))
+    });
+    Ok(Sse::new(events.take_until(shutdown.wait())).keep_alive(KeepAlive::default()))
+}
+
+// Header sent by the browsers that reconnect to a stream of server-sent events
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+fn last_event_id(headers: &HeaderMap) -> Result<Option<u64>, ApiError> {
+    let Some(value) = headers.get(LAST_EVENT_ID_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str().ok().and_then(|value| value.trim().parse().ok()) {
+        Some(seq) => Ok(Some(seq)),
+        None => Err(ApiError::BadRequest(format!(
+            "{} must be the sequence number of an event, not {:?}",
+            LAST_EVENT_ID_HEADER, value
+        ))),
+    }
+}
+
+// Handler for GET /tickets/events/ws?status=&label=&since= - the same events over a WebSocket,
+// one JSON text message each. The messages of the client are ignored.
+pub async fn stream_events_ws<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    Extension(shutdown): Extension<ShutdownSignal>,
+    params: Result<Query<EventsParams>, QueryRejection>,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(params) = params?;
+    let upgrade = upgrade.map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+
+    // Subscribe before upgrading, so that a stream that can't be resumed is an HTTP error
+    let subscription = repository.events().await?.subscribe(params.since, params.filter())?;
+
+    Ok(upgrade.on_upgrade(move |socket| send_events(socket, subscription, shutdown)))
+}
 
-    (StatusCode::CREATED, Json(ticket_id))
+// Forward the events to the socket until the client leaves, falls behind, or the server shuts down
+async fn send_events(mut socket: WebSocket, mut subscription: Subscription, shutdown: ShutdownSignal) {
+    let shutdown = shutdown.wait();
+    tokio::pin!(shutdown);
+    let close = loop {
+        tokio::select! {
+            event = subscription.next() => match event {
+                Some(event) => {
+                    let json = serde_json::to_string(&event).expect("events serialize to JSON");
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                None => break CloseFrame {
+                    code: close_code::AGAIN,
+                    reason: "Too far behind: resume from the last event received".into(),
+                },
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            _ = &mut shutdown => break CloseFrame {
+                code: close_code::AWAY,
+                reason: "The server is shutting down".into(),
+            },
+        }
+    };
+    let _ = socket.send(Message::Close(Some(close))).await;
 }
 
 // Handler for GET /tickets/:id - get ticket by ID
-pub async fn get_ticket(
-    Extension(store): Extension<Arc<RwLock<TicketStore>>>,
-    Path(id): Path<u64>,
-) -> impl IntoResponse {
-    // Acquire the read lock for the TicketStore
-    let ts_reader = store.read().expect("Cannot get read lock for TicketStore");
-
-    let ticket_id = TicketId(id);
-    let ticket_locked = ts_reader.get(ticket_id).expect("Cannot find ticket in TicketStore");
-
-    // Acquire the read lock for the Ticket
-    let ticket_reader = ticket_locked.read().expect("Cannot get read lock for Ticket");
-    // RwLockReadGuard only provides a read-only, temporary view
-    let ticket = ticket_reader.clone();
-
-    (StatusCode::OK, Json(ticket))
-}
-
-// Handler for POST /tickets - patch an existing ticket
-pub async fn patch_ticket(
-    Extension(store): Extension<Arc<RwLock<TicketStore>>>,
-    Json(patch): Json<TicketPatch>,
-) -> impl IntoResponse {
-    let _ = tokio::spawn(async move {
-        store.write().unwrap().get_mut(patch)
+pub async fn get_ticket<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let ticket = repository.get(TicketId(id)).await?;
+
+    Ok((StatusCode::OK, [(header::ETAG, etag(ticket.version))], Json(ticket)))
+}
+
+// The ETag of a ticket is its version. It changes with every modification of the ticket,
+// so it's a strong validator.
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+// Whether an `If-Match` header (`*` or a list of ETags) accepts the given version.
+// Weak ETags (W/"...") never match: `If-Match` uses the strong comparison.
+fn if_match_accepts(if_match: &str, version: u64) -> bool {
+    let etag = etag(version);
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+// Header naming who makes a change, recorded in the history of the ticket
+pub const ACTOR_HEADER: &str = "x-actor";
+
+// Requests without an `X-Actor` header are attributed to "anonymous"
+fn actor(headers: &HeaderMap) -> Actor {
+    headers
+        .get(ACTOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(Actor::new)
+        .unwrap_or_else(|| Actor::new("anonymous"))
+}
+
+// Handler for POST /tickets/patch - patch an existing ticket
+pub async fn patch_ticket<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    headers: HeaderMap,
+    payload: Result<Json<TicketPatch>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(patch) = payload?;
+
+    repository.patch(patch, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, ()))
+}
+
+// Handler for PATCH /tickets/:id - apply a JSON Merge Patch (RFC 7396) or a JSON Patch (RFC 6902),
+// depending on the content type, and return the updated ticket.
+// With an `If-Match` header, the patch is only applied if the ticket is at that version (412 otherwise).
+pub async fn patch_ticket_by_id<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+    let body = body?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let document = PatchDocument::parse(content_type, &body)?;
+    let if_match = match headers.get(header::IF_MATCH) {
+        Some(value) => Some(value.to_str().map_err(|err| ApiError::BadRequest(err.to_string()))?),
+        None => None,
+    };
+
+    let ticket = repository.get(TicketId(id)).await?;
+    if let Some(if_match) = if_match {
+        if !if_match_accepts(if_match, ticket.version) {
+            return Err(ApiError::PreconditionFailed(format!(
+                "Ticket {} is at version {}, which doesn't match If-Match: {}",
+                ticket.id, ticket.version, if_match
+            )));
+        }
+    }
+
+    // The patch is computed from the ticket we just read: it must not be applied
+    // if the ticket was changed in the meantime
+    let patch = document.apply(&ticket)?;
+    let ticket = match repository.patch_if_version(patch, ticket.version, actor(&headers)).await {
+        Ok(ticket) => ticket,
+        // The client didn't ask for a precondition: it's a conflict, that it can retry
+        Err(RepositoryError::Store(err @ TicketStoreError::VersionMismatch { .. })) if if_match.is_none() => {
+            return Err(ApiError::Conflict(format!("{}: the ticket was modified concurrently", err)));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok((StatusCode::OK, [(header::ETAG, etag(ticket.version))], Json(ticket)))
+}
+
+// Handler for GET /tickets/:id/history - the changes made to a ticket, oldest first.
+// The history of a removed ticket is still available.
+pub async fn get_ticket_history<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let history = repository.history(TicketId(id)).await?;
+    if history.is_empty() {
+        // 404 for an unknown ticket, an empty history for a ticket that was never changed
+        repository.get(TicketId(id)).await?;
+    }
+
+    Ok((StatusCode::OK, Json(history)))
+}
+
+// Handler for POST /tickets/:id/comments - comment on a ticket, or reply to one of its comments.
+// The author is the `X-Actor` of the request.
+pub async fn add_comment<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+    headers: HeaderMap,
+    payload: Result<Json<CommentDraft>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+    let Json(draft) = payload?;
+
+    let comment = repository.add_comment(TicketId(id), draft, actor(&headers)).await?;
+
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+// Handler for GET /tickets/:id/comments - the comments of a ticket, oldest first
+pub async fn list_comments<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let comments = repository.comments(TicketId(id)).await?;
+
+    Ok((StatusCode::OK, Json(comments)))
+}
+
+// Handler for PATCH /tickets/:id/comments/:comment_id - edit the body of a comment
+pub async fn edit_comment<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    ids: Result<Path<(u64, u64)>, PathRejection>,
+    headers: HeaderMap,
+    payload: Result<Json<CommentEdit>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path((id, comment_id)) = ids?;
+    let Json(edit) = payload?;
+
+    let comment = repository
+        .edit_comment(TicketId(id), CommentId(comment_id), edit.body, actor(&headers))
+        .await?;
+
+    Ok((StatusCode::OK, Json(comment)))
+}
+
+// Handler for DELETE /tickets/:id/comments/:comment_id - delete a comment.
+// Its replies are kept, and now reply to its parent.
+pub async fn delete_comment<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    ids: Result<Path<(u64, u64)>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path((id, comment_id)) = ids?;
+
+    repository
+        .delete_comment(TicketId(id), CommentId(comment_id), actor(&headers))
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler for PUT /tickets/:id/labels/:label - label a ticket, and return the updated ticket.
+// Labelling a ticket twice with the same label is harmless.
+pub async fn add_label<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    path: Result<Path<(u64, String)>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let (id, label) = label_path(path?)?;
+
+    let ticket = repository.add_label(id, label, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, [(header::ETAG, etag(ticket.version))], Json(ticket)))
+}
+
+// Handler for DELETE /tickets/:id/labels/:label - remove a label, and return the updated ticket
+pub async fn remove_label<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    path: Result<Path<(u64, String)>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let (id, label) = label_path(path?)?;
+
+    let ticket = repository.remove_label(id, label, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, [(header::ETAG, etag(ticket.version))], Json(ticket)))
+}
+
+fn label_path(Path((id, label)): Path<(u64, String)>) -> Result<(TicketId, TicketLabel), ApiError> {
+    let label = TicketLabel::try_from(label).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    Ok((TicketId(id), label))
+}
+
+// Handler for GET /tickets/:id/links - the links of a ticket, in both directions
+pub async fn list_links<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let links = repository.links(TicketId(id)).await?;
+
+    Ok((StatusCode::OK, Json(links)))
+}
+
+// Handler for PUT /tickets/:id/links/:kind/:target - link a ticket to another one,
+// e.g. PUT /tickets/3/links/blocks/5, and return the links of the ticket.
+// Adding the same link twice is harmless.
+pub async fn add_link<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    path: Result<Path<(u64, String, u64)>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let link = link_path(path?)?;
+
+    let links = repository.add_link(link, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, Json(links)))
+}
+
+// Handler for DELETE /tickets/:id/links/:kind/:target - remove a link, and return the links of the ticket
+pub async fn remove_link<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    path: Result<Path<(u64, String, u64)>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let link = link_path(path?)?;
+
+    let links = repository.remove_link(link, actor(&headers)).await?;
+
+    Ok((StatusCode::OK, Json(links)))
+}
+
+fn link_path(Path((source, kind, target)): Path<(u64, String, u64)>) -> Result<TicketLink, ApiError> {
+    let kind = LinkKind::try_from(kind).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    Ok(TicketLink {
+        source: TicketId(source),
+        kind,
+        target: TicketId(target),
     })
-    .await
-    .expect("Patch ticket failed");
+}
+
+// Handler for GET /tickets/:id/dependencies - the tree of the tickets that block a ticket
+pub async fn get_dependencies<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let tree = repository.dependencies(TicketId(id)).await?;
+
+    Ok((StatusCode::OK, Json(tree)))
+}
+
+// Handler for POST /users - register a user
+pub async fn add_user<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    payload: Result<Json<User>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    // A blank name, like an invalid id, is rejected when the body is deserialized
+    let Json(user) = payload?;
+
+    let user = repository.insert_user(user).await?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+// Handler for GET /users - every registered user, by id
+pub async fn list_users<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+) -> Result<impl IntoResponse, ApiError> {
+    let users = repository.list_users().await?;
+
+    Ok((StatusCode::OK, Json(users)))
+}
+
+// Handler for GET /users/:id
+pub async fn get_user<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<String>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = user_id(id?)?;
+
+    let user = repository.get_user(id).await?;
+
+    Ok((StatusCode::OK, Json(user)))
+}
+
+// Handler for DELETE /users/:id - unregister a user, who must not be referred to by any ticket
+pub async fn delete_user<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<String>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = user_id(id?)?;
+
+    repository.delete_user(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn user_id(Path(id): Path<String>) -> Result<UserId, ApiError> {
+    UserId::try_from(id).map_err(|err| ApiError::BadRequest(err.to_string()))
+}
+
+// Handler for DELETE /tickets/:id - remove a ticket
+pub async fn delete_ticket<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    repository.delete(TicketId(id)).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler for POST /tickets/:id/archive - archive a ticket
+pub async fn archive_ticket<R: TicketRepository>(
+    Extension(repository): Extension<R>,
+    id: Result<Path<u64>, PathRejection>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    let ticket = repository.archive(TicketId(id), actor(&headers)).await?;
+
+    Ok((StatusCode::OK, Json(ticket)))
+}
+
+// Handler for POST /webhooks - register a URL to notify of the changes to the tickets
+pub async fn register_webhook(
+    Extension(webhooks): Extension<Webhooks>,
+    payload: Result<Json<WebhookDraft>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(draft) = payload?;
+
+    let webhook = webhooks.register(draft)?;
+
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+// Handler for GET /webhooks - the registered webhooks, without their secrets
+pub async fn list_webhooks(Extension(webhooks): Extension<Webhooks>) -> impl IntoResponse {
+    (StatusCode::OK, Json(webhooks.list()))
+}
+
+// Handler for DELETE /webhooks/:id - stop notifying a webhook
+pub async fn unregister_webhook(
+    Extension(webhooks): Extension<Webhooks>,
+    id: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+
+    webhooks.unregister(WebhookId(id))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler for GET /webhooks/dead-letters - the deliveries that failed every attempt, oldest first
+pub async fn list_dead_letters(Extension(webhooks): Extension<Webhooks>) -> impl IntoResponse {
+    (StatusCode::OK, Json(webhooks.dead_letters()))
+}
+
+// Handler for POST /webhooks/dead-letters/:delivery/redeliver - try a dead letter again, in the background
+pub async fn redeliver_webhook(
+    Extension(webhooks): Extension<Webhooks>,
+    delivery: Result<Path<u64>, PathRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(delivery) = delivery?;
+
+    webhooks.redeliver(DeliveryId(delivery))?;
 
-    (StatusCode::OK, ()) 
+    Ok(StatusCode::ACCEPTED)
 }
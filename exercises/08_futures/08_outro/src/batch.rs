@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::store::{TicketId, TicketStoreError};
+
+// Batches of operations, e.g. to triage dozens of tickets at once with POST /tickets/batch.
+//
+// A batch is atomic: either every operation applies, or none does. The operations apply in
+// order, each one seeing the effects of those before it: a batch can patch a ticket it created,
+// but not one it deleted. When some operations fail, all the failures are reported, not only
+// the first one.
+
+// The most operations a batch can hold
+pub const MAX_BATCH_SIZE: usize = 500;
+
+// Body of POST /tickets/batch, e.g.
+// {"operations": [{"op": "create", "draft": {...}}, {"op": "delete", "id": 3}]}
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+// Response of POST /tickets/batch, when the batch was applied
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub outcomes: Vec<BatchOutcome>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create {
+        draft: TicketDraft,
+    },
+    // Applied only if the ticket is still at `version`, when set (see `TicketStore::patch_if_version`)
+    Patch {
+        patch: TicketPatch,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+    },
+    Delete {
+        id: TicketId,
+    },
+}
+
+// What an operation did, at the same position as the operation in the batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Created { id: TicketId },
+    Patched { ticket: Ticket },
+    Deleted { ticket: Ticket },
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("{} of the {total} operations of the batch failed: none was applied", .failures.len())]
+pub struct BatchError {
+    pub total: usize,
+    pub failures: Vec<OperationFailure>,
+}
+
+// An operation of a batch that failed, by its position in the batch (starting at 0)
+#[derive(Debug, PartialEq)]
+pub struct OperationFailure {
+    pub index: usize,
+    pub error: TicketStoreError,
+}
@@ -0,0 +1,189 @@
+use clap::{Parser, Subcommand};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, RwLock};
+
+use outro_08::bulk::{Format, IdPolicy, ImportParams};
+use outro_08::config::{StorageKind, TicketServerConfig};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::repository::TicketRepository;
+use outro_08::server::start_server;
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::TicketStore;
+
+/// Run the ticket service, or manage its data directory.
+///
+/// Settings are read from the config file (`--config`), then from the environment
+/// (TICKET_SERVER_*), then from the command-line flags: the last one wins.
+#[derive(Parser)]
+#[command(name = "ticket-server", version)]
+struct Cli {
+    /// Path to a TOML config file
+    #[arg(long, global = true, env = "TICKET_SERVER_CONFIG")]
+    config: Option<PathBuf>,
+    /// Where the tickets are stored. Without it, tickets are kept in memory
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Storage backend used in the data directory: `wal` or `sqlite`
+    #[arg(long, global = true, value_parser = parse_storage)]
+    storage: Option<StorageKind>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the REST API
+    Serve {
+        /// Address to listen on, e.g. 127.0.0.1:3000
+        #[arg(long)]
+        bind: Option<std::net::SocketAddr>,
+    },
+    /// Add the users and tickets of a JSON Lines file (or stdin) to the store, in the format
+    /// of GET /tickets/export. Tickets and comments get new ids, and links follow them;
+    /// users that already exist are kept as they are.
+    Import { file: Option<PathBuf> },
+    /// Write every user, then every ticket (archived ones included) with its comments and links,
+    /// as JSON Lines to a file (or stdout), like GET /tickets/export
+    Export { file: Option<PathBuf> },
+    /// Shrink the data directory: snapshot the write-ahead log, or vacuum the SQLite database
+    Compact,
+}
+
+fn parse_storage(value: &str) -> Result<StorageKind, String> {
+    StorageKind::try_from(value).map_err(|err| err.to_string())
+}
+
+const SQLITE_FILE: &str = "tickets.db";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+async fn run(cli: Cli) -> Result<(), BoxError> {
+    let mut config = TicketServerConfig::load(cli.config.as_deref())?;
+    if let Some(data_dir) = cli.data_dir {
+        config.data_dir = Some(data_dir);
+    }
+    if let Some(storage) = cli.storage {
+        config.storage = storage;
+    }
+    if let Command::Serve { bind: Some(bind) } = &cli.command {
+        config.bind = *bind;
+    }
+
+    let workflow = config.workflow.workflow();
+
+    let Some(data_dir) = config.data_dir.clone() else {
+        return match cli.command {
+            Command::Serve { .. } => {
+                println!("No data directory: tickets are kept in memory");
+                let store = TicketStore::new().with_workflow(workflow);
+                serve(&config, Arc::new(RwLock::new(store))).await
+            }
+            _ => Err("this command needs a data directory (--data-dir)".into()),
+        };
+    };
+
+    if let Command::Compact = cli.command {
+        return compact(&config, &data_dir);
+    }
+
+    match config.storage {
+        StorageKind::Wal => {
+            let store = PersistentTicketStore::open(&data_dir)?
+                .compact_every(config.compact_every)
+                .with_workflow(workflow);
+            let repository = Arc::new(RwLock::new(store));
+            let result = dispatch(cli.command, &config, repository.clone()).await;
+            if let Some(err) = repository.read().unwrap().last_compaction_error() {
+                eprintln!("Warning: the write-ahead log could not be compacted: {}", err);
+            }
+            result
+        }
+        StorageKind::Sqlite => {
+            std::fs::create_dir_all(&data_dir)?;
+            let store = SqliteTicketStore::open(data_dir.join(SQLITE_FILE))?.with_workflow(workflow);
+            dispatch(cli.command, &config, Arc::new(Mutex::new(store))).await
+        }
+    }
+}
+
+async fn dispatch<R: TicketRepository>(
+    command: Command,
+    config: &TicketServerConfig,
+    repository: R,
+) -> Result<(), BoxError> {
+    match command {
+        Command::Serve { .. } => serve(config, repository).await,
+        Command::Import { file } => {
+            let body = match file {
+                Some(path) => std::fs::read(path)?,
+                None => {
+                    let mut body = Vec::new();
+                    io::stdin().read_to_end(&mut body)?;
+                    body
+                }
+            };
+            let params = ImportParams {
+                format: Format::Jsonl,
+                ids: IdPolicy::Remap,
+            };
+            let report = repository.import(params, body).await?;
+            eprintln!("Imported {} users and {} tickets", report.users.len(), report.imported.len());
+            for error in &report.errors {
+                eprintln!("line {}: {}", error.line, error.message);
+            }
+            if !report.errors.is_empty() {
+                return Err(format!("{} records could not be imported", report.errors.len()).into());
+            }
+            Ok(())
+        }
+        Command::Export { file } => {
+            let body = repository.export(Format::Jsonl).await?;
+            match file {
+                Some(path) => std::fs::write(path, body)?,
+                None => io::stdout().write_all(&body)?,
+            }
+            Ok(())
+        }
+        Command::Compact => unreachable!("handled before opening the store"),
+    }
+}
+
+async fn serve<R: TicketRepository>(
+    config: &TicketServerConfig,
+    repository: R,
+) -> Result<(), BoxError> {
+    let server = start_server(config.server_config(), repository).await?;
+
+    // Run until Ctrl+C, then let the in-flight requests complete
+    tokio::signal::ctrl_c().await?;
+    println!("Shutting down");
+    let skipped = server.webhooks().skipped_events();
+    if skipped > 0 {
+        eprintln!("Warning: {} events were not delivered to the webhooks: the dispatcher fell behind", skipped);
+    }
+    server.shutdown().await?;
+    Ok(())
+}
+
+fn compact(config: &TicketServerConfig, data_dir: &Path) -> Result<(), BoxError> {
+    match config.storage {
+        StorageKind::Wal => PersistentTicketStore::open(data_dir)?.compact()?,
+        StorageKind::Sqlite => SqliteTicketStore::open(data_dir.join(SQLITE_FILE))?.vacuum()?,
+    }
+    eprintln!("Compacted {}", data_dir.display());
+    Ok(())
+}
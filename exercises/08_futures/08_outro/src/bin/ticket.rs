@@ -0,0 +1,630 @@
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::StatusCode;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use outro_08::batch::{BatchOutcome, BatchRequest};
+use outro_08::bulk::{ExportParams, Format, IdPolicy, ImportParams};
+use outro_08::client::{ClientError, TicketApiClient};
+use outro_08::events::{EventsParams, SequencedEvent, TicketEvent};
+use outro_08::filter::Filter;
+use outro_08::history::{Actor, HistoryEntry};
+use outro_08::link::{DependencyTree, LinkKind, TicketLink, TicketLinks};
+use outro_08::data::{OrderBy, Priority, SortOrder, Status, Ticket, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::search::SearchParams;
+use outro_08::store::TicketId;
+use outro_08::user::UserId;
+use ticket_fields::{TicketDescription, TicketLabel, TicketTitle};
+
+/// Manage tickets on a running ticket server.
+///
+/// Exit codes: 0 on success, 2 on invalid usage, 3 if the ticket doesn't exist,
+/// 4 if the input is rejected, 5 on a conflict, 6 on a server error, 1 otherwise.
+#[derive(Parser)]
+#[command(name = "ticket", version)]
+struct Cli {
+    /// Root URL of the ticket server
+    #[arg(long, global = true, env = "TICKET_SERVER_URL", default_value = "http://127.0.0.1:3000")]
+    server: String,
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    /// Who the changes are attributed to, in the history of the tickets
+    #[arg(long, global = true, env = "TICKET_ACTOR")]
+    actor: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a ticket
+    Create {
+        #[arg(long)]
+        title: String,
+        #[arg(long)]
+        description: String,
+        /// Id of a registered user
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Id of a registered user
+        #[arg(long)]
+        reporter: Option<String>,
+        /// P0 (most urgent) to P3
+        #[arg(long)]
+        priority: Option<String>,
+        /// Due date, YYYY-MM-DD
+        #[arg(long)]
+        due: Option<String>,
+    },
+    /// Show a ticket
+    Show { id: u64 },
+    /// Change the title, description, status, assignee, priority or due date of a ticket
+    Edit {
+        id: u64,
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+        #[arg(long)]
+        status: Option<String>,
+        /// Id of a registered user
+        #[arg(long, conflicts_with = "unassign")]
+        assignee: Option<String>,
+        /// Remove the assignee of the ticket
+        #[arg(long)]
+        unassign: bool,
+        /// P0 (most urgent) to P3
+        #[arg(long)]
+        priority: Option<String>,
+        /// Due date, YYYY-MM-DD
+        #[arg(long, conflicts_with = "clear_due")]
+        due: Option<String>,
+        /// Remove the due date of the ticket
+        #[arg(long)]
+        clear_due: bool,
+    },
+    /// Move a ticket to another status (todo, inprogress, done, blocked, inreview, cancelled),
+    /// if the workflow of the server allows it
+    Transition { id: u64, status: String },
+    /// Add a label to a ticket
+    Label { id: u64, label: String },
+    /// Remove a label from a ticket
+    Unlabel { id: u64, label: String },
+    /// Link a ticket to another one, e.g. `link 3 blocks 5` (kinds: parent_of, blocks, duplicates)
+    Link { id: u64, kind: String, target: u64 },
+    /// Remove a link between two tickets
+    Unlink { id: u64, kind: String, target: u64 },
+    /// Show the links of a ticket, in both directions
+    Links { id: u64 },
+    /// Show the tickets that block a ticket, and the ones that block them, as a tree
+    Deps { id: u64 },
+    /// Show the changes made to a ticket, oldest first
+    History { id: u64 },
+    /// List tickets
+    List {
+        #[arg(long)]
+        status: Option<String>,
+        /// Only tickets assigned to this user
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Only tickets reported by this user
+        #[arg(long)]
+        reporter: Option<String>,
+        /// Only tickets with this label
+        #[arg(long)]
+        label: Option<String>,
+        /// Only tickets whose title contains this text
+        #[arg(long)]
+        title: Option<String>,
+        /// Only open tickets past their due date
+        #[arg(long)]
+        overdue: bool,
+        /// Only tickets matching a filter, e.g. `status:InProgress AND NOT title~"flaky"`
+        #[arg(long, short)]
+        query: Option<String>,
+        /// Most urgent tickets first: by priority, then by due date
+        #[arg(long)]
+        by_priority: bool,
+        /// Newest (or least urgent, with --by-priority) tickets first
+        #[arg(long)]
+        desc: bool,
+        /// Include archived tickets
+        #[arg(long)]
+        all: bool,
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Id of the last ticket of the previous page
+        #[arg(long)]
+        cursor: Option<u64>,
+    },
+    /// Search the titles and descriptions, e.g. `search "login error OR crash*"`, the best matches first
+    Search {
+        query: String,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Apply the create/patch/delete operations of a JSON file, all of them or none.
+    ///
+    /// The file holds the body of POST /tickets/batch: {"operations": [{"op": "delete", "id": 3}, ...]}
+    Batch { file: PathBuf },
+    /// Import tickets from a JSON Lines or CSV file. Invalid records are reported and skipped.
+    Import {
+        file: PathBuf,
+        /// jsonl or csv
+        #[arg(long, default_value = "jsonl")]
+        format: String,
+        /// Keep the ids of the file, instead of giving the tickets new ones
+        #[arg(long)]
+        preserve_ids: bool,
+    },
+    /// Print every ticket, archived ones included, as JSON Lines or CSV
+    Export {
+        /// jsonl or csv
+        #[arg(long, default_value = "jsonl")]
+        format: String,
+    },
+    /// Print the changes to the tickets as they happen, until interrupted
+    Watch {
+        /// Only the tickets with this status
+        #[arg(long)]
+        status: Option<String>,
+        /// Only the tickets with this label
+        #[arg(long)]
+        label: Option<String>,
+        /// Sequence number of the last change seen: start right after it
+        #[arg(long)]
+        since: Option<u64>,
+    },
+}
+
+// Errors of the CLI, each with its own exit code
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::InvalidInput(_) => 4,
+            CliError::Client(err) => match err.status() {
+                Some(StatusCode::NOT_FOUND) => 3,
+                Some(StatusCode::BAD_REQUEST)
+                | Some(StatusCode::UNPROCESSABLE_ENTITY)
+                | Some(StatusCode::PAYLOAD_TOO_LARGE) => 4,
+                Some(StatusCode::CONFLICT) | Some(StatusCode::PRECONDITION_FAILED) => 5,
+                Some(status) if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT => 6,
+                _ => 1,
+            },
+            CliError::Json(_) | CliError::Io(_) => 1,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let mut client = TicketApiClient::new(cli.server);
+    if let Some(actor) = cli.actor {
+        client = client.actor(Actor::new(actor));
+    }
+
+    match cli.command {
+        Command::Create {
+            title,
+            description,
+            assignee,
+            reporter,
+            priority,
+            due,
+        } => {
+            let draft = TicketDraft {
+                title: parse_title(title)?,
+                description: parse_description(description)?,
+                assignee: assignee.map(parse_user).transpose()?,
+                reporter: reporter.map(parse_user).transpose()?,
+                priority: priority.map(parse_priority).transpose()?.unwrap_or_default(),
+                due_date: due.map(parse_date).transpose()?,
+            };
+            let id = client.create(&draft).await?;
+            let ticket = client.get(id).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Show { id } => {
+            let ticket = client.get(TicketId(id)).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Edit {
+            id,
+            title,
+            description,
+            status,
+            assignee,
+            unassign,
+            priority,
+            due,
+            clear_due,
+        } => {
+            let assignee = match assignee {
+                Some(assignee) => Some(Some(parse_user(assignee)?)),
+                None if unassign => Some(None),
+                None => None,
+            };
+            let due_date = match due {
+                Some(due) => Some(Some(parse_date(due)?)),
+                None if clear_due => Some(None),
+                None => None,
+            };
+            let patch = TicketPatch {
+                id: TicketId(id),
+                title: title.map(parse_title).transpose()?,
+                description: description.map(parse_description).transpose()?,
+                status: status.map(parse_status).transpose()?,
+                assignee,
+                reporter: None,
+                priority: priority.map(parse_priority).transpose()?,
+                due_date,
+            };
+            client.patch(&patch).await?;
+            let ticket = client.get(TicketId(id)).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Transition { id, status } => {
+            let patch = TicketPatch {
+                id: TicketId(id),
+                title: None,
+                description: None,
+                status: Some(parse_status(status)?),
+                assignee: None,
+                reporter: None,
+                priority: None,
+                due_date: None,
+            };
+            client.patch(&patch).await?;
+            let ticket = client.get(TicketId(id)).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Label { id, label } => {
+            let ticket = client.add_label(TicketId(id), &parse_label(label)?).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Unlabel { id, label } => {
+            let ticket = client.remove_label(TicketId(id), &parse_label(label)?).await?;
+            print_ticket(&ticket, cli.output)
+        }
+        Command::Link { id, kind, target } => {
+            let links = client.add_link(&parse_link(id, kind, target)?).await?;
+            print_links(&links, cli.output)
+        }
+        Command::Unlink { id, kind, target } => {
+            let links = client.remove_link(&parse_link(id, kind, target)?).await?;
+            print_links(&links, cli.output)
+        }
+        Command::Links { id } => {
+            let links = client.links(TicketId(id)).await?;
+            print_links(&links, cli.output)
+        }
+        Command::Deps { id } => {
+            let tree = client.dependencies(TicketId(id)).await?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&tree)?),
+                Output::Table => print_tree(&tree, 0),
+            }
+            Ok(())
+        }
+        Command::History { id } => {
+            let history = client.history(TicketId(id)).await?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&history)?),
+                Output::Table => print_history(&history),
+            }
+            Ok(())
+        }
+        Command::List {
+            status,
+            assignee,
+            reporter,
+            label,
+            title,
+            overdue,
+            query,
+            by_priority,
+            desc,
+            all,
+            limit,
+            cursor,
+        } => {
+            let query = TicketQuery {
+                status: status.map(parse_status).transpose()?,
+                title,
+                sort: if desc { SortOrder::Desc } else { SortOrder::Asc },
+                cursor: cursor.map(TicketId),
+                limit,
+                include_archived: all,
+                assignee: assignee.map(parse_user).transpose()?,
+                reporter: reporter.map(parse_user).transpose()?,
+                label: label.map(parse_label).transpose()?,
+                order_by: if by_priority { OrderBy::Priority } else { OrderBy::Id },
+                overdue,
+                q: query.map(parse_filter).transpose()?,
+            };
+            let page = client.list(&query).await?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&page)?),
+                Output::Table => {
+                    print_table(&page.tickets);
+                    if let Some(cursor) = page.next_cursor {
+                        println!("More tickets: --cursor {}", cursor);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Search { query, limit } => {
+            let hits = client.search(&SearchParams { q: query, limit }).await?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&hits)?),
+                Output::Table => {
+                    let tickets: Vec<Ticket> = hits.into_iter().map(|hit| hit.ticket).collect();
+                    print_table(&tickets);
+                }
+            }
+            Ok(())
+        }
+        Command::Batch { file } => {
+            let request: BatchRequest = serde_json::from_slice(&std::fs::read(file)?)?;
+            let outcomes = match client.apply_batch(request.operations).await {
+                Ok(outcomes) => outcomes,
+                Err(ClientError::Api { status, body }) => {
+                    for failure in &body.operations {
+                        eprintln!("Operation {}: {}", failure.index, failure.message);
+                    }
+                    return Err(ClientError::Api { status, body }.into());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&outcomes)?),
+                Output::Table => {
+                    for outcome in &outcomes {
+                        match outcome {
+                            BatchOutcome::Created { id } => println!("Created ticket {}", id),
+                            BatchOutcome::Patched { ticket } => {
+                                println!("Patched ticket {} (version {})", ticket.id, ticket.version)
+                            }
+                            BatchOutcome::Deleted { ticket } => println!("Deleted ticket {}", ticket.id),
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Import {
+            file,
+            format,
+            preserve_ids,
+        } => {
+            let params = ImportParams {
+                format: parse_format(format)?,
+                ids: if preserve_ids { IdPolicy::Preserve } else { IdPolicy::Remap },
+            };
+            let report = client.import(&params, std::fs::read(file)?).await?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                Output::Table => {
+                    println!("Imported {} tickets", report.imported.len());
+                    for error in &report.errors {
+                        println!("Line {}: {}", error.line, error.message);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Export { format } => {
+            let params = ExportParams {
+                format: parse_format(format)?,
+            };
+            print!("{}", client.export(&params).await?);
+            Ok(())
+        }
+        Command::Watch { status, label, since } => {
+            let params = EventsParams {
+                status: status.map(parse_status).transpose()?,
+                label: label.map(parse_label).transpose()?,
+                since,
+            };
+            let mut events = client.events(&params).await?;
+            while let Some(event) = events.next().await? {
+                match cli.output {
+                    // One event per line, as they come
+                    Output::Json => println!("{}", serde_json::to_string(&event)?),
+                    Output::Table => print_event(&event),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn parse_title(title: String) -> Result<TicketTitle, CliError> {
+    TicketTitle::try_from(title).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_description(description: String) -> Result<TicketDescription, CliError> {
+    TicketDescription::try_from(description).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_status(status: String) -> Result<Status, CliError> {
+    Status::try_from(status).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_user(id: String) -> Result<UserId, CliError> {
+    UserId::try_from(id).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_priority(priority: String) -> Result<Priority, CliError> {
+    Priority::try_from(priority).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_date(date: String) -> Result<NaiveDate, CliError> {
+    date.parse()
+        .map_err(|err| CliError::InvalidInput(format!("`{}` is not a valid date (YYYY-MM-DD): {}", date, err)))
+}
+
+fn parse_label(label: String) -> Result<TicketLabel, CliError> {
+    TicketLabel::try_from(label).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_filter(filter: String) -> Result<Filter, CliError> {
+    Filter::try_from(filter).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_format(format: String) -> Result<Format, CliError> {
+    Format::try_from(format).map_err(|err| CliError::InvalidInput(err.to_string()))
+}
+
+fn parse_link(id: u64, kind: String, target: u64) -> Result<TicketLink, CliError> {
+    let kind = LinkKind::try_from(kind).map_err(|err| CliError::InvalidInput(err.to_string()))?;
+    Ok(TicketLink {
+        source: TicketId(id),
+        kind,
+        target: TicketId(target),
+    })
+}
+
+fn print_ticket(ticket: &Ticket, output: Output) -> Result<(), CliError> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(ticket)?),
+        Output::Table => {
+            println!("Id:          {}", ticket.id);
+            println!("Title:       {}", ticket.title.as_str());
+            println!("Status:      {}", ticket.status.as_str());
+            println!("Archived:    {}", if ticket.archived { "yes" } else { "no" });
+            println!("Version:     {}", ticket.version);
+            println!("Assignee:    {}", user_or_dash(&ticket.assignee));
+            println!("Reporter:    {}", user_or_dash(&ticket.reporter));
+            println!("Priority:    {}", ticket.priority.as_str());
+            println!("Due:         {}", date_or_dash(ticket.due_date));
+            let labels: Vec<&str> = ticket.labels.iter().map(TicketLabel::as_str).collect();
+            println!("Labels:      {}", if labels.is_empty() { "-".to_string() } else { labels.join(", ") });
+            println!("Description: {}", ticket.description.as_str());
+        }
+    }
+    Ok(())
+}
+
+fn print_table(tickets: &[Ticket]) {
+    println!(
+        "{:<8} {:<12} {:<9} {:<16} {:<4} {:<10} TITLE",
+        "ID", "STATUS", "ARCHIVED", "ASSIGNEE", "PRIO", "DUE"
+    );
+    for ticket in tickets {
+        println!(
+            "{:<8} {:<12} {:<9} {:<16} {:<4} {:<10} {}",
+            ticket.id.to_string(),
+            ticket.status.as_str(),
+            if ticket.archived { "yes" } else { "no" },
+            user_or_dash(&ticket.assignee),
+            ticket.priority.as_str(),
+            date_or_dash(ticket.due_date),
+            ticket.title.as_str()
+        );
+    }
+}
+
+fn user_or_dash(user: &Option<UserId>) -> &str {
+    user.as_ref().map_or("-", UserId::as_str)
+}
+
+fn date_or_dash(date: Option<NaiveDate>) -> String {
+    date.map_or_else(|| "-".to_string(), |date| date.to_string())
+}
+
+fn print_links(links: &TicketLinks, output: Output) -> Result<(), CliError> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(links)?),
+        Output::Table => {
+            let ids = |ids: &[TicketId]| match ids {
+                [] => "-".to_string(),
+                ids => ids.iter().map(TicketId::to_string).collect::<Vec<_>>().join(", "),
+            };
+            println!("Parent:        {}", ids(links.parent.as_slice()));
+            println!("Children:      {}", ids(&links.children));
+            println!("Blocks:        {}", ids(&links.blocks));
+            println!("Blocked by:    {}", ids(&links.blocked_by));
+            println!("Duplicates:    {}", ids(&links.duplicates));
+            println!("Duplicated by: {}", ids(&links.duplicated_by));
+        }
+    }
+    Ok(())
+}
+
+// One line per ticket, indented under the ticket it blocks
+fn print_tree(tree: &DependencyTree, depth: usize) {
+    println!(
+        "{}{} [{}] {}",
+        "  ".repeat(depth),
+        tree.id,
+        tree.status.as_str(),
+        tree.title.as_str()
+    );
+    for blocker in &tree.blocked_by {
+        print_tree(blocker, depth + 1);
+    }
+}
+
+fn print_event(event: &SequencedEvent) {
+    let ticket = event.event.ticket();
+    let changes = match &event.event {
+        TicketEvent::Patched { changes, .. } => changes
+            .iter()
+            .map(|change| format!("{}: {} -> {}", change.field, change.before, change.after))
+            .collect::<Vec<_>>()
+            .join(", "),
+        TicketEvent::Created { .. } | TicketEvent::Deleted { .. } => ticket.title.as_str().to_string(),
+    };
+    println!("#{:<6} {:<8} {:<8} {}", event.seq, event.event.kind(), ticket.id.to_string(), changes);
+}
+
+fn print_history(history: &[HistoryEntry]) {
+    println!("{:<8} {:<14} {:<16} CHANGES", "VERSION", "TIMESTAMP (MS)", "ACTOR");
+    for entry in history {
+        let changes: Vec<String> = entry
+            .changes
+            .iter()
+            .map(|change| format!("{}: {} -> {}", change.field, change.before, change.after))
+            .collect();
+        println!(
+            "{:<8} {:<14} {:<16} {}",
+            entry.version,
+            entry.timestamp,
+            entry.actor.as_str(),
+            changes.join(", ")
+        );
+    }
+}
@@ -0,0 +1,733 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use crate::comment::{CommentId, TicketComment};
+use crate::data::{Priority, Status, Ticket};
+use crate::history::Actor;
+use crate::link::TicketLink;
+use crate::store::{TicketId, TicketStoreError};
+use crate::user::{User, UserId};
+use ticket_fields::{CommentBody, TicketDescription, TicketLabel, TicketTitle};
+
+// Bulk import and export of tickets, to seed a store or to back it up.
+//
+// Two formats, one ticket per record:
+//  - JSON Lines: each line is a ticket, as returned by GET /tickets/:id, with its comments
+//    and the links from it (`comments` and `links`). The users come first, one per line,
+//    as returned by GET /users/:id: an export is a complete backup of the store.
+//  - CSV (RFC 4180), starting with a header that names the columns (see `CSV_COLUMNS`,
+//    in any order). The labels of a ticket are listed in a single cell, separated by commas.
+//    Users, comments and links are left out.
+//
+// Only the title and the description are required on import: a ticket without a status is
+// ToDo, one without a priority P2, and so on. Every record is validated on its own: an invalid
+// one is reported with its line number, and the others are imported anyway.
+// Imported tickets are restored as they are (the workflow isn't checked), at version 1 and
+// without history. Their comments get new ids, and keep their author, dates and thread.
+// Links are added once every ticket is in, following the ids of the tickets; with remapped ids,
+// a link to a ticket that isn't in the file is dropped. Users that already exist are kept as they are.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Jsonl => "application/x-ndjson",
+            Format::Csv => "text/csv",
+        }
+    }
+}
+
+// The parsing is case-insensitive
+impl TryFrom<&str> for Format {
+    type Error = ParseFormatError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            _ => Err(ParseFormatError {
+                invalid_format: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for Format {
+    type Error = ParseFormatError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Format::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{invalid_format}` is not a valid format. Use one of: jsonl, csv")]
+pub struct ParseFormatError {
+    invalid_format: String,
+}
+
+// What becomes of the ids of the imported tickets
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdPolicy {
+    // The tickets get new ids, as if they were created: the ids of the file are ignored
+    #[default]
+    Remap,
+    // The tickets keep the ids of the file, which are required. An id already taken is an error.
+    Preserve,
+}
+
+// Query parameters of POST /tickets/import, e.g. `/tickets/import?format=csv&ids=preserve`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub ids: IdPolicy,
+}
+
+// Query parameters of GET /tickets/export, e.g. `/tickets/export?format=csv`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: Format,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    // The ids of the imported tickets, in the order of the file
+    pub imported: Vec<TicketId>,
+    // The users registered by the import, in the order of the file
+    #[serde(default)]
+    pub users: Vec<UserId>,
+    pub errors: Vec<LineError>,
+}
+
+// Why a record wasn't imported. A CSV record may span several lines: `line` is the first one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineError {
+    pub line: usize,
+    pub message: String,
+}
+
+// The columns of the CSV format, in the order they are exported
+pub const CSV_COLUMNS: [&str; 10] = [
+    "id",
+    "title",
+    "description",
+    "status",
+    "archived",
+    "assignee",
+    "reporter",
+    "labels",
+    "priority",
+    "due_date",
+];
+
+// A ticket read from a file, once validated. `id` is `None` when the ids are remapped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TicketRecord {
+    pub id: Option<TicketId>,
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+    pub status: Status,
+    pub archived: bool,
+    pub assignee: Option<UserId>,
+    pub reporter: Option<UserId>,
+    pub labels: BTreeSet<TicketLabel>,
+    pub priority: Priority,
+    pub due_date: Option<NaiveDate>,
+    // In the order they were added: a reply comes after its parent
+    pub comments: Vec<CommentRecord>,
+}
+
+// A comment of an imported ticket. `id` is the one of the file, that its replies refer to
+// as their `parent_id`; the comment gets a new id once imported (see `number_comments`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommentRecord {
+    pub id: Option<CommentId>,
+    pub parent_id: Option<CommentId>,
+    pub author: Actor,
+    pub body: CommentBody,
+    // When missing, the comment is dated from the import
+    pub created_at: Option<u64>,
+    pub edited_at: Option<u64>,
+}
+
+impl TicketRecord {
+    pub fn into_ticket(self, id: TicketId) -> Ticket {
+        Ticket {
+            id,
+            title: self.title,
+            description: self.description,
+            status: self.status,
+            archived: self.archived,
+            version: 1,
+            assignee: self.assignee,
+            reporter: self.reporter,
+            labels: self.labels,
+            priority: self.priority,
+            due_date: self.due_date,
+        }
+    }
+}
+
+// Number the comments of an imported ticket from `first_id`, in order, and point the replies
+// to the new id of their parent
+pub(crate) fn number_comments(
+    records: Vec<CommentRecord>,
+    ticket_id: TicketId,
+    first_id: u64,
+    now: u64,
+) -> Vec<TicketComment> {
+    let mut ids = BTreeMap::new();
+    records
+        .into_iter()
+        .zip(first_id..)
+        .map(|(record, id)| {
+            let id = CommentId(id);
+            if let Some(file_id) = record.id {
+                ids.insert(file_id, id);
+            }
+            TicketComment {
+                id,
+                ticket_id,
+                parent_id: record.parent_id.and_then(|parent| ids.get(&parent).copied()),
+                author: record.author,
+                body: record.body,
+                created_at: record.created_at.unwrap_or(now),
+                edited_at: record.edited_at,
+            }
+        })
+        .collect()
+}
+
+// A ticket line of the JSON Lines format
+#[derive(Serialize)]
+struct TicketLine<'a> {
+    #[serde(flatten)]
+    ticket: &'a Ticket,
+    comments: &'a [TicketComment],
+    links: &'a [TicketLink],
+}
+
+// Writes tickets one at a time, as they are read from the store. With JSON Lines,
+// the users are written first (see `write_user`).
+pub struct TicketWriter<W: Write> {
+    writer: W,
+    format: Format,
+    written: usize,
+}
+
+impl<W: Write> TicketWriter<W> {
+    pub fn new(format: Format, mut writer: W) -> io::Result<Self> {
+        if format == Format::Csv {
+            write_csv_row(&mut writer, &CSV_COLUMNS)?;
+        }
+        Ok(Self {
+            writer,
+            format,
+            written: 0,
+        })
+    }
+
+    // CSV has no room for users: they are skipped
+    pub fn write_user(&mut self, user: &User) -> io::Result<()> {
+        if self.format == Format::Jsonl {
+            serde_json::to_writer(&mut self.writer, user)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    // A ticket, with its comments and the links from it (that CSV leaves out)
+    pub fn write(&mut self, ticket: &Ticket, comments: &[TicketComment], links: &[TicketLink]) -> io::Result<()> {
+        match self.format {
+            Format::Jsonl => {
+                let line = TicketLine {
+                    ticket,
+                    comments,
+                    links,
+                };
+                serde_json::to_writer(&mut self.writer, &line)?;
+                self.writer.write_all(b"\n")?;
+            }
+            Format::Csv => {
+                let labels: Vec<&str> = ticket.labels.iter().map(TicketLabel::as_str).collect();
+                let cells = [
+                    ticket.id.to_string(),
+                    ticket.title.as_str().to_string(),
+                    ticket.description.as_str().to_string(),
+                    ticket.status.as_str().to_string(),
+                    ticket.archived.to_string(),
+                    ticket.assignee.as_ref().map(|user| user.as_str().to_string()).unwrap_or_default(),
+                    ticket.reporter.as_ref().map(|user| user.as_str().to_string()).unwrap_or_default(),
+                    labels.join(","),
+                    ticket.priority.as_str().to_string(),
+                    ticket.due_date.map(|date| date.to_string()).unwrap_or_default(),
+                ];
+                write_csv_row(&mut self.writer, &cells)?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    // Flush the writer and return the number of tickets written
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.writer.flush()?;
+        Ok(self.written)
+    }
+}
+
+fn write_csv_row(writer: &mut impl Write, cells: &[impl AsRef<str>]) -> io::Result<()> {
+    let cells: Vec<String> = cells.iter().map(|cell| csv_cell(cell.as_ref())).collect();
+    writer.write_all(cells.join(",").as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+// Cells with a comma, a quote or a line break are quoted, with their quotes doubled
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// What an import adds to a store. Each method returns why the store rejected the item, if it did;
+// it fails (with `Error`) only if the store itself fails, which stops the import.
+pub(crate) trait ImportTarget {
+    type Error: From<io::Error>;
+
+    fn insert_user(&mut self, user: User) -> Result<Result<(), TicketStoreError>, Self::Error>;
+
+    // Returns the id of the ticket
+    fn insert_record(&mut self, record: TicketRecord) -> Result<Result<TicketId, TicketStoreError>, Self::Error>;
+
+    fn insert_link(&mut self, link: TicketLink) -> Result<Result<(), TicketStoreError>, Self::Error>;
+}
+
+// Read the records of `reader` and add the valid ones to `target`, then the links between them.
+// An I/O error stops the import.
+pub(crate) fn import_into<T: ImportTarget>(
+    target: &mut T,
+    format: Format,
+    reader: impl BufRead,
+    ids: IdPolicy,
+) -> Result<ImportReport, T::Error> {
+    let mut report = ImportReport::default();
+    let mut records = Records {
+        reader,
+        format,
+        line: 0,
+        columns: None,
+        stopped: false,
+    };
+    // The id of each ticket in the file and in the store, and the links to add at the end
+    // along with the line of their ticket
+    let mut ticket_ids = BTreeMap::new();
+    let mut links = Vec::new();
+    while let Some((line, record)) = records.next_record()? {
+        let rejected = match record {
+            Ok(Record::User(user)) => match target.insert_user(user.clone())? {
+                Ok(()) => {
+                    report.users.push(user.id);
+                    continue;
+                }
+                Err(TicketStoreError::UserAlreadyExists(_)) => continue,
+                Err(err) => err.to_string(),
+            },
+            Ok(Record::Ticket(record)) => {
+                let file_id = record.id.map(TicketId);
+                match (*record).validate(ids) {
+                    Ok((record, ticket_links)) => match target.insert_record(record)? {
+                        Ok(id) => {
+                            report.imported.push(id);
+                            if let Some(file_id) = file_id {
+                                ticket_ids.insert(file_id, id);
+                            }
+                            links.extend(ticket_links.into_iter().map(|link| (line, id, link)));
+                            continue;
+                        }
+                        Err(err) => err.to_string(),
+                    },
+                    Err(message) => message,
+                }
+            }
+            Err(message) => message,
+        };
+        report.errors.push(LineError { line, message: rejected });
+    }
+
+    for (line, source, link) in links {
+        let target_id = match ids {
+            IdPolicy::Remap => match ticket_ids.get(&link.target) {
+                Some(&id) => id,
+                None => continue,
+            },
+            IdPolicy::Preserve => link.target,
+        };
+        let link = TicketLink {
+            source,
+            target: target_id,
+            ..link
+        };
+        if let Err(err) = target.insert_link(link)? {
+            report.errors.push(LineError {
+                line,
+                message: format!("link {}: {}", link, err),
+            });
+        }
+    }
+    Ok(report)
+}
+
+// A record of the file, before validation
+enum Record {
+    User(User),
+    Ticket(Box<RawRecord>),
+}
+
+// A record as written in the file, before validation
+#[derive(Default, Deserialize)]
+struct RawRecord {
+    id: Option<u64>,
+    title: Option<String>,
+    description: Option<String>,
+    status: Option<String>,
+    archived: Option<bool>,
+    assignee: Option<String>,
+    reporter: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    priority: Option<String>,
+    due_date: Option<String>,
+    #[serde(default)]
+    comments: Vec<RawComment>,
+    // The links from the ticket: their `source` is the ticket of the record, whatever the file says
+    #[serde(default)]
+    links: Vec<TicketLink>,
+}
+
+#[derive(Deserialize)]
+struct RawComment {
+    id: Option<CommentId>,
+    parent_id: Option<CommentId>,
+    author: Option<Actor>,
+    body: Option<String>,
+    created_at: Option<u64>,
+    edited_at: Option<u64>,
+}
+
+// The comments of a record, each reply after its parent
+fn validate_comments(comments: Vec<RawComment>) -> Result<Vec<CommentRecord>, String> {
+    let mut ids = BTreeSet::new();
+    let mut records = Vec::new();
+    for (index, comment) in comments.into_iter().enumerate() {
+        let body = required(comment.body)
+            .and_then(|body| parse(CommentBody::try_from(body)))
+            .map_err(|message| format!("comment {}: {}", index, message))?;
+        if let Some(parent_id) = comment.parent_id.filter(|parent_id| !ids.contains(parent_id)) {
+            return Err(format!(
+                "comment {}: replies to comment {}, which doesn't come before it",
+                index, parent_id
+            ));
+        }
+        if let Some(id) = comment.id {
+            if !ids.insert(id) {
+                return Err(format!("comment {}: the id {} is already taken", index, id));
+            }
+        }
+        records.push(CommentRecord {
+            id: comment.id,
+            parent_id: comment.parent_id,
+            author: comment.author.unwrap_or_default(),
+            body,
+            created_at: comment.created_at,
+            edited_at: comment.edited_at,
+        });
+    }
+    Ok(records)
+}
+
+impl RawRecord {
+    // Every invalid field is reported, e.g. "title: The title cannot be empty; priority: ..."
+    fn validate(self, ids: IdPolicy) -> Result<(TicketRecord, Vec<TicketLink>), String> {
+        let mut errors = Vec::new();
+        let id = match (ids, self.id) {
+            (IdPolicy::Remap, _) => Some(None),
+            (IdPolicy::Preserve, Some(id)) => Some(Some(TicketId(id))),
+            (IdPolicy::Preserve, None) => {
+                check(&mut errors, "id", Err("missing, while the ids are preserved".to_string()))
+            }
+        };
+        let title = check(
+            &mut errors,
+            "title",
+            required(self.title).and_then(|title| parse(TicketTitle::try_from(title))),
+        );
+        let description = check(
+            &mut errors,
+            "description",
+            required(self.description).and_then(|description| parse(TicketDescription::try_from(description))),
+        );
+        let status = check(
+            &mut errors,
+            "status",
+            self.status.map_or(Ok(Status::ToDo), |status| parse(Status::try_from(status))),
+        );
+        let assignee = check(
+            &mut errors,
+            "assignee",
+            self.assignee.map(|user| parse(UserId::try_from(user))).transpose(),
+        );
+        let reporter = check(
+            &mut errors,
+            "reporter",
+            self.reporter.map(|user| parse(UserId::try_from(user))).transpose(),
+        );
+        let labels = check(
+            &mut errors,
+            "labels",
+            self.labels
+                .into_iter()
+                .map(|label| parse(TicketLabel::try_from(label)))
+                .collect(),
+        );
+        let priority = check(
+            &mut errors,
+            "priority",
+            self.priority.map_or(Ok(Priority::default()), |priority| parse(Priority::try_from(priority))),
+        );
+        let due_date = check(
+            &mut errors,
+            "due_date",
+            self.due_date
+                .map(|date| date.parse().map_err(|_| format!("`{}` is not a date (YYYY-MM-DD)", date)))
+                .transpose(),
+        );
+        let comments = check(&mut errors, "comments", validate_comments(self.comments));
+
+        match (id, title, description, status, assignee, reporter, labels, priority, due_date, comments) {
+            (
+                Some(id),
+                Some(title),
+                Some(description),
+                Some(status),
+                Some(assignee),
+                Some(reporter),
+                Some(labels),
+                Some(priority),
+                Some(due_date),
+                Some(comments),
+            ) => {
+                let record = TicketRecord {
+                    id,
+                    title,
+                    description,
+                    status,
+                    archived: self.archived.unwrap_or(false),
+                    assignee,
+                    reporter,
+                    labels,
+                    priority,
+                    due_date,
+                    comments,
+                };
+                Ok((record, self.links))
+            }
+            _ => Err(errors.join("; ")),
+        }
+    }
+}
+
+fn check<T>(errors: &mut Vec<String>, field: &str, result: Result<T, String>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(message) => {
+            errors.push(format!("{}: {}", field, message));
+            None
+        }
+    }
+}
+
+fn required(value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| "missing".to_string())
+}
+
+fn parse<T, E: std::fmt::Display>(result: Result<T, E>) -> Result<T, String> {
+    result.map_err(|err| err.to_string())
+}
+
+struct Records<R> {
+    reader: R,
+    format: Format,
+    // Number of lines read so far
+    line: usize,
+    // The columns named by the CSV header, once read
+    columns: Option<Vec<&'static str>>,
+    // Set when the CSV header is invalid: none of the rows can be read
+    stopped: bool,
+}
+
+impl<R: BufRead> Records<R> {
+    // The next record and the line it starts on, skipping blank lines
+    fn next_record(&mut self) -> io::Result<Option<(usize, Result<Record, String>)>> {
+        loop {
+            if self.stopped {
+                return Ok(None);
+            }
+            let Some(text) = self.read_line()? else {
+                return Ok(None);
+            };
+            let line = self.line;
+            let text = match text {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => text,
+                Err(message) => return Ok(Some((line, Err(message)))),
+            };
+            let record = match self.format {
+                Format::Jsonl => jsonl_record(&text),
+                Format::Csv => match self.read_csv_cells(text)? {
+                    Err(message) => Err(message),
+                    Ok(cells) => match &self.columns {
+                        Some(columns) => csv_record(columns, cells).map(|record| Record::Ticket(Box::new(record))),
+                        None => {
+                            match csv_header(cells) {
+                                Ok(columns) => self.columns = Some(columns),
+                                Err(message) => {
+                                    self.stopped = true;
+                                    return Ok(Some((line, Err(message))));
+                                }
+                            }
+                            continue;
+                        }
+                    },
+                },
+            };
+            return Ok(Some((line, record)));
+        }
+    }
+
+    // The next line, without its line break. A line that isn't valid UTF-8 is an error of its record.
+    fn read_line(&mut self) -> io::Result<Option<Result<String, String>>> {
+        let mut bytes = Vec::new();
+        if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        if bytes.ends_with(b"\n") {
+            bytes.pop();
+            if bytes.ends_with(b"\r") {
+                bytes.pop();
+            }
+        }
+        Ok(Some(String::from_utf8(bytes).map_err(|_| "the line is not valid UTF-8".to_string())))
+    }
+
+    // The cells of a CSV record, reading more lines while a quoted cell is open
+    fn read_csv_cells(&mut self, mut text: String) -> io::Result<Result<Vec<String>, String>> {
+        let mut cells = Vec::new();
+        let mut cell = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        cell.push('"');
+                        chars.next();
+                    }
+                    (true, '"') => quoted = false,
+                    (false, '"') if cell.is_empty() => quoted = true,
+                    (false, ',') => cells.push(std::mem::take(&mut cell)),
+                    (_, c) => cell.push(c),
+                }
+            }
+            if !quoted {
+                cells.push(cell);
+                return Ok(Ok(cells));
+            }
+            // The line break is part of the quoted cell
+            text = match self.read_line()? {
+                Some(Ok(text)) => text,
+                Some(Err(message)) => return Ok(Err(message)),
+                None => return Ok(Err("the quoted cell is not closed".to_string())),
+            };
+            cell.push('\n');
+        }
+    }
+}
+
+// A line with a `name` but no `title` is a user
+fn jsonl_record(text: &str) -> Result<Record, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|err| format!("not a ticket: {}", err))?;
+    if value.get("name").is_some() && value.get("title").is_none() {
+        return serde_json::from_value(value)
+            .map(Record::User)
+            .map_err(|err| format!("not a user: {}", err));
+    }
+    serde_json::from_value(value)
+        .map(Record::Ticket)
+        .map_err(|err| format!("not a ticket: {}", err))
+}
+
+fn csv_header(cells: Vec<String>) -> Result<Vec<&'static str>, String> {
+    let mut columns = Vec::new();
+    for cell in cells {
+        let name = cell.trim();
+        let column = CSV_COLUMNS
+            .into_iter()
+            .find(|column| *column == name)
+            .ok_or_else(|| format!("`{}` is not a column. Use: {}", name, CSV_COLUMNS.join(", ")))?;
+        if columns.contains(&column) {
+            return Err(format!("the column `{}` appears twice", column));
+        }
+        columns.push(column);
+    }
+    for required in ["title", "description"] {
+        if !columns.contains(&required) {
+            return Err(format!("the column `{}` is missing", required));
+        }
+    }
+    Ok(columns)
+}
+
+// Empty cells are missing values
+fn csv_record(columns: &[&'static str], cells: Vec<String>) -> Result<RawRecord, String> {
+    if cells.len() != columns.len() {
+        return Err(format!("expected {} cells, found {}", columns.len(), cells.len()));
+    }
+    let mut record = RawRecord::default();
+    for (&column, cell) in columns.iter().zip(cells) {
+        if cell.is_empty() && column != "title" && column != "description" {
+            continue;
+        }
+        match column {
+            "id" => record.id = Some(cell.parse().map_err(|_| format!("id: `{}` is not a number", cell))?),
+            "title" => record.title = Some(cell),
+            "description" => record.description = Some(cell),
+            "status" => record.status = Some(cell),
+            "archived" => {
+                record.archived = Some(cell.parse().map_err(|_| format!("archived: `{}` is not true or false", cell))?)
+            }
+            "assignee" => record.assignee = Some(cell),
+            "reporter" => record.reporter = Some(cell),
+            "labels" => record.labels = cell.split(',').map(|label| label.trim().to_string()).collect(),
+            "priority" => record.priority = Some(cell),
+            "due_date" => record.due_date = Some(cell),
+            _ => unreachable!("the columns come from CSV_COLUMNS"),
+        }
+    }
+    Ok(record)
+}
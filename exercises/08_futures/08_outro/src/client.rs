@@ -0,0 +1,468 @@
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::api::{etag, ACTOR_HEADER};
+use crate::batch::{BatchOperation, BatchOutcome, BatchRequest, BatchResponse};
+use crate::bulk::{ExportParams, ImportParams, ImportReport};
+use crate::comment::{CommentDraft, CommentEdit, CommentId, TicketComment};
+use crate::data::{Ticket, TicketDraft, TicketPage, TicketPatch, TicketQuery};
+use crate::error::ErrorBody;
+use crate::events::{EventsParams, SequencedEvent};
+use crate::history::{Actor, HistoryEntry};
+use crate::link::{DependencyTree, TicketLink, TicketLinks};
+use crate::patch::{JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE};
+use crate::retry::RetryPolicy;
+use crate::search::{SearchHit, SearchParams};
+use crate::store::TicketId;
+use crate::user::{User, UserId};
+use crate::webhook::{DeadLetter, DeliveryId, Webhook, WebhookDraft, WebhookId};
+use ticket_fields::TicketLabel;
+
+// A typed client for the REST API exposed by `server::start_server`, e.g.
+//   let client = TicketApiClient::new("http://127.0.0.1:3000");
+//   let id = client.create(&draft).await?;
+#[derive(Clone, Debug)]
+pub struct TicketApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    base_path: String,
+    retry: RetryPolicy,
+    actor: Option<Actor>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    // The request didn't get a response (server down, connection reset, ...)
+    // or the response couldn't be decoded
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    // The server answered with an error
+    #[error("{} ({})", .body.message, .status)]
+    Api { status: StatusCode, body: ErrorBody },
+    // An event of the change-feed that isn't valid JSON
+    #[error("Invalid event: {0}")]
+    InvalidEvent(#[from] serde_json::Error),
+}
+
+impl ClientError {
+    // Status code of the error response, if the server answered
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Http(err) => err.status(),
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::InvalidEvent(_) => None,
+        }
+    }
+
+    // Machine-readable error code sent by the server (see `ApiError::code`)
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Http(_) | ClientError::InvalidEvent(_) => None,
+            ClientError::Api { body, .. } => Some(&body.code),
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(err) => err.is_connect() || err.is_timeout(),
+            ClientError::Api { status, .. } => status.is_server_error(),
+            ClientError::InvalidEvent(_) => false,
+        }
+    }
+}
+
+impl TicketApiClient {
+    // `base_url` is the root of the server, e.g. "http://127.0.0.1:3000"
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self {
+            http,
+            base_url,
+            base_path: String::new(),
+            retry: RetryPolicy::default(),
+            actor: None,
+        }
+    }
+
+    // Prefix of every route, for a server mounted under a sub-path
+    // (e.g. "/api" behind a reverse proxy)
+    pub fn base_path(mut self, path: &str) -> Self {
+        let path = path.trim_matches('/');
+        self.base_path = if path.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", path)
+        };
+        self
+    }
+
+    // How requests are retried when the server fails (5xx) or can't be reached. Only requests that
+    // are safe to repeat are retried: reads, `patch`, `merge_patch` and `edit_comment`. Creating,
+    // archiving or deleting a ticket twice would not have the same effect as doing it once.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    // Who the changes made through this client are attributed to, in the history of the tickets
+    pub fn actor(mut self, actor: Actor) -> Self {
+        self.actor = Some(actor);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // POST /tickets
+    pub async fn create(&self, draft: &TicketDraft) -> Result<TicketId, ClientError> {
+        let request = self.http.post(self.url("/tickets")).json(draft);
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /tickets/:id
+    pub async fn get(&self, id: TicketId) -> Result<Ticket, ClientError> {
+        let request = self.http.get(self.url(&format!("/tickets/{}", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // POST /tickets/patch
+    pub async fn patch(&self, patch: &TicketPatch) -> Result<(), ClientError> {
+        let request = self.http.post(self.url("/tickets/patch")).json(patch);
+        self.send_with_retries(request).await?;
+        Ok(())
+    }
+
+    // PATCH /tickets/:id with a JSON Merge Patch, e.g. json!({"status": "Done"})
+    pub async fn merge_patch(&self, id: TicketId, patch: &serde_json::Value) -> Result<Ticket, ClientError> {
+        let request = self.merge_patch_request(id, patch);
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // Same as `merge_patch`, but only if the ticket is still at `version`:
+    // fails with a 412 (Precondition Failed) if someone else changed it in the meantime
+    pub async fn merge_patch_if_version(
+        &self,
+        id: TicketId,
+        version: u64,
+        patch: &serde_json::Value,
+    ) -> Result<Ticket, ClientError> {
+        let request = self
+            .merge_patch_request(id, patch)
+            .header(reqwest::header::IF_MATCH, etag(version));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    fn merge_patch_request(&self, id: TicketId, patch: &serde_json::Value) -> RequestBuilder {
+        self.http
+            .patch(self.url(&format!("/tickets/{}", id)))
+            .header(reqwest::header::CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE)
+            .body(serde_json::to_vec(patch).expect("a JSON value always serializes"))
+    }
+
+    // PATCH /tickets/:id with a JSON Patch.
+    // Not retried: applying the operations twice may not give the same ticket.
+    pub async fn json_patch(&self, id: TicketId, patch: &json_patch::Patch) -> Result<Ticket, ClientError> {
+        let request = self
+            .http
+            .patch(self.url(&format!("/tickets/{}", id)))
+            .header(reqwest::header::CONTENT_TYPE, JSON_PATCH_CONTENT_TYPE)
+            .body(serde_json::to_vec(patch).expect("a JSON Patch always serializes"));
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /tickets
+    pub async fn list(&self, query: &TicketQuery) -> Result<TicketPage, ClientError> {
+        let request = self.http.get(self.url("/tickets")).query(query);
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // GET /tickets/search?q=
+    pub async fn search(&self, params: &SearchParams) -> Result<Vec<SearchHit>, ClientError> {
+        let request = self.http.get(self.url("/tickets/search")).query(params);
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // POST /tickets/batch. Not retried: creating tickets twice isn't the same as once.
+    pub async fn apply_batch(&self, operations: Vec<BatchOperation>) -> Result<Vec<BatchOutcome>, ClientError> {
+        let request = self.http.post(self.url("/tickets/batch")).json(&BatchRequest { operations });
+        let response: BatchResponse = json(self.send_once(request).await?).await?;
+        Ok(response.outcomes)
+    }
+
+    // POST /tickets/import. Not retried: a retry would import the tickets again.
+    pub async fn import(&self, params: &ImportParams, body: impl Into<Vec<u8>>) -> Result<ImportReport, ClientError> {
+        let request = self
+            .http
+            .post(self.url("/tickets/import"))
+            .query(params)
+            .header(reqwest::header::CONTENT_TYPE, params.format.content_type())
+            .body(body.into());
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /tickets/export
+    pub async fn export(&self, params: &ExportParams) -> Result<String, ClientError> {
+        let request = self.http.get(self.url("/tickets/export")).query(params);
+        Ok(self.send_with_retries(request).await?.text().await?)
+    }
+
+    // GET /tickets/events: the changes to the tickets, as they happen (see `events`).
+    // Not retried: a stream that broke is resumed with `EventsParams::since` instead.
+    pub async fn events(&self, params: &EventsParams) -> Result<EventStream, ClientError> {
+        let request = self.http.get(self.url("/tickets/events")).query(params);
+        Ok(EventStream {
+            response: self.send_once(request).await?,
+            buffer: Vec::new(),
+        })
+    }
+
+    // GET /tickets/:id/history
+    pub async fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, ClientError> {
+        let request = self.http.get(self.url(&format!("/tickets/{}/history", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // POST /tickets/:id/comments
+    pub async fn add_comment(&self, id: TicketId, draft: &CommentDraft) -> Result<TicketComment, ClientError> {
+        let request = self.http.post(self.url(&format!("/tickets/{}/comments", id))).json(draft);
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /tickets/:id/comments
+    pub async fn comments(&self, id: TicketId) -> Result<Vec<TicketComment>, ClientError> {
+        let request = self.http.get(self.url(&format!("/tickets/{}/comments", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // PATCH /tickets/:id/comments/:comment_id
+    pub async fn edit_comment(
+        &self,
+        id: TicketId,
+        comment_id: CommentId,
+        edit: &CommentEdit,
+    ) -> Result<TicketComment, ClientError> {
+        let request = self
+            .http
+            .patch(self.url(&format!("/tickets/{}/comments/{}", id, comment_id)))
+            .json(edit);
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // DELETE /tickets/:id/comments/:comment_id
+    pub async fn delete_comment(&self, id: TicketId, comment_id: CommentId) -> Result<(), ClientError> {
+        let request = self
+            .http
+            .delete(self.url(&format!("/tickets/{}/comments/{}", id, comment_id)));
+        self.send_once(request).await?;
+        Ok(())
+    }
+
+    // PUT /tickets/:id/labels/:label
+    pub async fn add_label(&self, id: TicketId, label: &TicketLabel) -> Result<Ticket, ClientError> {
+        let request = self.http.put(self.url(&label_path(id, label)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // DELETE /tickets/:id/labels/:label
+    pub async fn remove_label(&self, id: TicketId, label: &TicketLabel) -> Result<Ticket, ClientError> {
+        let request = self.http.delete(self.url(&label_path(id, label)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // GET /tickets/:id/links
+    pub async fn links(&self, id: TicketId) -> Result<TicketLinks, ClientError> {
+        let request = self.http.get(self.url(&format!("/tickets/{}/links", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // PUT /tickets/:id/links/:kind/:target
+    pub async fn add_link(&self, link: &TicketLink) -> Result<TicketLinks, ClientError> {
+        let request = self.http.put(self.url(&link_path(link)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // DELETE /tickets/:id/links/:kind/:target
+    pub async fn remove_link(&self, link: &TicketLink) -> Result<TicketLinks, ClientError> {
+        let request = self.http.delete(self.url(&link_path(link)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // GET /tickets/:id/dependencies
+    pub async fn dependencies(&self, id: TicketId) -> Result<DependencyTree, ClientError> {
+        let request = self.http.get(self.url(&format!("/tickets/{}/dependencies", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // POST /users
+    pub async fn create_user(&self, user: &User) -> Result<User, ClientError> {
+        let request = self.http.post(self.url("/users")).json(user);
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /users
+    pub async fn users(&self) -> Result<Vec<User>, ClientError> {
+        let request = self.http.get(self.url("/users"));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // GET /users/:id
+    pub async fn user(&self, id: &UserId) -> Result<User, ClientError> {
+        let request = self.http.get(self.url(&format!("/users/{}", id)));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // DELETE /users/:id
+    pub async fn delete_user(&self, id: &UserId) -> Result<(), ClientError> {
+        let request = self.http.delete(self.url(&format!("/users/{}", id)));
+        self.send_once(request).await?;
+        Ok(())
+    }
+
+    // POST /webhooks
+    pub async fn register_webhook(&self, draft: &WebhookDraft) -> Result<Webhook, ClientError> {
+        let request = self.http.post(self.url("/webhooks")).json(draft);
+        json(self.send_once(request).await?).await
+    }
+
+    // GET /webhooks
+    pub async fn webhooks(&self) -> Result<Vec<Webhook>, ClientError> {
+        let request = self.http.get(self.url("/webhooks"));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // DELETE /webhooks/:id
+    pub async fn delete_webhook(&self, id: WebhookId) -> Result<(), ClientError> {
+        let request = self.http.delete(self.url(&format!("/webhooks/{}", id)));
+        self.send_once(request).await?;
+        Ok(())
+    }
+
+    // GET /webhooks/dead-letters
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetter>, ClientError> {
+        let request = self.http.get(self.url("/webhooks/dead-letters"));
+        json(self.send_with_retries(request).await?).await
+    }
+
+    // POST /webhooks/dead-letters/:delivery/redeliver. The delivery happens in the background:
+    // it's a dead letter again if it fails.
+    pub async fn redeliver(&self, delivery: DeliveryId) -> Result<(), ClientError> {
+        let request = self
+            .http
+            .post(self.url(&format!("/webhooks/dead-letters/{}/redeliver", delivery)));
+        self.send_once(request).await?;
+        Ok(())
+    }
+
+    // POST /tickets/:id/archive
+    pub async fn archive(&self, id: TicketId) -> Result<Ticket, ClientError> {
+        let request = self.http.post(self.url(&format!("/tickets/{}/archive", id)));
+        json(self.send_once(request).await?).await
+    }
+
+    // DELETE /tickets/:id
+    pub async fn delete(&self, id: TicketId) -> Result<(), ClientError> {
+        let request = self.http.delete(self.url(&format!("/tickets/{}", id)));
+        self.send_once(request).await?;
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.base_url, self.base_path, path)
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<reqwest::Response, ClientError> {
+        let request = match &self.actor {
+            Some(actor) => request.header(ACTOR_HEADER, actor.as_str()),
+            None => request,
+        };
+        check(request.send().await?).await
+    }
+
+    async fn send_with_retries(&self, request: RequestBuilder) -> Result<reqwest::Response, ClientError> {
+        let mut retry = 0;
+        loop {
+            // Our requests have in-memory bodies, so they can always be cloned
+            let attempt = request.try_clone().expect("the request body is not a stream");
+            match self.send_once(attempt).await {
+                Err(err) if err.is_retryable() && retry < self.retry.max_retries => {
+                    retry += 1;
+                    tokio::time::sleep(self.retry.backoff(retry)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// The events sent by GET /tickets/events
+pub struct EventStream {
+    response: reqwest::Response,
+    // What was received of the events not read yet
+    buffer: Vec<u8>,
+}
+
+impl EventStream {
+    // The next event, or `None` once the server ended the stream
+    pub async fn next(&mut self) -> Result<Option<SequencedEvent>, ClientError> {
+        loop {
+            // Events are separated by a blank line. Our server only sends `\n` line endings.
+            if let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+                let frame: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                let frame = String::from_utf8_lossy(&frame);
+                let data: Vec<&str> = frame
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                    .collect();
+                // Keep-alive comments have no data
+                if !data.is_empty() {
+                    return Ok(Some(serde_json::from_str(&data.join("\n"))?));
+                }
+                continue;
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// Labels may contain slashes, that must not split the path segment
+fn label_path(id: TicketId, label: &TicketLabel) -> String {
+    format!("/tickets/{}/labels/{}", id, label.as_str().replace('/', "%2F"))
+}
+
+fn link_path(link: &TicketLink) -> String {
+    format!("/tickets/{}/links/{}/{}", link.source, link.kind, link.target)
+}
+
+// Turn an error response into a `ClientError::Api`
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await?;
+    // Errors produced by our handlers have a JSON body, but e.g. a 405 from the router doesn't
+    let body = serde_json::from_str(&text).unwrap_or(ErrorBody {
+        code: "unknown".to_string(),
+        message: text,
+        fields: Vec::new(),
+        operations: Vec::new(),
+    });
+    Err(ClientError::Api { status, body })
+}
+
+async fn json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ClientError> {
+    Ok(check(response).await?.json().await?)
+}
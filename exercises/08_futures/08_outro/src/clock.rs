@@ -0,0 +1,82 @@
+use chrono::{DateTime, NaiveDate};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Where the stores get the current time from: the timestamps of the history and of the comments,
+// and "today" when checking due dates. Tests inject a `ManualClock`, so that their results
+// don't depend on when they run.
+pub trait Clock: Send + Sync {
+    // Milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+
+    // Due dates are calendar dates, compared to the current date in UTC
+    fn today(&self) -> NaiveDate {
+        date_of(self.now_millis())
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now_millis(&self) -> u64 {
+        (**self).now_millis()
+    }
+}
+
+// The clock of the machine, used by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+// A clock that only moves when told to. Its clones share the same time,
+// so a test can keep one and move the clock of the store it injected another one in.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock(Arc<AtomicU64>);
+
+impl ManualClock {
+    pub fn new(millis: u64) -> Self {
+        Self(Arc::new(AtomicU64::new(millis)))
+    }
+
+    // Midnight (UTC) at the start of `date`
+    pub fn at(date: NaiveDate) -> Self {
+        Self::new(midnight(date))
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.0.store(millis, Ordering::SeqCst);
+    }
+
+    pub fn set_date(&self, date: NaiveDate) {
+        self.set(midnight(date));
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.0.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+fn date_of(millis: u64) -> NaiveDate {
+    DateTime::from_timestamp_millis(millis as i64)
+        .map(|time| time.date_naive())
+        .unwrap_or_default()
+}
+
+fn midnight(date: NaiveDate) -> u64 {
+    date.and_hms_opt(0, 0, 0)
+        .map(|time| time.and_utc().timestamp_millis().max(0) as u64)
+        .unwrap_or_default()
+}
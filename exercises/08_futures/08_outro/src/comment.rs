@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ticket_fields::CommentBody;
+
+use crate::history::{Actor, FieldChange};
+use crate::store::TicketId;
+
+// Comments on a ticket. They are threaded: a comment can reply to another comment
+// of the same ticket. Comments don't change the version of their ticket, but each of
+// them is recorded in its history (see `change`).
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CommentId(pub u64);
+
+impl std::fmt::Display for CommentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketComment {
+    pub id: CommentId,
+    pub ticket_id: TicketId,
+    // The comment this one replies to, `None` for a top-level comment
+    pub parent_id: Option<CommentId>,
+    pub author: Actor,
+    pub body: CommentBody,
+    // Milliseconds since the Unix epoch
+    pub created_at: u64,
+    // When the body was last edited, if ever
+    pub edited_at: Option<u64>,
+}
+
+// Body of POST /tickets/:id/comments
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentDraft {
+    pub body: CommentBody,
+    #[serde(default)]
+    pub parent_id: Option<CommentId>,
+}
+
+// Body of PATCH /tickets/:id/comments/:comment_id
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentEdit {
+    pub body: CommentBody,
+}
+
+// A comment appears in the history of its ticket as the field `comments/<id>`:
+// its body before the change is `null` when it's added, and after the change when it's deleted
+pub fn change(id: CommentId, before: Option<&CommentBody>, after: Option<&CommentBody>) -> FieldChange {
+    let value = |body: Option<&CommentBody>| body.map_or(Value::Null, |body| body.as_str().into());
+    FieldChange {
+        field: format!("comments/{}", id),
+        before: value(before),
+        after: value(after),
+    }
+}
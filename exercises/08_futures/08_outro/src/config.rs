@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::persistent::DEFAULT_COMPACT_EVERY;
+use crate::server::{ServerConfig, DEFAULT_BIND, DEFAULT_BODY_LIMIT, DEFAULT_REQUEST_TIMEOUT};
+use crate::workflow::WorkflowConfig;
+
+// Configuration of the `ticket-server` binary, read from a TOML file, e.g.
+//
+//   bind = "0.0.0.0:8080"
+//   data_dir = "/var/lib/tickets"
+//   storage = "sqlite"
+//   request_timeout_secs = 10
+//   body_limit = 65536
+//   compact_every = 1000
+//
+//   [workflow]
+//   extra_statuses = ["Blocked", "InReview"]
+//
+// Every field is optional. Each top-level field can be overridden by an environment variable,
+// named after the field: TICKET_SERVER_BIND, TICKET_SERVER_DATA_DIR, ...
+// The workflow (see `WorkflowConfig`) can only be set in the file.
+// Without a data directory, tickets are kept in memory and lost on restart.
+
+pub const ENV_PREFIX: &str = "TICKET_SERVER_";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    // `PersistentTicketStore`: write-ahead log + snapshots
+    #[default]
+    Wal,
+    // `SqliteTicketStore`
+    Sqlite,
+}
+
+impl TryFrom<&str> for StorageKind {
+    type Error = ConfigError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "wal" => Ok(StorageKind::Wal),
+            "sqlite" => Ok(StorageKind::Sqlite),
+            _ => Err(ConfigError::InvalidValue {
+                key: "storage".to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TicketServerConfig {
+    pub bind: SocketAddr,
+    pub data_dir: Option<PathBuf>,
+    pub storage: StorageKind,
+    pub request_timeout_secs: u64,
+    pub body_limit: usize,
+    pub compact_every: usize,
+    pub workflow: WorkflowConfig,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Cannot read the config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid value `{value}` for `{key}`")]
+    InvalidValue { key: String, value: String },
+}
+
+impl Default for TicketServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: DEFAULT_BIND.into(),
+            data_dir: None,
+            storage: StorageKind::default(),
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT.as_secs(),
+            body_limit: DEFAULT_BODY_LIMIT,
+            compact_every: DEFAULT_COMPACT_EVERY,
+            workflow: WorkflowConfig::default(),
+        }
+    }
+}
+
+impl TicketServerConfig {
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    // Read the config file (if any), then apply the overrides from the environment
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let config = match path {
+            Some(path) => {
+                let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+                Self::from_toml(&content)?
+            }
+            None => Self::default(),
+        };
+        config.with_env_overrides(|name| std::env::var(name).ok())
+    }
+
+    // `env` looks up an environment variable: it's a parameter so that tests don't have to
+    // modify the environment of the whole process
+    pub fn with_env_overrides(
+        mut self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let var = |key: &str| env(&format!("{}{}", ENV_PREFIX, key.to_uppercase()));
+        let invalid = |key: &str, value: &str| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        if let Some(value) = var("bind") {
+            self.bind = value.parse().map_err(|_| invalid("bind", &value))?;
+        }
+        if let Some(value) = var("data_dir") {
+            self.data_dir = Some(PathBuf::from(value));
+        }
+        if let Some(value) = var("storage") {
+            self.storage = StorageKind::try_from(value.as_str())?;
+        }
+        if let Some(value) = var("request_timeout_secs") {
+            self.request_timeout_secs = value.parse().map_err(|_| invalid("request_timeout_secs", &value))?;
+        }
+        if let Some(value) = var("body_limit") {
+            self.body_limit = value.parse().map_err(|_| invalid("body_limit", &value))?;
+        }
+        if let Some(value) = var("compact_every") {
+            self.compact_every = value.parse().map_err(|_| invalid("compact_every", &value))?;
+        }
+        Ok(self)
+    }
+
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig::new()
+            .bind(self.bind)
+            .request_timeout(Duration::from_secs(self.request_timeout_secs))
+            .body_limit(self.body_limit)
+    }
+}
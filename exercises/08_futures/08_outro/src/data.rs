@@ -1,7 +1,11 @@
-use serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
 
+use crate::filter::Filter;
 use crate::store::TicketId;
-use ticket_fields::{TicketDescription, TicketTitle};
+use crate::user::UserId;
+use ticket_fields::{TicketDescription, TicketLabel, TicketTitle};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Ticket {
@@ -9,12 +13,48 @@ pub struct Ticket {
     pub title: TicketTitle,
     pub description: TicketDescription,
     pub status: Status,
+    // Archived tickets are hidden from listings, but can still be retrieved by id
+    #[serde(default)]
+    pub archived: bool,
+    // Starts at 1 and is incremented by every change: it's the ETag of the ticket,
+    // used to detect concurrent modifications (see `TicketStore::patch_if_version`)
+    #[serde(default)]
+    pub version: u64,
+    // Registered users (see `TicketStore::add_user`)
+    #[serde(default)]
+    pub assignee: Option<UserId>,
+    #[serde(default)]
+    pub reporter: Option<UserId>,
+    // Changed with `TicketStore::add_label` and `TicketStore::remove_label`, not with a patch
+    #[serde(default)]
+    pub labels: BTreeSet<TicketLabel>,
+    // Tickets created before priorities existed are P2
+    #[serde(default)]
+    pub priority: Priority,
+    // e.g. "2024-06-30", see `Ticket::is_overdue`
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+}
+
+impl Ticket {
+    // Past its due date (the day before `today`, or earlier) and still open
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        !self.status.is_closed() && self.due_date.is_some_and(|due_date| due_date < today)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TicketDraft {
     pub title: TicketTitle,
     pub description: TicketDescription,
+    #[serde(default)]
+    pub assignee: Option<UserId>,
+    #[serde(default)]
+    pub reporter: Option<UserId>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -23,11 +63,208 @@ pub struct TicketPatch {
     pub title: Option<TicketTitle>,
     pub description: Option<TicketDescription>,
     pub status: Option<Status>,
+    // `None` leaves the field as it is, `Some(None)` clears it.
+    // In JSON, a missing field is `None` and `null` is `Some(None)`.
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<Option<UserId>>,
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<Option<UserId>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    // Same as the users: `Some(None)` removes the due date
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<Option<NaiveDate>>,
 }
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
+// Only called for fields that are in the JSON document, even if they are `null`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Which statuses a workspace uses, and how a ticket moves between them,
+// is defined by its `Workflow`
+#[derive(Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Status {
     ToDo,
     InProgress,
     Done,
+    // Optional statuses, disabled by default
+    Blocked,
+    InReview,
+    Cancelled,
+}
+
+impl Status {
+    // The statuses every workspace has
+    pub const CORE: [Status; 3] = [Status::ToDo, Status::InProgress, Status::Done];
+    pub const ALL: [Status; 6] = [
+        Status::ToDo,
+        Status::InProgress,
+        Status::Done,
+        Status::Blocked,
+        Status::InReview,
+        Status::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::ToDo => "ToDo",
+            Status::InProgress => "InProgress",
+            Status::Done => "Done",
+            Status::Blocked => "Blocked",
+            Status::InReview => "InReview",
+            Status::Cancelled => "Cancelled",
+        }
+    }
+
+    // Nothing left to do on the ticket: it can't be overdue anymore
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Status::Done | Status::Cancelled)
+    }
+}
+
+// The parsing is case-insensitive
+impl TryFrom<&str> for Status {
+    type Error = ParseStatusError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "todo" => Ok(Status::ToDo),
+            "inprogress" => Ok(Status::InProgress),
+            "done" => Ok(Status::Done),
+            "blocked" => Ok(Status::Blocked),
+            "inreview" => Ok(Status::InReview),
+            "cancelled" => Ok(Status::Cancelled),
+            _ => Err(ParseStatusError {
+                invalid_status: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for Status {
+    type Error = ParseStatusError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Status::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{invalid_status}` is not a valid status. Use one of: ToDo, InProgress, Done, Blocked, InReview, Cancelled")]
+pub struct ParseStatusError {
+    invalid_status: String,
+}
+
+// How urgent a ticket is, from P0 (drop everything else) to P3 (when there is time).
+// P0 is the smallest: sorting in ascending order puts the most urgent tickets first.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Priority {
+    P0,
+    P1,
+    #[default]
+    P2,
+    P3,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 4] = [Priority::P0, Priority::P1, Priority::P2, Priority::P3];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::P0 => "P0",
+            Priority::P1 => "P1",
+            Priority::P2 => "P2",
+            Priority::P3 => "P3",
+        }
+    }
+}
+
+// The parsing is case-insensitive, like the one of `Status`
+impl TryFrom<&str> for Priority {
+    type Error = ParsePriorityError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "p0" => Ok(Priority::P0),
+            "p1" => Ok(Priority::P1),
+            "p2" => Ok(Priority::P2),
+            "p3" => Ok(Priority::P3),
+            _ => Err(ParsePriorityError {
+                invalid_priority: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for Priority {
+    type Error = ParsePriorityError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Priority::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{invalid_priority}` is not a valid priority. Use one of: P0, P1, P2, P3")]
+pub struct ParsePriorityError {
+    invalid_priority: String,
+}
+
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderBy {
+    #[default]
+    Id,
+    // By priority, then by due date (tickets without one last), then by id
+    Priority,
+}
+
+// Query parameters of GET /tickets, e.g. `/tickets?status=InProgress&title=login&sort=desc&cursor=42&limit=20`,
+// or `/tickets?assignee=alice&status=InProgress` for the tickets alice is working on,
+// or `/tickets?label=bug` for the tickets with the label "bug",
+// or `/tickets?overdue=true&order_by=priority` for the overdue tickets, most urgent first,
+// or `/tickets?q=status:InProgress AND NOT label:backend` for a filter (see `Filter`), URL-encoded
+// All fields are optional: an empty query lists the first page of all tickets, in ascending id order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketQuery {
+    pub status: Option<Status>,
+    // Case-insensitive substring of the ticket title
+    pub title: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+    #[serde(default)]
+    pub order_by: OrderBy,
+    // Id of the last ticket of the previous page: the page starts right after it
+    pub cursor: Option<TicketId>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub include_archived: bool,
+    pub assignee: Option<UserId>,
+    pub reporter: Option<UserId>,
+    pub label: Option<TicketLabel>,
+    // Only the overdue tickets (see `Ticket::is_overdue`), according to the clock of the store
+    #[serde(default)]
+    pub overdue: bool,
+    // Applies on top of the other parameters: archived tickets still need `include_archived`
+    pub q: Option<Filter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TicketPage {
+    pub tickets: Vec<Ticket>,
+    // Cursor to pass to the next request, `None` on the last page
+    pub next_cursor: Option<TicketId>,
 }
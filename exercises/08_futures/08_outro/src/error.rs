@@ -0,0 +1,279 @@
+use axum::{
+    extract::rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ticket_fields::{CommentBodyError, TicketDescriptionError, TicketTitleError};
+
+use crate::batch::BatchError;
+use crate::events::ResumeError;
+use crate::repository::RepositoryError;
+use crate::store::TicketStoreError;
+use crate::webhook::WebhookError;
+use crate::workflow::WorkflowError;
+
+// Errors returned by the API handlers.
+// Each variant maps to an HTTP status code and a stable JSON body (see `ErrorBody`)
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("The request is malformed: {0}")]
+    BadRequest(String),
+    // An unknown ticket, or an unknown comment of a ticket
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Validation(String),
+    // Some operations of a batch failed, so none was applied: each failure is reported
+    // in `ErrorBody::operations`
+    #[error("{message}")]
+    BatchFailed {
+        message: String,
+        operations: Vec<OperationError>,
+    },
+    // Several fields of a patch are invalid: each of them is reported in `ErrorBody::fields`
+    #[error("Invalid fields: {}", .0.iter().map(|error| error.field.as_str()).collect::<Vec<_>>().join(", "))]
+    InvalidFields(Vec<FieldError>),
+    #[error("The request body is too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("Unsupported content type: {0}")]
+    UnsupportedMediaType(String),
+    // The status change is not allowed by the workflow
+    #[error("{0}")]
+    InvalidTransition(String),
+    // The `If-Match` header doesn't match the current version of the ticket
+    #[error("{0}")]
+    PreconditionFailed(String),
+    // The change-feed can't be resumed from the requested event: the client has to reload
+    // the tickets, then follow the feed from now on. Likewise for a listing whose cursor is gone.
+    #[error("{0}")]
+    Gone(String),
+    #[error("The request timed out")]
+    Timeout,
+    #[error("Internal server error: {0}")]
+    Internal(String),
+}
+
+// The JSON body sent back to the client for every error, e.g.
+// {"code": "not_found", "message": "Ticket 999 not found"}
+// Validation errors of a patch also list the invalid fields:
+// {"code": "validation_failed", "message": "Invalid fields: title",
+//  "fields": [{"field": "title", "message": "The title cannot be empty"}]}
+// and so do the failed operations of a batch, with the code of the error each one would get alone:
+// {"code": "batch_failed", "message": "1 of the 2 operations of the batch failed: none was applied",
+//  "operations": [{"index": 1, "code": "not_found", "message": "Ticket 999 not found"}]}
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operations: Vec<OperationError>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationError {
+    // The position of the operation in the batch, starting at 0
+    pub index: usize,
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) | ApiError::InvalidTransition(_) | ApiError::BatchFailed { .. } => {
+                StatusCode::CONFLICT
+            }
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::Gone(_) => StatusCode::GONE,
+            ApiError::Timeout => StatusCode::REQUEST_TIMEOUT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Machine-readable error code, which stays the same even if the message changes
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::InvalidTransition(_) => "invalid_transition",
+            ApiError::BatchFailed { .. } => "batch_failed",
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => "validation_failed",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::Gone(_) => "gone",
+            ApiError::Timeout => "timeout",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let fields = match &self {
+            ApiError::InvalidFields(fields) => fields.clone(),
+            _ => Vec::new(),
+        };
+        let operations = match &self {
+            ApiError::BatchFailed { operations, .. } => operations.clone(),
+            _ => Vec::new(),
+        };
+        let body = ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            fields,
+            operations,
+        };
+        (self.status_code(), Json(body)).into_response()
+    }
+}
+
+impl From<TicketStoreError> for ApiError {
+    fn from(err: TicketStoreError) -> Self {
+        match err {
+            err @ (TicketStoreError::NotFound(_)
+            | TicketStoreError::CommentNotFound { .. }
+            | TicketStoreError::UserNotFound(_)) => ApiError::NotFound(err.to_string()),
+            err @ (TicketStoreError::AlreadyArchived(_)
+            | TicketStoreError::AlreadyExists(_)
+            | TicketStoreError::UserAlreadyExists(_)
+            | TicketStoreError::UserInUse { .. }
+            | TicketStoreError::LinkCycle(_)
+            | TicketStoreError::ParentAlreadySet { .. }) => ApiError::Conflict(err.to_string()),
+            err @ TicketStoreError::VersionMismatch { .. } => ApiError::PreconditionFailed(err.to_string()),
+            err @ TicketStoreError::Workflow { source: WorkflowError::InvalidTransition { .. }, .. } => {
+                ApiError::InvalidTransition(err.to_string())
+            }
+            err @ TicketStoreError::Workflow { source: WorkflowError::StatusNotEnabled(_), .. } => {
+                ApiError::Validation(err.to_string())
+            }
+            err @ (TicketStoreError::UnknownParent { .. }
+            | TicketStoreError::UnknownUser(_)
+            | TicketStoreError::SelfLink(_)) => ApiError::Validation(err.to_string()),
+            // Like a transition the workflow doesn't allow: retrying won't help until the blocker is closed
+            err @ TicketStoreError::OpenBlocker { .. } => ApiError::InvalidTransition(err.to_string()),
+            err @ TicketStoreError::StaleCursor(_) => ApiError::Gone(err.to_string()),
+        }
+    }
+}
+
+impl From<RepositoryError> for ApiError {
+    fn from(err: RepositoryError) -> Self {
+        match err {
+            RepositoryError::Store(err) => err.into(),
+            RepositoryError::Batch(err) => err.into(),
+            RepositoryError::Backend(message) => ApiError::Internal(message),
+        }
+    }
+}
+
+impl From<BatchError> for ApiError {
+    fn from(err: BatchError) -> Self {
+        let message = err.to_string();
+        let operations = err
+            .failures
+            .into_iter()
+            .map(|failure| {
+                let error = ApiError::from(failure.error);
+                OperationError {
+                    index: failure.index,
+                    code: error.code().to_string(),
+                    message: error.to_string(),
+                }
+            })
+            .collect();
+        ApiError::BatchFailed { message, operations }
+    }
+}
+
+impl From<ResumeError> for ApiError {
+    fn from(err: ResumeError) -> Self {
+        ApiError::Gone(err.to_string())
+    }
+}
+
+impl From<WebhookError> for ApiError {
+    fn from(err: WebhookError) -> Self {
+        match err {
+            err @ (WebhookError::NotFound(_) | WebhookError::DeadLetterNotFound(_)) => {
+                ApiError::NotFound(err.to_string())
+            }
+            err @ (WebhookError::InvalidUrl { .. } | WebhookError::EmptySecret) => {
+                ApiError::Validation(err.to_string())
+            }
+        }
+    }
+}
+
+impl From<TicketTitleError> for ApiError {
+    fn from(err: TicketTitleError) -> Self {
+        ApiError::Validation(err.to_string())
+    }
+}
+
+impl From<TicketDescriptionError> for ApiError {
+    fn from(err: TicketDescriptionError) -> Self {
+        ApiError::Validation(err.to_string())
+    }
+}
+
+impl From<CommentBodyError> for ApiError {
+    fn from(err: CommentBodyError) -> Self {
+        ApiError::Validation(err.to_string())
+    }
+}
+
+// A JSON body that is well-formed but fails the `ticket_fields` validation
+// (e.g. an empty title) is rejected by axum with a `JsonDataError`: it becomes a 422
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::JsonDataError(err) => ApiError::Validation(err.body_text()),
+            // The body is bigger than the limit set with `ServerConfig::body_limit`
+            other if other.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                ApiError::PayloadTooLarge(other.body_text())
+            }
+            other => ApiError::BadRequest(other.body_text()),
+        }
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge(rejection.body_text())
+        } else {
+            ApiError::BadRequest(rejection.body_text())
+        }
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
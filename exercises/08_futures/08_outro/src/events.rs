@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::data::{Status, Ticket};
+use crate::history::{diff, FieldChange};
+use ticket_fields::TicketLabel;
+
+// The change-feed of the tickets, e.g. for UIs that would otherwise poll GET /tickets/:id.
+//
+// Every store announces the changes made to its tickets on its `EventBus`, once they are
+// applied (and, for the stores backed by a disk, durable). Each event gets a sequence number,
+// starting at 1, that orders it among all the events of the store.
+//
+// The bus keeps the latest events, so that a subscriber that lost its connection can resume
+// from the last event it received without missing any. Sequence numbers are not persisted:
+// they start over when the store is opened again.
+
+// Number of events kept for the subscribers that resume, by default. A subscriber that falls
+// further behind than that is disconnected: it has to resume from its last event.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TicketEvent {
+    Created {
+        ticket: Ticket,
+    },
+    // A patch, a label added or removed, or the ticket archived: the ticket as it is now,
+    // and the fields that changed
+    Patched {
+        ticket: Ticket,
+        changes: Vec<FieldChange>,
+    },
+    // The ticket as it was when it was removed
+    Deleted {
+        ticket: Ticket,
+    },
+}
+
+impl TicketEvent {
+    pub fn patched(before: &Ticket, after: Ticket) -> Self {
+        TicketEvent::Patched {
+            changes: diff(before, &after),
+            ticket: after,
+        }
+    }
+
+    pub fn ticket(&self) -> &Ticket {
+        match self {
+            TicketEvent::Created { ticket } | TicketEvent::Patched { ticket, .. } | TicketEvent::Deleted { ticket } => {
+                ticket
+            }
+        }
+    }
+
+    // The name of the event in the SSE stream, and its `type` in JSON
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TicketEvent::Created { .. } => "created",
+            TicketEvent::Patched { .. } => "patched",
+            TicketEvent::Deleted { .. } => "deleted",
+        }
+    }
+}
+
+// An event and its sequence number, e.g.
+// {"seq": 12, "type": "patched", "ticket": {...}, "changes": [{"field": "status", ...}]}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SequencedEvent {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: TicketEvent,
+}
+
+// Which events a subscriber gets: those of the tickets with the given status and label.
+// A patch is let through if the ticket matched before or after it, so that a subscriber
+// watching the `InProgress` tickets learns about the tickets that leave the status too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub status: Option<Status>,
+    pub label: Option<TicketLabel>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &TicketEvent) -> bool {
+        let ticket = event.ticket();
+        let labels: Vec<&str> = ticket.labels.iter().map(TicketLabel::as_str).collect();
+        let after = self.accepts(ticket.status.as_str(), &labels);
+        match event {
+            TicketEvent::Patched { changes, .. } if !after => {
+                // The changes hold the previous values, as in the history of the ticket
+                let before = |field: &str| {
+                    let change = changes.iter().find(|change| change.field == field)?;
+                    Some(&change.before)
+                };
+                let status = before("status").and_then(Value::as_str).unwrap_or(ticket.status.as_str());
+                let labels = match before("labels").and_then(Value::as_array) {
+                    Some(before) => before.iter().filter_map(Value::as_str).collect(),
+                    None => labels,
+                };
+                self.accepts(status, &labels)
+            }
+            _ => after,
+        }
+    }
+
+    // Whether a ticket with this status and these labels is of interest
+    fn accepts(&self, status: &str, labels: &[&str]) -> bool {
+        self.status.is_none_or(|wanted| wanted.as_str() == status)
+            && self.label.as_ref().is_none_or(|wanted| labels.contains(&wanted.as_str()))
+    }
+}
+
+// Query parameters of GET /tickets/events and GET /tickets/events/ws, e.g.
+// `/tickets/events?status=InProgress&label=backend&since=41`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventsParams {
+    pub status: Option<Status>,
+    pub label: Option<TicketLabel>,
+    // Sequence number of the last event received: the stream starts right after it.
+    // Without it, the stream starts with the next change.
+    pub since: Option<u64>,
+}
+
+impl EventsParams {
+    pub fn filter(&self) -> EventFilter {
+        EventFilter {
+            status: self.status,
+            label: self.label.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ResumeError {
+    // The events that came after `since` are no longer kept
+    #[error("Cannot resume after event {since}: the oldest event still available is {oldest}")]
+    Expired { since: u64, oldest: u64 },
+    // e.g. a sequence number handed out before the store was opened again
+    #[error("Cannot resume after event {since}: the last event is {last}")]
+    Unknown { since: u64, last: u64 },
+}
+
+// Where a store publishes its events. A clone is a new handle to the same bus.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    recent: Arc<Mutex<RecentEvents>>,
+    sender: broadcast::Sender<SequencedEvent>,
+}
+
+#[derive(Debug)]
+struct RecentEvents {
+    last_seq: u64,
+    events: VecDeque<SequencedEvent>,
+    capacity: usize,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_EVENT_CAPACITY)
+    }
+
+    // Keep the last `capacity` events (at least 1) for the subscribers that resume or fall behind
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            recent: Arc::new(Mutex::new(RecentEvents {
+                last_seq: 0,
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+            })),
+            sender,
+        }
+    }
+
+    // Announce a change, and return its sequence number
+    pub fn publish(&self, event: TicketEvent) -> u64 {
+        let mut recent = self.recent.lock().unwrap();
+        recent.last_seq += 1;
+        let event = SequencedEvent {
+            seq: recent.last_seq,
+            event,
+        };
+        if recent.events.len() == recent.capacity {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+        // Sent while holding the lock, so that the subscribers get the events in order,
+        // and a new subscriber gets each event once: either from `recent` or from the channel.
+        // Without subscribers there is nobody to send to, which is fine.
+        let _ = self.sender.send(event);
+        recent.last_seq
+    }
+
+    // Sequence number of the last event published, 0 if there is none yet
+    pub fn last_seq(&self) -> u64 {
+        self.recent.lock().unwrap().last_seq
+    }
+
+    // Subscribe to the events matching `filter`: those published after event `since`, when set,
+    // or those published from now on
+    pub fn subscribe(&self, since: Option<u64>, filter: EventFilter) -> Result<Subscription, ResumeError> {
+        let recent = self.recent.lock().unwrap();
+        let backlog = match since {
+            None => VecDeque::new(),
+            Some(since) if since > recent.last_seq => {
+                return Err(ResumeError::Unknown {
+                    since,
+                    last: recent.last_seq,
+                })
+            }
+            Some(since) => {
+                let oldest = recent.events.front().map_or(recent.last_seq + 1, |event| event.seq);
+                if since + 1 < oldest {
+                    return Err(ResumeError::Expired { since, oldest });
+                }
+                recent.events.iter().filter(|event| event.seq > since).cloned().collect()
+            }
+        };
+        Ok(Subscription {
+            backlog,
+            receiver: self.sender.subscribe(),
+            filter,
+        })
+    }
+}
+
+// The events a subscriber gets, in order
+#[derive(Debug)]
+pub struct Subscription {
+    // The events published before the subscription, when resuming
+    backlog: VecDeque<SequencedEvent>,
+    receiver: broadcast::Receiver<SequencedEvent>,
+    filter: EventFilter,
+}
+
+impl Subscription {
+    // The next event matching the filter. `None` once the bus is gone, or if the subscriber
+    // fell so far behind that events were lost: it has to subscribe again from its last event.
+    pub async fn next(&mut self) -> Option<SequencedEvent> {
+        loop {
+            let event = match self.backlog.pop_front() {
+                Some(event) => event,
+                None => match self.receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_) | RecvError::Closed) => return None,
+                },
+            };
+            if self.filter.matches(&event.event) {
+                return Some(event);
+            }
+        }
+    }
+}
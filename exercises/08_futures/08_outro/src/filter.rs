@@ -0,0 +1,380 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Priority, Status, Ticket};
+use crate::user::UserId;
+use ticket_fields::TicketLabel;
+
+// A small language to filter tickets, e.g.
+//   status:InProgress AND label:backend AND NOT title~"flaky"
+//
+// A condition is a field, an operator and a value. `:` compares a field with a value:
+//   status:Done, label:bug, assignee:alice, reporter:bob, priority:P0, archived:true
+// `~` tells whether the title or the description contains a text, ignoring the case:
+//   title~login, description~"stack trace"
+// Values with spaces or any of ( ) : ~ " are quoted, with \" and \\ for quotes and backslashes.
+//
+// Conditions are combined with NOT, AND and OR (in this order of precedence, case-insensitive),
+// and grouped with parentheses: `priority:P0 OR (priority:P1 AND NOT archived:true)`.
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Filter {
+    Condition(Condition),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Status(Status),
+    Label(TicketLabel),
+    Assignee(UserId),
+    Reporter(UserId),
+    Priority(Priority),
+    Archived(bool),
+    TitleContains(String),
+    DescriptionContains(String),
+}
+
+impl Filter {
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        match self {
+            Filter::Condition(condition) => condition.matches(ticket),
+            Filter::Not(filter) => !filter.matches(ticket),
+            Filter::And(left, right) => left.matches(ticket) && right.matches(ticket),
+            Filter::Or(left, right) => left.matches(ticket) || right.matches(ticket),
+        }
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        match self {
+            Condition::Status(status) => ticket.status == *status,
+            Condition::Label(label) => ticket.labels.contains(label),
+            Condition::Assignee(user) => ticket.assignee.as_ref() == Some(user),
+            Condition::Reporter(user) => ticket.reporter.as_ref() == Some(user),
+            Condition::Priority(priority) => ticket.priority == *priority,
+            Condition::Archived(archived) => ticket.archived == *archived,
+            Condition::TitleContains(text) => contains(ticket.title.as_str(), text),
+            Condition::DescriptionContains(text) => contains(ticket.description.as_str(), text),
+        }
+    }
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+// Where parsing failed, as a byte offset in the filter, and why
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid filter at position {position}: {kind}")]
+pub struct ParseFilterError {
+    pub position: usize,
+    pub kind: ParseFilterErrorKind,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ParseFilterErrorKind {
+    #[error("the filter is empty")]
+    Empty,
+    #[error("the filter ends too early")]
+    UnexpectedEnd,
+    #[error("expected {expected}, found `{found}`")]
+    Unexpected { expected: &'static str, found: String },
+    #[error("the string is not closed")]
+    UnterminatedString,
+    #[error("`{0}` is not a field. Use one of: status, label, assignee, reporter, priority, archived, title, description")]
+    UnknownField(String),
+    #[error("`{field}` can't be used with `{operator}`")]
+    UnsupportedOperator { field: String, operator: char },
+    #[error("{0}")]
+    InvalidValue(String),
+}
+
+impl TryFrom<&str> for Filter {
+    type Error = ParseFilterError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut parser = Parser {
+            tokens: tokenize(value)?,
+            next: 0,
+            end: value.len(),
+        };
+        if parser.tokens.is_empty() {
+            return Err(ParseFilterError {
+                position: 0,
+                kind: ParseFilterErrorKind::Empty,
+            });
+        }
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(token.unexpected("AND, OR or the end of the filter")),
+        }
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = ParseFilterError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Filter::try_from(value.as_str())
+    }
+}
+
+// Fully parenthesized: parsing the output gives back the same filter
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::Condition(condition) => write!(f, "{}", condition),
+            Filter::Not(filter) => write!(f, "NOT {}", filter),
+            Filter::And(left, right) => write!(f, "({} AND {})", left, right),
+            Filter::Or(left, right) => write!(f, "({} OR {})", left, right),
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Status(status) => write!(f, "status:{}", status.as_str()),
+            Condition::Label(label) => write!(f, "label:{}", quote(label.as_str())),
+            Condition::Assignee(user) => write!(f, "assignee:{}", quote(user.as_str())),
+            Condition::Reporter(user) => write!(f, "reporter:{}", quote(user.as_str())),
+            Condition::Priority(priority) => write!(f, "priority:{}", priority.as_str()),
+            Condition::Archived(archived) => write!(f, "archived:{}", archived),
+            Condition::TitleContains(text) => write!(f, "title~{}", quote(text)),
+            Condition::DescriptionContains(text) => write!(f, "description~{}", quote(text)),
+        }
+    }
+}
+
+impl From<Filter> for String {
+    fn from(filter: Filter) -> Self {
+        filter.to_string()
+    }
+}
+
+// A value as it has to be written in a filter
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.chars().any(is_special) {
+        return value.to_string();
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ':' | '~' | '"')
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TokenKind {
+    Open,
+    Close,
+    Operator(char),
+    Word(String),
+    // A quoted string: never a keyword
+    Quoted(String),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseFilterError {
+        let found = match &self.kind {
+            TokenKind::Open => "(".to_string(),
+            TokenKind::Close => ")".to_string(),
+            TokenKind::Operator(operator) => operator.to_string(),
+            TokenKind::Word(word) => word.clone(),
+            TokenKind::Quoted(text) => quote(text),
+        };
+        self.error(ParseFilterErrorKind::Unexpected { expected, found })
+    }
+
+    fn error(&self, kind: ParseFilterErrorKind) -> ParseFilterError {
+        ParseFilterError {
+            position: self.position,
+            kind,
+        }
+    }
+}
+
+fn tokenize(filter: &str) -> Result<Vec<Token>, ParseFilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            ':' | '~' => TokenKind::Operator(c),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => text.push(escaped),
+                            None => break,
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => {
+                            return Err(ParseFilterError {
+                                position,
+                                kind: ParseFilterErrorKind::UnterminatedString,
+                            })
+                        }
+                    }
+                }
+                TokenKind::Quoted(text)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if is_special(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                TokenKind::Word(word)
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+    Ok(tokens)
+}
+
+// Recursive descent, one method per level of precedence:
+//   or    = and { OR and }
+//   and   = not { AND not }
+//   not   = NOT not | atom
+//   atom  = "(" or ")" | field (":" | "~") value
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    // Length of the filter: the position of the errors at its end
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Result<&Token, ParseFilterError> {
+        let token = self.tokens.get(self.next).ok_or(ParseFilterError {
+            position: self.end,
+            kind: ParseFilterErrorKind::UnexpectedEnd,
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    // Consume the next token if it is the given keyword
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.is_keyword(keyword));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Filter, ParseFilterError> {
+        let mut filter = self.and()?;
+        while self.keyword("OR") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, ParseFilterError> {
+        let mut filter = self.not()?;
+        while self.keyword("AND") {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, ParseFilterError> {
+        if self.keyword("NOT") {
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Filter, ParseFilterError> {
+        let token = self.advance()?;
+        let field = match &token.kind {
+            TokenKind::Open => {
+                let filter = self.or()?;
+                let close = self.advance()?;
+                if close.kind != TokenKind::Close {
+                    return Err(close.unexpected("AND, OR or `)`"));
+                }
+                return Ok(filter);
+            }
+            TokenKind::Word(word) if !["AND", "OR", "NOT"].iter().any(|keyword| token.is_keyword(keyword)) => {
+                word.clone()
+            }
+            _ => return Err(token.unexpected("a field or `(`")),
+        };
+        let field_position = token.position;
+
+        let token = self.advance()?;
+        let TokenKind::Operator(operator) = token.kind else {
+            return Err(token.unexpected("`:` or `~`"));
+        };
+        let token = self.advance()?;
+        let value = match &token.kind {
+            TokenKind::Word(value) | TokenKind::Quoted(value) => value.clone(),
+            _ => return Err(token.unexpected("a value")),
+        };
+        let value_position = token.position;
+
+        let invalid = |err: String| ParseFilterError {
+            position: value_position,
+            kind: ParseFilterErrorKind::InvalidValue(err),
+        };
+        let condition = match (field.as_str(), operator) {
+            ("status", ':') => Condition::Status(Status::try_from(value).map_err(|err| invalid(err.to_string()))?),
+            ("label", ':') => Condition::Label(TicketLabel::try_from(value).map_err(|err| invalid(err.to_string()))?),
+            ("assignee", ':') => Condition::Assignee(UserId::try_from(value).map_err(|err| invalid(err.to_string()))?),
+            ("reporter", ':') => Condition::Reporter(UserId::try_from(value).map_err(|err| invalid(err.to_string()))?),
+            ("priority", ':') => {
+                Condition::Priority(Priority::try_from(value).map_err(|err| invalid(err.to_string()))?)
+            }
+            ("archived", ':') => match value.as_str() {
+                "true" => Condition::Archived(true),
+                "false" => Condition::Archived(false),
+                _ => return Err(invalid(format!("`{}` is not a boolean. Use true or false", value))),
+            },
+            ("title", '~') => Condition::TitleContains(value),
+            ("description", '~') => Condition::DescriptionContains(value),
+            ("status" | "label" | "assignee" | "reporter" | "priority" | "archived" | "title" | "description", _) => {
+                return Err(ParseFilterError {
+                    position: field_position,
+                    kind: ParseFilterErrorKind::UnsupportedOperator { field, operator },
+                })
+            }
+            _ => {
+                return Err(ParseFilterError {
+                    position: field_position,
+                    kind: ParseFilterErrorKind::UnknownField(field),
+                })
+            }
+        };
+        Ok(Filter::Condition(condition))
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::data::Ticket;
+use crate::store::TicketId;
+use crate::user::UserId;
+
+// The change history of the tickets: every patch that modifies a ticket appends an entry,
+// that is never modified afterwards. It's kept even when the ticket is removed.
+
+// Who made a change. Over HTTP, it's the `X-Actor` header of the request.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Actor(String);
+
+impl Actor {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    // Changes made by the service itself, or through an API that doesn't say who is calling
+    pub fn system() -> Self {
+        Self::new("system")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// Entries written before actors were recorded are attributed to the system
+impl Default for Actor {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl std::fmt::Display for Actor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub ticket_id: TicketId,
+    // Version of the ticket after the change. Comments don't change it.
+    pub version: u64,
+    // Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub actor: Actor,
+    pub changes: Vec<FieldChange>,
+}
+
+// A field of the ticket, with its value before and after the change
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+// The fields that differ between two states of a ticket. The version isn't one of them:
+// it changes every time, and is recorded on the entry itself.
+pub fn diff(before: &Ticket, after: &Ticket) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &str, before: Value, after: Value| {
+        if before != after {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                before,
+                after,
+            });
+        }
+    };
+    compare("title", before.title.as_str().into(), after.title.as_str().into());
+    compare(
+        "description",
+        before.description.as_str().into(),
+        after.description.as_str().into(),
+    );
+    compare("status", before.status.as_str().into(), after.status.as_str().into());
+    compare("archived", before.archived.into(), after.archived.into());
+    let user = |user: &Option<UserId>| user.as_ref().map_or(Value::Null, |user| user.as_str().into());
+    compare("assignee", user(&before.assignee), user(&after.assignee));
+    compare("reporter", user(&before.reporter), user(&after.reporter));
+    let labels = |ticket: &Ticket| ticket.labels.iter().map(|label| label.as_str()).collect::<Vec<_>>().into();
+    compare("labels", labels(before), labels(after));
+    compare("priority", before.priority.as_str().into(), after.priority.as_str().into());
+    let date = |ticket: &Ticket| ticket.due_date.map_or(Value::Null, |date| date.to_string().into());
+    compare("due_date", date(before), date(after));
+    changes
+}
+
@@ -11,6 +11,26 @@
 // (if any) to build this system.
 
 pub mod api;
+pub mod batch;
+pub mod bulk;
+pub mod client;
+pub mod clock;
+pub mod comment;
+pub mod config;
 pub mod server;
 pub mod data;
+pub mod error;
+pub mod events;
+pub mod filter;
+pub mod history;
+pub mod link;
+pub mod patch;
+pub mod persistent;
+pub mod repository;
+pub mod retry;
+pub mod search;
+pub mod sqlite;
 pub mod store;
+pub mod user;
+pub mod webhook;
+pub mod workflow;
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ticket_fields::TicketTitle;
+
+use crate::data::Status;
+use crate::history::FieldChange;
+use crate::store::TicketId;
+
+// Links between tickets. A link reads "source <kind> target": 1 is the parent of 4,
+// 3 blocks 5, 7 duplicates 2. The reverse relations (child of, blocked by, duplicated by)
+// are the same links, seen from their target.
+//
+// The stores keep the `parent_of` and `blocks` links free of cycles, and a ticket has
+// at most one parent. A ticket can't be moved to `Done` while a ticket that blocks it is open.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    ParentOf,
+    Blocks,
+    Duplicates,
+}
+
+impl LinkKind {
+    pub const ALL: [LinkKind; 3] = [LinkKind::ParentOf, LinkKind::Blocks, LinkKind::Duplicates];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::ParentOf => "parent_of",
+            LinkKind::Blocks => "blocks",
+            LinkKind::Duplicates => "duplicates",
+        }
+    }
+
+    // Whether following links of this kind must never lead back to where we started
+    pub fn is_acyclic(&self) -> bool {
+        matches!(self, LinkKind::ParentOf | LinkKind::Blocks)
+    }
+}
+
+impl std::fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// The parsing is case-insensitive, and accepts dashes for underscores (`parent-of`)
+impl TryFrom<&str> for LinkKind {
+    type Error = ParseLinkKindError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "parent_of" => Ok(LinkKind::ParentOf),
+            "blocks" => Ok(LinkKind::Blocks),
+            "duplicates" => Ok(LinkKind::Duplicates),
+            _ => Err(ParseLinkKindError {
+                invalid_kind: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for LinkKind {
+    type Error = ParseLinkKindError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        LinkKind::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{invalid_kind}` is not a kind of link. Use one of: parent_of, blocks, duplicates")]
+pub struct ParseLinkKindError {
+    invalid_kind: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TicketLink {
+    pub source: TicketId,
+    pub kind: LinkKind,
+    pub target: TicketId,
+}
+
+impl std::fmt::Display for TicketLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.source, self.kind, self.target)
+    }
+}
+
+// Body of GET /tickets/:id/links: the links of a ticket, in both directions, by id
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketLinks {
+    pub parent: Option<TicketId>,
+    pub children: Vec<TicketId>,
+    pub blocks: Vec<TicketId>,
+    pub blocked_by: Vec<TicketId>,
+    pub duplicates: Vec<TicketId>,
+    pub duplicated_by: Vec<TicketId>,
+}
+
+impl TicketLinks {
+    // Sort the links of ticket `id` (from it or to it) into the relations they stand for
+    pub fn of(id: TicketId, links: impl IntoIterator<Item = TicketLink>) -> Self {
+        let mut sorted = TicketLinks::default();
+        for link in links {
+            match (link.kind, link.source == id) {
+                (LinkKind::ParentOf, true) => sorted.children.push(link.target),
+                (LinkKind::ParentOf, false) => sorted.parent = Some(link.source),
+                (LinkKind::Blocks, true) => sorted.blocks.push(link.target),
+                (LinkKind::Blocks, false) => sorted.blocked_by.push(link.source),
+                (LinkKind::Duplicates, true) => sorted.duplicates.push(link.target),
+                (LinkKind::Duplicates, false) => sorted.duplicated_by.push(link.source),
+            }
+        }
+        for ids in [
+            &mut sorted.children,
+            &mut sorted.blocks,
+            &mut sorted.blocked_by,
+            &mut sorted.duplicates,
+            &mut sorted.duplicated_by,
+        ] {
+            ids.sort();
+        }
+        sorted
+    }
+
+    // The links that start from ticket `id`, i.e. the ones to recreate to restore these links
+    pub fn outgoing(&self, id: TicketId) -> Vec<TicketLink> {
+        let link = |kind, target| TicketLink { source: id, kind, target };
+        let children = self.children.iter().map(|&target| link(LinkKind::ParentOf, target));
+        let blocks = self.blocks.iter().map(|&target| link(LinkKind::Blocks, target));
+        let duplicates = self.duplicates.iter().map(|&target| link(LinkKind::Duplicates, target));
+        children.chain(blocks).chain(duplicates).collect()
+    }
+}
+
+// Body of GET /tickets/:id/dependencies: the tickets that block a ticket, the ones that
+// block them, and so on. A ticket that blocks several others appears under each of them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyTree {
+    pub id: TicketId,
+    pub title: TicketTitle,
+    pub status: Status,
+    pub blocked_by: Vec<DependencyTree>,
+}
+
+impl DependencyTree {
+    // Build the tree of ticket `id`. `node` gives the title and the status of a ticket,
+    // and the tickets that block it. The `blocks` links have no cycle, so this terminates.
+    pub(crate) fn build<E>(
+        id: TicketId,
+        node: &mut impl FnMut(TicketId) -> Result<(TicketTitle, Status, Vec<TicketId>), E>,
+    ) -> Result<Self, E> {
+        let (title, status, blockers) = node(id)?;
+        let blocked_by = blockers
+            .into_iter()
+            .map(|blocker| DependencyTree::build(blocker, node))
+            .collect::<Result<_, _>>()?;
+        Ok(DependencyTree {
+            id,
+            title,
+            status,
+            blocked_by,
+        })
+    }
+}
+
+// A link appears in the history of its source ticket as the field `links/<kind>`:
+// the target is the value after the change when the link is added, before it when it's removed
+pub fn change(link: &TicketLink, added: bool) -> FieldChange {
+    let target = Value::from(link.target.0);
+    let (before, after) = if added { (Value::Null, target) } else { (target, Value::Null) };
+    FieldChange {
+        field: format!("links/{}", link.kind),
+        before,
+        after,
+    }
+}
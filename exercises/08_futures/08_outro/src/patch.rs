@@ -0,0 +1,216 @@
+use chrono::NaiveDate;
+use json_patch::{Patch, PatchErrorKind};
+use serde_json::{json, Map, Value};
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::data::{Priority, Status, Ticket, TicketPatch};
+use crate::error::{ApiError, FieldError};
+use crate::user::UserId;
+
+// Media types accepted by PATCH /tickets/:id
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+// A patch document, applied to the JSON representation of a ticket:
+// {"id": 0, "title": "...", "description": "...", "status": "ToDo", "archived": false, "version": 1,
+//  "assignee": "alice", "reporter": null, "labels": ["bug"], "priority": "P2", "due_date": "2024-06-30"}
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchDocument {
+    // RFC 7396: an object whose fields replace those of the ticket
+    Merge(Value),
+    // RFC 6902: a list of operations (add, remove, replace, move, copy, test)
+    Json(Patch),
+}
+
+impl PatchDocument {
+    // Parse a request body, according to its content type.
+    // `application/json` is treated as a merge patch.
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<Self, ApiError> {
+        // Ignore parameters, e.g. "; charset=utf-8"
+        let media_type = content_type
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_lowercase());
+        let malformed = |err: serde_json::Error| ApiError::BadRequest(err.to_string());
+
+        match media_type.as_deref() {
+            Some(MERGE_PATCH_CONTENT_TYPE) | Some("application/json") => {
+                Ok(PatchDocument::Merge(serde_json::from_slice(body).map_err(malformed)?))
+            }
+            Some(JSON_PATCH_CONTENT_TYPE) => {
+                Ok(PatchDocument::Json(serde_json::from_slice(body).map_err(malformed)?))
+            }
+            _ => Err(ApiError::UnsupportedMediaType(format!(
+                "expected {} or {}",
+                MERGE_PATCH_CONTENT_TYPE, JSON_PATCH_CONTENT_TYPE
+            ))),
+        }
+    }
+
+    // Apply the document to `ticket`, and turn the result into a `TicketPatch`
+    // that sets every editable field.
+    // All the invalid fields are reported at once, see `ApiError::InvalidFields`.
+    pub fn apply(&self, ticket: &Ticket) -> Result<TicketPatch, ApiError> {
+        let original = json!({
+            "id": ticket.id,
+            "title": ticket.title,
+            "description": ticket.description,
+            "status": ticket.status,
+            "archived": ticket.archived,
+            "version": ticket.version,
+            "assignee": ticket.assignee,
+            "reporter": ticket.reporter,
+            "labels": ticket.labels,
+            "priority": ticket.priority,
+            "due_date": ticket.due_date,
+        });
+        let mut document = original.clone();
+        let Value::Object(original) = original else {
+            unreachable!("a JSON object literal")
+        };
+
+        match self {
+            PatchDocument::Merge(patch) => {
+                if !patch.is_object() {
+                    return Err(ApiError::BadRequest(
+                        "a merge patch must be a JSON object".to_string(),
+                    ));
+                }
+                json_patch::merge(&mut document, patch);
+            }
+            PatchDocument::Json(patch) => {
+                json_patch::patch(&mut document, patch).map_err(|err| match err.kind {
+                    // A `test` operation is a precondition on the current state of the ticket
+                    PatchErrorKind::TestFailed => ApiError::Conflict(err.to_string()),
+                    _ => ApiError::Validation(err.to_string()),
+                })?;
+            }
+        }
+
+        let Value::Object(fields) = document else {
+            return Err(ApiError::Validation(
+                "the patched ticket must be a JSON object".to_string(),
+            ));
+        };
+        validate(ticket, &original, fields)
+    }
+}
+
+fn validate(
+    ticket: &Ticket,
+    original: &Map<String, Value>,
+    mut fields: Map<String, Value>,
+) -> Result<TicketPatch, ApiError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(FieldError {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    let title = take_string(&mut fields, "title")
+        .and_then(|title| TicketTitle::try_from(title).map_err(|err| err.to_string()))
+        .map_err(|message| error("title", message))
+        .ok();
+    let description = take_string(&mut fields, "description")
+        .and_then(|description| TicketDescription::try_from(description).map_err(|err| err.to_string()))
+        .map_err(|message| error("description", message))
+        .ok();
+    let status = take_string(&mut fields, "status")
+        .and_then(|status| Status::try_from(status).map_err(|err| err.to_string()))
+        .map_err(|message| error("status", message))
+        .ok()
+        // The document holds the current status even when the client didn't touch it: that is no transition
+        .filter(|status| *status != ticket.status);
+    let priority = take_string(&mut fields, "priority")
+        .and_then(|priority| Priority::try_from(priority).map_err(|err| err.to_string()))
+        .map_err(|message| error("priority", message))
+        .ok();
+    let due_date = take_date(&mut fields, "due_date")
+        .map_err(|message| error("due_date", message))
+        .ok();
+
+    let assignee = take_user(&mut fields, "assignee")
+        .map_err(|message| error("assignee", message))
+        .ok();
+    let reporter = take_user(&mut fields, "reporter")
+        .map_err(|message| error("reporter", message))
+        .ok();
+
+    // The other fields can't be changed (nor removed) with a patch
+    for (field, value) in original {
+        if EDITABLE_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        match fields.remove(field) {
+            Some(patched) if patched == *value => {}
+            _ if field == "archived" => error(
+                field,
+                "use POST /tickets/:id/archive to archive a ticket".to_string(),
+            ),
+            _ if field == "labels" => error(
+                field,
+                "use PUT or DELETE /tickets/:id/labels/:label to change the labels of a ticket".to_string(),
+            ),
+            _ => error(field, "this field is read-only".to_string()),
+        }
+    }
+    for field in fields.keys() {
+        error(field, "unknown field".to_string());
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::InvalidFields(errors));
+    }
+    Ok(TicketPatch {
+        id: ticket.id,
+        title,
+        description,
+        status,
+        assignee,
+        reporter,
+        priority,
+        due_date,
+    })
+}
+
+const EDITABLE_FIELDS: [&str; 7] = [
+    "title",
+    "description",
+    "status",
+    "assignee",
+    "reporter",
+    "priority",
+    "due_date",
+];
+
+// Every editable field is required, and is a string...
+fn take_string(fields: &mut Map<String, Value>, field: &str) -> Result<String, String> {
+    match fields.remove(field) {
+        Some(Value::String(value)) => Ok(value),
+        None | Some(Value::Null) => Err("this field is required".to_string()),
+        Some(_) => Err("expected a string".to_string()),
+    }
+}
+
+// ...except for the users, that can be removed from the ticket: `null` (or removing the field)
+// clears them. The store checks that they are registered (see `TicketStoreError::UnknownUser`).
+fn take_user(fields: &mut Map<String, Value>, field: &str) -> Result<Option<UserId>, String> {
+    match fields.remove(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(id)) => UserId::try_from(id).map(Some).map_err(|err| err.to_string()),
+        Some(_) => Err("expected a string or null".to_string()),
+    }
+}
+
+// ...and for the due date, that can be cleared the same way. A calendar date, "YYYY-MM-DD".
+fn take_date(fields: &mut Map<String, Value>, field: &str) -> Result<Option<NaiveDate>, String> {
+    match fields.remove(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(date)) => date
+            .parse::<NaiveDate>()
+            .map(Some)
+            .map_err(|err| format!("expected a date like 2024-06-30: {}", err)),
+        Some(_) => Err("expected a string or null".to_string()),
+    }
+}
@@ -0,0 +1,742 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::batch::{BatchError, BatchOperation, BatchOutcome};
+use crate::bulk::{self, Format, IdPolicy, ImportReport, ImportTarget, TicketRecord};
+use crate::clock::Clock;
+use crate::comment::{CommentDraft, CommentId, TicketComment};
+use crate::data::{Ticket, TicketDraft, TicketPage, TicketPatch, TicketQuery};
+use crate::events::EventBus;
+use crate::filter::Filter;
+use crate::history::{Actor, HistoryEntry};
+use crate::link::{DependencyTree, TicketLink, TicketLinks};
+use crate::search::{SearchHit, SearchQuery};
+use crate::store::{StoreSnapshot, TicketId, TicketStore, TicketStoreError};
+use crate::user::{User, UserId};
+use crate::workflow::Workflow;
+use ticket_fields::{CommentBody, TicketLabel};
+
+// A `TicketStore` that survives restarts.
+//
+// Every operation is appended to a write-ahead log (one JSON object per line) and flushed
+// to disk before returning. On startup the latest snapshot is loaded and the log is replayed
+// on top of it. Every `compact_every` operations, the store is written to a new snapshot
+// and the log is truncated, so that replay time stays bounded.
+//
+// Data directory layout:
+//  - snapshot.json: the store as of operation number `seq`
+//  - wal.jsonl: the operations applied after the snapshot, each tagged with its `seq`
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.json.tmp";
+const WAL_FILE: &str = "wal.jsonl";
+
+pub const DEFAULT_COMPACT_EVERY: usize = 1000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PersistenceError {
+    #[error("I/O error on the data directory: {0}")]
+    Io(#[from] io::Error),
+    #[error("The snapshot is corrupted: {0}")]
+    CorruptSnapshot(serde_json::Error),
+    #[error("The write-ahead log is corrupted at line {line}: {reason}")]
+    CorruptWal { line: usize, reason: String },
+    #[error(transparent)]
+    Store(#[from] TicketStoreError),
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+}
+
+// An operation, as recorded in the write-ahead log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum WalOp {
+    Insert { id: TicketId, draft: TicketDraft },
+    // Entries written before the history existed have no actor nor timestamp
+    Patch {
+        patch: TicketPatch,
+        #[serde(default)]
+        actor: Actor,
+        #[serde(default)]
+        timestamp: u64,
+    },
+    // Entries written before archiving was recorded in the history have no actor nor timestamp
+    Archive {
+        id: TicketId,
+        #[serde(default)]
+        actor: Actor,
+        #[serde(default)]
+        timestamp: u64,
+    },
+    Remove { id: TicketId },
+    AddComment {
+        ticket_id: TicketId,
+        id: CommentId,
+        draft: CommentDraft,
+        actor: Actor,
+        timestamp: u64,
+    },
+    EditComment {
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+        timestamp: u64,
+    },
+    DeleteComment {
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: Actor,
+        timestamp: u64,
+    },
+    AddLabel {
+        id: TicketId,
+        label: TicketLabel,
+        actor: Actor,
+        timestamp: u64,
+    },
+    RemoveLabel {
+        id: TicketId,
+        label: TicketLabel,
+        actor: Actor,
+        timestamp: u64,
+    },
+    AddUser { user: User },
+    RemoveUser { id: UserId },
+    AddLink {
+        link: TicketLink,
+        actor: Actor,
+        timestamp: u64,
+    },
+    RemoveLink {
+        link: TicketLink,
+        actor: Actor,
+        timestamp: u64,
+    },
+    // A ticket restored as it was imported, with its id and its comments
+    Import {
+        ticket: Ticket,
+        // Missing from the entries written before imports had comments
+        #[serde(default)]
+        comments: Vec<TicketComment>,
+    },
+    // A whole batch in a single entry: after a crash, it is replayed entirely or not at all
+    Batch {
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+        timestamp: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct WalEntry {
+    seq: u64,
+    #[serde(flatten)]
+    op: WalOp,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    // Sequence number of the last operation included in the snapshot
+    seq: u64,
+    store: StoreSnapshot,
+}
+
+pub struct PersistentTicketStore {
+    store: TicketStore,
+    dir: PathBuf,
+    wal: File,
+    // Sequence number of the last operation applied to `store`
+    seq: u64,
+    ops_since_snapshot: usize,
+    compact_every: usize,
+    // Why the last automatic compaction failed, until one succeeds
+    compaction_error: Option<PersistenceError>,
+}
+
+impl PersistentTicketStore {
+    // Open the store saved in `dir`, creating the directory if it doesn't exist
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (store, seq, ops_since_snapshot) = load(&dir)?;
+        let store = store.with_workflow(Workflow::standard());
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(WAL_FILE))?;
+
+        Ok(Self {
+            store,
+            dir,
+            wal,
+            seq,
+            ops_since_snapshot,
+            compact_every: DEFAULT_COMPACT_EVERY,
+            compaction_error: None,
+        })
+    }
+
+    // Status changes are checked against `workflow` (the standard one by default).
+    // The log is always replayed without restrictions: it only holds accepted operations.
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.store = self.store.with_workflow(workflow);
+        self
+    }
+
+    // Where the timestamps of the history and "today" come from (the system clock by default)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.store = self.store.with_clock(clock);
+        self
+    }
+
+    // Number of operations after which the log is compacted into a new snapshot
+    pub fn compact_every(mut self, ops: usize) -> Self {
+        self.compact_every = ops.max(1);
+        self
+    }
+
+    // The error of the last automatic compaction, if it failed: the operations are still logged,
+    // but the log keeps growing until a compaction succeeds
+    pub fn last_compaction_error(&self) -> Option<&PersistenceError> {
+        self.compaction_error.as_ref()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // The bus the changes are announced on, once they are in the log
+    pub fn events(&self) -> &EventBus {
+        self.store.events()
+    }
+
+    pub fn add_ticket(&mut self, draft: TicketDraft) -> Result<TicketId, PersistenceError> {
+        let id = self.store.add_ticket(draft.clone())?;
+        self.log(WalOp::Insert { id, draft })?;
+        Ok(id)
+    }
+
+    pub fn get(&self, id: TicketId) -> Option<Ticket> {
+        self.store.get(id).map(|ticket| ticket.read().unwrap().clone())
+    }
+
+    pub fn get_mut(&mut self, patch: TicketPatch) -> Result<(), PersistenceError> {
+        self.patch_by(patch, None, &Actor::system())?;
+        Ok(())
+    }
+
+    // See `TicketStore::patch_if_version`
+    pub fn patch_if_version(&mut self, patch: TicketPatch, expected: u64) -> Result<Ticket, PersistenceError> {
+        self.patch_by(patch, Some(expected), &Actor::system())
+    }
+
+    // See `TicketStore::patch_by`. The patch is logged as a plain one: once the version has
+    // been checked, replaying it unconditionally gives the same result.
+    // The actor and the timestamp are logged too, so that the history is replayed as it was.
+    pub fn patch_by(
+        &mut self,
+        patch: TicketPatch,
+        expected: Option<u64>,
+        actor: &Actor,
+    ) -> Result<Ticket, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let ticket = self.store.patch_at(patch.clone(), expected, actor, timestamp)?;
+        self.log(WalOp::Patch {
+            patch,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(ticket)
+    }
+
+    pub fn history(&self, id: TicketId) -> Vec<HistoryEntry> {
+        self.store.history(id).to_vec()
+    }
+
+    // See `TicketStore::add_comment`
+    pub fn add_comment(
+        &mut self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: &Actor,
+    ) -> Result<TicketComment, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let comment = self.store.add_comment_at(ticket_id, draft.clone(), actor, timestamp)?;
+        self.log(WalOp::AddComment {
+            ticket_id,
+            id: comment.id,
+            draft,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(comment)
+    }
+
+    pub fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, PersistenceError> {
+        Ok(self.store.comments(ticket_id)?)
+    }
+
+    pub fn edit_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: &Actor,
+    ) -> Result<TicketComment, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let comment = self
+            .store
+            .edit_comment_at(ticket_id, comment_id, body.clone(), actor, timestamp)?;
+        self.log(WalOp::EditComment {
+            ticket_id,
+            comment_id,
+            body,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(comment)
+    }
+
+    pub fn delete_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: &Actor,
+    ) -> Result<TicketComment, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let comment = self.store.delete_comment_at(ticket_id, comment_id, actor, timestamp)?;
+        self.log(WalOp::DeleteComment {
+            ticket_id,
+            comment_id,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(comment)
+    }
+
+    // See `TicketStore::add_label`
+    pub fn add_label(&mut self, id: TicketId, label: TicketLabel, actor: &Actor) -> Result<Ticket, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let ticket = self.store.add_label_at(id, label.clone(), actor, timestamp)?;
+        self.log(WalOp::AddLabel {
+            id,
+            label,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(ticket)
+    }
+
+    pub fn remove_label(&mut self, id: TicketId, label: &TicketLabel, actor: &Actor) -> Result<Ticket, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let ticket = self.store.remove_label_at(id, label, actor, timestamp)?;
+        self.log(WalOp::RemoveLabel {
+            id,
+            label: label.clone(),
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(ticket)
+    }
+
+    // See `TicketStore::add_link`
+    pub fn add_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let links = self.store.add_link_at(link, actor, timestamp)?;
+        self.log(WalOp::AddLink {
+            link,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(links)
+    }
+
+    pub fn remove_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let links = self.store.remove_link_at(link, actor, timestamp)?;
+        self.log(WalOp::RemoveLink {
+            link,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(links)
+    }
+
+    pub fn links(&self, id: TicketId) -> Result<TicketLinks, PersistenceError> {
+        Ok(self.store.links(id)?)
+    }
+
+    pub fn dependencies(&self, id: TicketId) -> Result<DependencyTree, PersistenceError> {
+        Ok(self.store.dependencies(id)?)
+    }
+
+    // See `TicketStore::add_user`
+    pub fn add_user(&mut self, user: User) -> Result<User, PersistenceError> {
+        let user = self.store.add_user(user)?;
+        self.log(WalOp::AddUser { user: user.clone() })?;
+        Ok(user)
+    }
+
+    pub fn get_user(&self, id: &UserId) -> Option<User> {
+        self.store.get_user(id)
+    }
+
+    pub fn users(&self) -> Vec<User> {
+        self.store.users()
+    }
+
+    pub fn remove_user(&mut self, id: &UserId) -> Result<User, PersistenceError> {
+        let user = self.store.remove_user(id)?;
+        self.log(WalOp::RemoveUser { id: id.clone() })?;
+        Ok(user)
+    }
+
+    pub fn archive(&mut self, id: TicketId, actor: &Actor) -> Result<Ticket, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let ticket = self.store.archive_at(id, actor, timestamp)?;
+        self.log(WalOp::Archive {
+            id,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(ticket)
+    }
+
+    pub fn remove(&mut self, id: TicketId) -> Result<Ticket, PersistenceError> {
+        let ticket = self.store.remove(id)?;
+        self.log(WalOp::Remove { id })?;
+        Ok(ticket)
+    }
+
+    pub fn list(&self, query: &TicketQuery) -> Result<TicketPage, TicketStoreError> {
+        self.store.list(query)
+    }
+
+    // See `TicketStore::filter`
+    pub fn filter<'a>(&'a self, filter: &'a Filter) -> impl Iterator<Item = impl Deref<Target = Ticket> + 'a> + 'a {
+        self.store.filter(filter)
+    }
+
+    // The index is rebuilt from the tickets on open: searching doesn't touch the disk
+    pub fn search(&self, query: &SearchQuery, limit: Option<usize>) -> Vec<SearchHit> {
+        self.store.search(query, limit)
+    }
+
+    // See `TicketStore::apply_batch`
+    pub fn apply_batch(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        actor: &Actor,
+    ) -> Result<Vec<BatchOutcome>, PersistenceError> {
+        let timestamp = self.store.clock().now_millis();
+        let outcomes = self.store.apply_batch_at(operations.clone(), actor, timestamp)?;
+        self.log(WalOp::Batch {
+            operations,
+            actor: actor.clone(),
+            timestamp,
+        })?;
+        Ok(outcomes)
+    }
+
+    // See `TicketStore::import`. Each imported user, ticket and link is logged on its own.
+    pub fn import(
+        &mut self,
+        format: Format,
+        reader: impl BufRead,
+        ids: IdPolicy,
+    ) -> Result<ImportReport, PersistenceError> {
+        bulk::import_into(self, format, reader, ids)
+    }
+
+    pub fn export(&self, format: Format, writer: impl Write) -> io::Result<usize> {
+        self.store.export(format, writer)
+    }
+
+    // Write the current state to a new snapshot and truncate the log
+    pub fn compact(&mut self) -> Result<(), PersistenceError> {
+        let snapshot = Snapshot {
+            seq: self.seq,
+            store: self.store.snapshot(),
+        };
+        let bytes = serde_json::to_vec(&snapshot).map_err(io::Error::from)?;
+
+        // Write to a temporary file first, then rename it: a crash leaves either the old
+        // or the new snapshot in place, never a half-written one
+        let tmp_path = self.dir.join(SNAPSHOT_TMP_FILE);
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&bytes)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        // The rename has to reach the disk before the truncation does: otherwise a crash could
+        // leave the old snapshot with an empty log, losing the entries in between
+        sync_dir(&self.dir)?;
+
+        // If we crash before the truncation, the entries already in the snapshot
+        // are skipped on replay thanks to their sequence number
+        self.wal.set_len(0)?;
+        self.wal.sync_all()?;
+        self.ops_since_snapshot = 0;
+        Ok(())
+    }
+
+    // Append an operation that was just applied to the in-memory store
+    fn log(&mut self, op: WalOp) -> Result<(), PersistenceError> {
+        let entry = WalEntry {
+            seq: self.seq + 1,
+            op,
+        };
+        if let Err(err) = self.append(&entry) {
+            // The operation is in memory but not on disk: reload the store from disk,
+            // so that what clients see matches what survives a restart
+            let (store, seq, ops_since_snapshot) = load(&self.dir)?;
+            self.store = store
+                .with_workflow(self.store.workflow().clone())
+                .with_clock(self.store.clock().clone())
+                .with_events(self.store.events().clone());
+            self.seq = seq;
+            self.ops_since_snapshot = ops_since_snapshot;
+            return Err(err.into());
+        }
+        self.seq = entry.seq;
+        self.ops_since_snapshot += 1;
+        // Now that the operation is durable, its changes can be announced
+        self.store.flush_events();
+
+        if self.ops_since_snapshot >= self.compact_every {
+            // The operation itself is already durable: a failed compaction is retried
+            // on the next operation, it must not be reported as a failure of this one
+            self.compaction_error = self.compact().err();
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, entry: &WalEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        // A single write, so that a crash leaves at most one torn line at the end of the log
+        self.wal.write_all(&line)?;
+        self.wal.sync_data()
+    }
+}
+
+// Load the snapshot and replay the log on top of it.
+// Returns the store, the sequence number of the last operation and the number of
+// operations replayed from the log.
+fn load(dir: &Path) -> Result<(TicketStore, u64, usize), PersistenceError> {
+    let (store, snapshot_seq) = match fs::read(dir.join(SNAPSHOT_FILE)) {
+        Ok(bytes) => {
+            let snapshot: Snapshot =
+                serde_json::from_slice(&bytes).map_err(PersistenceError::CorruptSnapshot)?;
+            (TicketStore::from_snapshot(snapshot.store), snapshot.seq)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (TicketStore::new(), 0),
+        Err(err) => return Err(err.into()),
+    };
+    // The log may hold transitions that the current workflow doesn't allow anymore.
+    // The changes are announced once they are in the log (see `PersistentTicketStore::log`).
+    let mut store = store.with_workflow(Workflow::unrestricted()).defer_events();
+
+    let wal_path = dir.join(WAL_FILE);
+    let content = match fs::read_to_string(&wal_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut seq = snapshot_seq;
+    let mut replayed = 0;
+    let mut valid_len = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        if !line.ends_with('\n') {
+            // The process crashed while appending this entry: it was never acknowledged,
+            // so we drop it
+            break;
+        }
+        let entry: WalEntry = serde_json::from_str(line).map_err(|err| PersistenceError::CorruptWal {
+            line: line_number,
+            reason: err.to_string(),
+        })?;
+        valid_len += line.len();
+
+        if entry.seq <= seq {
+            // Already part of the snapshot
+            continue;
+        }
+        if entry.seq != seq + 1 {
+            return Err(PersistenceError::CorruptWal {
+                line: line_number,
+                reason: format!("expected operation {}, found {}", seq + 1, entry.seq),
+            });
+        }
+        replay(&mut store, entry.op).map_err(|reason| PersistenceError::CorruptWal {
+            line: line_number,
+            reason,
+        })?;
+        seq = entry.seq;
+        replayed += 1;
+    }
+    // Replayed changes aren't news: subscribers only hear about the changes made from now on
+    store.discard_events();
+
+    if valid_len < content.len() {
+        // Cut the torn entry off, so that the next append starts on a fresh line
+        let wal = OpenOptions::new().write(true).open(&wal_path)?;
+        wal.set_len(valid_len as u64)?;
+        wal.sync_all()?;
+    }
+
+    Ok((store, seq, replayed))
+}
+
+fn replay(store: &mut TicketStore, op: WalOp) -> Result<(), String> {
+    match op {
+        WalOp::Insert { id, draft } => {
+            let assigned = store.add_ticket(draft).map_err(|err| err.to_string())?;
+            if assigned != id {
+                return Err(format!("ticket {} was replayed as ticket {}", id, assigned));
+            }
+        }
+        WalOp::Patch {
+            patch,
+            actor,
+            timestamp,
+        } => {
+            store
+                .patch_at(patch, None, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::Batch {
+            operations,
+            actor,
+            timestamp,
+        } => {
+            store
+                .apply_batch_at(operations, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::Import { ticket, comments } => {
+            store.restore(ticket).map_err(|err| err.to_string())?;
+            for comment in comments {
+                store.restore_comment(comment);
+            }
+        }
+        WalOp::Archive { id, actor, timestamp } => {
+            store.archive_at(id, &actor, timestamp).map_err(|err| err.to_string())?;
+        }
+        WalOp::Remove { id } => {
+            store.remove(id).map_err(|err| err.to_string())?;
+        }
+        WalOp::AddComment {
+            ticket_id,
+            id,
+            draft,
+            actor,
+            timestamp,
+        } => {
+            let comment = store
+                .add_comment_at(ticket_id, draft, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+            if comment.id != id {
+                return Err(format!("comment {} was replayed as comment {}", id, comment.id));
+            }
+        }
+        WalOp::EditComment {
+            ticket_id,
+            comment_id,
+            body,
+            actor,
+            timestamp,
+        } => {
+            store
+                .edit_comment_at(ticket_id, comment_id, body, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::DeleteComment {
+            ticket_id,
+            comment_id,
+            actor,
+            timestamp,
+        } => {
+            store
+                .delete_comment_at(ticket_id, comment_id, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::AddLabel {
+            id,
+            label,
+            actor,
+            timestamp,
+        } => {
+            store
+                .add_label_at(id, label, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::RemoveLabel {
+            id,
+            label,
+            actor,
+            timestamp,
+        } => {
+            store
+                .remove_label_at(id, &label, &actor, timestamp)
+                .map_err(|err| err.to_string())?;
+        }
+        WalOp::AddUser { user } => {
+            store.add_user(user).map_err(|err| err.to_string())?;
+        }
+        WalOp::RemoveUser { id } => {
+            store.remove_user(&id).map_err(|err| err.to_string())?;
+        }
+        WalOp::AddLink { link, actor, timestamp } => {
+            store.add_link_at(link, &actor, timestamp).map_err(|err| err.to_string())?;
+        }
+        WalOp::RemoveLink { link, actor, timestamp } => {
+            store.remove_link_at(link, &actor, timestamp).map_err(|err| err.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// Make the renames in `dir` durable. Only Unix lets a directory be opened and synced.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+// The store errors are the reasons to reject an item; the others stop the import
+fn rejected<T>(result: Result<T, PersistenceError>) -> Result<Result<T, TicketStoreError>, PersistenceError> {
+    match result {
+        Ok(value) => Ok(Ok(value)),
+        Err(PersistenceError::Store(err)) => Ok(Err(err)),
+        Err(err) => Err(err),
+    }
+}
+
+impl ImportTarget for PersistentTicketStore {
+    type Error = PersistenceError;
+
+    fn insert_user(&mut self, user: User) -> Result<Result<(), TicketStoreError>, PersistenceError> {
+        rejected(self.add_user(user).map(|_| ()))
+    }
+
+    fn insert_record(&mut self, record: TicketRecord) -> Result<Result<TicketId, TicketStoreError>, PersistenceError> {
+        let (ticket, comments) = match self.store.insert_record(record) {
+            Ok(imported) => imported,
+            Err(err) => return Ok(Err(err)),
+        };
+        let id = ticket.id;
+        self.log(WalOp::Import { ticket, comments })?;
+        Ok(Ok(id))
+    }
+
+    fn insert_link(&mut self, link: TicketLink) -> Result<Result<(), TicketStoreError>, PersistenceError> {
+        rejected(self.add_link(link, &Actor::system()).map(|_| ()))
+    }
+}
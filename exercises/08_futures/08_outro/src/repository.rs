@@ -0,0 +1,717 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::batch::{BatchError, BatchOperation, BatchOutcome};
+use crate::bulk::{Format, ImportParams, ImportReport};
+use crate::comment::{CommentDraft, CommentId, TicketComment};
+use crate::data::{Ticket, TicketDraft, TicketPage, TicketPatch, TicketQuery};
+use crate::events::EventBus;
+use crate::history::{Actor, HistoryEntry};
+use crate::link::{DependencyTree, TicketLink, TicketLinks};
+use crate::persistent::{PersistenceError, PersistentTicketStore};
+use crate::search::{SearchHit, SearchQuery};
+use crate::sqlite::{SqliteError, SqliteTicketStore};
+use crate::store::{TicketId, TicketStore, TicketStoreError};
+use crate::user::{User, UserId};
+use ticket_fields::{CommentBody, TicketLabel};
+
+// The storage used by the API handlers.
+//
+// The handlers only talk to this trait, so that the server can run on top of any backend:
+//  - `Arc<RwLock<TicketStore>>`: the default, in-memory store
+//  - `Arc<RwLock<PersistentTicketStore>>`: the store backed by a write-ahead log on disk
+//  - `Arc<Mutex<SqliteTicketStore>>`: the store backed by an SQLite database
+//
+// Implementations are cheap to clone (a clone is a new handle to the same storage),
+// since axum hands a clone of the repository to every request.
+pub trait TicketRepository: Clone + Send + Sync + 'static {
+    fn insert(
+        &self,
+        draft: TicketDraft,
+    ) -> impl Future<Output = Result<TicketId, RepositoryError>> + Send;
+
+    fn get(&self, id: TicketId) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // Apply the patch on behalf of `actor`, record it in the history of the ticket,
+    // and return the updated ticket
+    fn patch(
+        &self,
+        patch: TicketPatch,
+        actor: Actor,
+    ) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // Same as `patch`, but only if the ticket is still at version `expected`
+    // (see `TicketStore::patch_if_version`)
+    fn patch_if_version(
+        &self,
+        patch: TicketPatch,
+        expected: u64,
+        actor: Actor,
+    ) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // The changes made to the ticket, oldest first (empty for an unknown ticket)
+    fn history(&self, id: TicketId) -> impl Future<Output = Result<Vec<HistoryEntry>, RepositoryError>> + Send;
+
+    fn list(
+        &self,
+        query: TicketQuery,
+    ) -> impl Future<Output = Result<TicketPage, RepositoryError>> + Send;
+
+    // The tickets matching a full-text query, the most relevant first (see `TicketStore::search`)
+    fn search(
+        &self,
+        query: SearchQuery,
+        limit: Option<usize>,
+    ) -> impl Future<Output = Result<Vec<SearchHit>, RepositoryError>> + Send;
+
+    // Apply the operations on behalf of `actor`, all of them or none (see `batch`)
+    fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+    ) -> impl Future<Output = Result<Vec<BatchOutcome>, RepositoryError>> + Send;
+
+    // Add the tickets of a JSON Lines or CSV document (see `bulk`). Invalid records are listed
+    // in the report rather than failing the import.
+    fn import(
+        &self,
+        params: ImportParams,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<ImportReport, RepositoryError>> + Send;
+
+    // Every ticket, archived ones included, in the given format
+    fn export(&self, format: Format) -> impl Future<Output = Result<Vec<u8>, RepositoryError>> + Send;
+
+    // Comments are recorded in the history of their ticket, on behalf of `actor`
+    // (who is the author of the comments added)
+    fn add_comment(
+        &self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: Actor,
+    ) -> impl Future<Output = Result<TicketComment, RepositoryError>> + Send;
+
+    // The comments of the ticket, in the order they were added
+    fn comments(
+        &self,
+        ticket_id: TicketId,
+    ) -> impl Future<Output = Result<Vec<TicketComment>, RepositoryError>> + Send;
+
+    fn edit_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+    ) -> impl Future<Output = Result<TicketComment, RepositoryError>> + Send;
+
+    // Remove the comment and return it
+    fn delete_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: Actor,
+    ) -> impl Future<Output = Result<TicketComment, RepositoryError>> + Send;
+
+    // Add a label to the ticket (a no-op if it already has it) and return the updated ticket
+    fn add_label(
+        &self,
+        id: TicketId,
+        label: TicketLabel,
+        actor: Actor,
+    ) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // Remove a label from the ticket (a no-op if it doesn't have it) and return the updated ticket
+    fn remove_label(
+        &self,
+        id: TicketId,
+        label: TicketLabel,
+        actor: Actor,
+    ) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // Link two tickets (a no-op if they already are) and return the links of the source ticket
+    fn add_link(
+        &self,
+        link: TicketLink,
+        actor: Actor,
+    ) -> impl Future<Output = Result<TicketLinks, RepositoryError>> + Send;
+
+    // Remove a link (a no-op if there is none) and return the links of the source ticket
+    fn remove_link(
+        &self,
+        link: TicketLink,
+        actor: Actor,
+    ) -> impl Future<Output = Result<TicketLinks, RepositoryError>> + Send;
+
+    // The links of the ticket, in both directions
+    fn links(&self, id: TicketId) -> impl Future<Output = Result<TicketLinks, RepositoryError>> + Send;
+
+    // The tickets that block the ticket, recursively (see `TicketStore::dependencies`)
+    fn dependencies(&self, id: TicketId) -> impl Future<Output = Result<DependencyTree, RepositoryError>> + Send;
+
+    // Register a user (see `TicketStore::add_user`)
+    fn insert_user(&self, user: User) -> impl Future<Output = Result<User, RepositoryError>> + Send;
+
+    fn get_user(&self, id: UserId) -> impl Future<Output = Result<User, RepositoryError>> + Send;
+
+    fn list_users(&self) -> impl Future<Output = Result<Vec<User>, RepositoryError>> + Send;
+
+    // Unregister the user and return it
+    fn delete_user(&self, id: UserId) -> impl Future<Output = Result<User, RepositoryError>> + Send;
+
+    // Archive the ticket on behalf of `actor`, and return it
+    fn archive(&self, id: TicketId, actor: Actor) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // Remove the ticket and return it
+    fn delete(&self, id: TicketId) -> impl Future<Output = Result<Ticket, RepositoryError>> + Send;
+
+    // The bus the changes to the tickets are announced on (see `events`)
+    fn events(&self) -> impl Future<Output = Result<EventBus, RepositoryError>> + Send;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryError {
+    // The operation was rejected by the store (e.g. unknown ticket)
+    #[error(transparent)]
+    Store(#[from] TicketStoreError),
+    // Some operations of a batch were rejected, so none was applied
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+    // The storage itself failed (I/O error, poisoned lock, ...)
+    #[error("Storage failure: {0}")]
+    Backend(String),
+}
+
+impl From<PersistenceError> for RepositoryError {
+    fn from(err: PersistenceError) -> Self {
+        match err {
+            PersistenceError::Store(err) => RepositoryError::Store(err),
+            PersistenceError::Batch(err) => RepositoryError::Batch(err),
+            other => RepositoryError::Backend(other.to_string()),
+        }
+    }
+}
+
+impl From<SqliteError> for RepositoryError {
+    fn from(err: SqliteError) -> Self {
+        match err {
+            SqliteError::Store(err) => RepositoryError::Store(err),
+            SqliteError::Batch(err) => RepositoryError::Batch(err),
+            other => RepositoryError::Backend(other.to_string()),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for RepositoryError {
+    fn from(err: PoisonError<T>) -> Self {
+        RepositoryError::Backend(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for RepositoryError {
+    fn from(err: std::io::Error) -> Self {
+        RepositoryError::Backend(err.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for RepositoryError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        RepositoryError::Backend(err.to_string())
+    }
+}
+
+impl TicketRepository for Arc<RwLock<TicketStore>> {
+    async fn insert(&self, draft: TicketDraft) -> Result<TicketId, RepositoryError> {
+        Ok(self.write()?.add_ticket(draft)?)
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        // Acquire the read lock for the TicketStore, then the one for the Ticket
+        let ticket = self.read()?.get(id).ok_or(TicketStoreError::NotFound(id))?;
+        let ticket = ticket.read()?.clone();
+        Ok(ticket)
+    }
+
+    async fn patch(&self, patch: TicketPatch, actor: Actor) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.patch_by(patch, None, &actor)?)
+    }
+
+    async fn patch_if_version(
+        &self,
+        patch: TicketPatch,
+        expected: u64,
+        actor: Actor,
+    ) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.patch_by(patch, Some(expected), &actor)?)
+    }
+
+    async fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        Ok(self.read()?.history(id).to_vec())
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<TicketPage, RepositoryError> {
+        Ok(self.read()?.list(&query)?)
+    }
+
+    async fn search(&self, query: SearchQuery, limit: Option<usize>) -> Result<Vec<SearchHit>, RepositoryError> {
+        Ok(self.read()?.search(&query, limit))
+    }
+
+    async fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+    ) -> Result<Vec<BatchOutcome>, RepositoryError> {
+        Ok(self.write()?.apply_batch(operations, &actor)?)
+    }
+
+    async fn import(&self, params: ImportParams, body: Vec<u8>) -> Result<ImportReport, RepositoryError> {
+        Ok(self.write()?.import(params.format, body.as_slice(), params.ids)?)
+    }
+
+    async fn export(&self, format: Format) -> Result<Vec<u8>, RepositoryError> {
+        let mut body = Vec::new();
+        self.read()?.export(format, &mut body)?;
+        Ok(body)
+    }
+
+    async fn add_comment(
+        &self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        Ok(self.write()?.add_comment(ticket_id, draft, &actor)?)
+    }
+
+    async fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, RepositoryError> {
+        Ok(self.read()?.comments(ticket_id)?)
+    }
+
+    async fn edit_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        Ok(self.write()?.edit_comment(ticket_id, comment_id, body, &actor)?)
+    }
+
+    async fn delete_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        Ok(self.write()?.delete_comment(ticket_id, comment_id, &actor)?)
+    }
+
+    async fn add_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.add_label(id, label, &actor)?)
+    }
+
+    async fn remove_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.remove_label(id, &label, &actor)?)
+    }
+
+    async fn add_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        Ok(self.write()?.add_link(link, &actor)?)
+    }
+
+    async fn remove_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        Ok(self.write()?.remove_link(link, &actor)?)
+    }
+
+    async fn links(&self, id: TicketId) -> Result<TicketLinks, RepositoryError> {
+        Ok(self.read()?.links(id)?)
+    }
+
+    async fn dependencies(&self, id: TicketId) -> Result<DependencyTree, RepositoryError> {
+        Ok(self.read()?.dependencies(id)?)
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, RepositoryError> {
+        Ok(self.write()?.add_user(user)?)
+    }
+
+    async fn get_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        let user = self.read()?.get_user(&id).ok_or(TicketStoreError::UserNotFound(id))?;
+        Ok(user)
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, RepositoryError> {
+        Ok(self.read()?.users())
+    }
+
+    async fn delete_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        Ok(self.write()?.remove_user(&id)?)
+    }
+
+    async fn archive(&self, id: TicketId, actor: Actor) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.archive(id, &actor)?)
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        Ok(self.write()?.remove(id)?)
+    }
+
+    async fn events(&self) -> Result<EventBus, RepositoryError> {
+        Ok(self.read()?.events().clone())
+    }
+}
+
+// Writes wait for the write-ahead log to be flushed to disk: they run on tokio's blocking
+// thread pool, so that they don't stall the other requests handled by the same worker thread.
+// Reads run there too: they wait for the lock, which a writer holds until its entry is flushed.
+impl TicketRepository for Arc<RwLock<PersistentTicketStore>> {
+    async fn insert(&self, draft: TicketDraft) -> Result<TicketId, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.add_ticket(draft)?)).await?
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.get(id).ok_or(TicketStoreError::NotFound(id))?)).await?
+    }
+
+    async fn patch(&self, patch: TicketPatch, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.patch_by(patch, None, &actor)?)).await?
+    }
+
+    async fn patch_if_version(
+        &self,
+        patch: TicketPatch,
+        expected: u64,
+        actor: Actor,
+    ) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.patch_by(patch, Some(expected), &actor)?)).await?
+    }
+
+    async fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.history(id))).await?
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<TicketPage, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.list(&query)?)).await?
+    }
+
+    async fn search(&self, query: SearchQuery, limit: Option<usize>) -> Result<Vec<SearchHit>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.search(&query, limit))).await?
+    }
+
+    async fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+    ) -> Result<Vec<BatchOutcome>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.apply_batch(operations, &actor)?)).await?
+    }
+
+    async fn import(&self, params: ImportParams, body: Vec<u8>) -> Result<ImportReport, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.import(params.format, body.as_slice(), params.ids)?))
+            .await?
+    }
+
+    async fn export(&self, format: Format) -> Result<Vec<u8>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut body = Vec::new();
+            store.read()?.export(format, &mut body)?;
+            Ok(body)
+        })
+        .await?
+    }
+
+    async fn add_comment(
+        &self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.add_comment(ticket_id, draft, &actor)?)).await?
+    }
+
+    async fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.comments(ticket_id)?)).await?
+    }
+
+    async fn edit_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.edit_comment(ticket_id, comment_id, body, &actor)?))
+            .await?
+    }
+
+    async fn delete_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.delete_comment(ticket_id, comment_id, &actor)?)).await?
+    }
+
+    async fn add_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.add_label(id, label, &actor)?)).await?
+    }
+
+    async fn remove_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.remove_label(id, &label, &actor)?)).await?
+    }
+
+    async fn add_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.add_link(link, &actor)?)).await?
+    }
+
+    async fn remove_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.remove_link(link, &actor)?)).await?
+    }
+
+    async fn links(&self, id: TicketId) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.links(id)?)).await?
+    }
+
+    async fn dependencies(&self, id: TicketId) -> Result<DependencyTree, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.dependencies(id)?)).await?
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.add_user(user)?)).await?
+    }
+
+    async fn get_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let user = store.read()?.get_user(&id).ok_or(TicketStoreError::UserNotFound(id))?;
+            Ok(user)
+        })
+        .await?
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.users())).await?
+    }
+
+    async fn delete_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.remove_user(&id)?)).await?
+    }
+
+    async fn archive(&self, id: TicketId, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.archive(id, &actor)?)).await?
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.write()?.remove(id)?)).await?
+    }
+
+    async fn events(&self) -> Result<EventBus, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.read()?.events().clone())).await?
+    }
+}
+
+// An SQLite connection can't be shared between threads, hence the `Mutex`.
+// Queries hit the disk, so they run on tokio's blocking thread pool as well.
+impl TicketRepository for Arc<Mutex<SqliteTicketStore>> {
+    async fn insert(&self, draft: TicketDraft) -> Result<TicketId, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.add_ticket(draft)?)).await?
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let ticket = store.lock()?.get(id)?.ok_or(TicketStoreError::NotFound(id))?;
+            Ok(ticket)
+        })
+        .await?
+    }
+
+    async fn patch(&self, patch: TicketPatch, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.patch_by(patch, None, &actor)?)).await?
+    }
+
+    async fn patch_if_version(
+        &self,
+        patch: TicketPatch,
+        expected: u64,
+        actor: Actor,
+    ) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.patch_by(patch, Some(expected), &actor)?)).await?
+    }
+
+    async fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.history(id)?)).await?
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<TicketPage, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.list(&query)?)).await?
+    }
+
+    async fn search(&self, query: SearchQuery, limit: Option<usize>) -> Result<Vec<SearchHit>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.search(&query, limit)?)).await?
+    }
+
+    async fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+    ) -> Result<Vec<BatchOutcome>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.apply_batch(operations, &actor)?)).await?
+    }
+
+    async fn import(&self, params: ImportParams, body: Vec<u8>) -> Result<ImportReport, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.import(params.format, body.as_slice(), params.ids)?))
+            .await?
+    }
+
+    async fn export(&self, format: Format) -> Result<Vec<u8>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut body = Vec::new();
+            store.lock()?.export(format, &mut body)?;
+            Ok(body)
+        })
+        .await?
+    }
+
+    async fn add_comment(
+        &self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.add_comment(ticket_id, draft, &actor)?)).await?
+    }
+
+    async fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.comments(ticket_id)?)).await?
+    }
+
+    async fn edit_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.edit_comment(ticket_id, comment_id, body, &actor)?))
+            .await?
+    }
+
+    async fn delete_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.delete_comment(ticket_id, comment_id, &actor)?)).await?
+    }
+
+    async fn add_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.add_label(id, label, &actor)?)).await?
+    }
+
+    async fn remove_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.remove_label(id, &label, &actor)?)).await?
+    }
+
+    async fn add_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.add_link(link, &actor)?)).await?
+    }
+
+    async fn remove_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.remove_link(link, &actor)?)).await?
+    }
+
+    async fn links(&self, id: TicketId) -> Result<TicketLinks, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.links(id)?)).await?
+    }
+
+    async fn dependencies(&self, id: TicketId) -> Result<DependencyTree, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.dependencies(id)?)).await?
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.add_user(user)?)).await?
+    }
+
+    async fn get_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let user = store.lock()?.get_user(&id)?.ok_or(TicketStoreError::UserNotFound(id))?;
+            Ok(user)
+        })
+        .await?
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.users()?)).await?
+    }
+
+    async fn delete_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.remove_user(&id)?)).await?
+    }
+
+    async fn archive(&self, id: TicketId, actor: Actor) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.archive(id, &actor)?)).await?
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.remove(id)?)).await?
+    }
+
+    async fn events(&self) -> Result<EventBus, RepositoryError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || Ok(store.lock()?.events().clone())).await?
+    }
+}
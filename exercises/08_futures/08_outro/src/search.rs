@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::data::Ticket;
+use crate::store::TicketId;
+
+// Full-text search over the titles and descriptions of the tickets.
+//
+// Texts are split into terms: runs of letters and digits, lowercased. In a query, terms
+// separated by spaces must all match (`AND` can be spelled out), and `OR` separates
+// alternatives: `login error OR crash` finds the tickets mentioning both "login" and "error",
+// and those mentioning "crash". A term ending with `*` matches the terms it is a prefix of.
+//
+// The matching tickets are ranked by TF-IDF: a ticket scores higher when the terms of the
+// query make up more of its text, and when these terms are rare among the tickets.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchQuery {
+    // A ticket matches if it matches every term of one of the alternatives
+    alternatives: Vec<Vec<QueryTerm>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum QueryTerm {
+    Exact(String),
+    Prefix(String),
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ParseSearchError {
+    #[error("The search query is empty")]
+    Empty,
+    #[error("`{0}` must stand between two terms")]
+    DanglingOperator(String),
+    #[error("`{0}` is not a term: terms are made of letters and digits")]
+    InvalidTerm(String),
+}
+
+impl TryFrom<&str> for SearchQuery {
+    type Error = ParseSearchError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut alternatives = Vec::new();
+        let mut terms = Vec::new();
+        // The operator read last, until a term follows it
+        let mut operator = None;
+        for word in value.split_whitespace() {
+            match word {
+                "OR" | "AND" => {
+                    if terms.is_empty() || operator.is_some() {
+                        return Err(ParseSearchError::DanglingOperator(word.to_string()));
+                    }
+                    if word == "OR" {
+                        alternatives.push(std::mem::take(&mut terms));
+                    }
+                    operator = Some(word);
+                }
+                _ => {
+                    // `log-in*` stands for `log AND in*`
+                    let (text, prefix) = match word.strip_suffix('*') {
+                        Some(text) => (text, true),
+                        None => (word, false),
+                    };
+                    let mut tokens: Vec<String> = tokenize(text).collect();
+                    let last = tokens.pop().ok_or_else(|| ParseSearchError::InvalidTerm(word.to_string()))?;
+                    terms.extend(tokens.into_iter().map(QueryTerm::Exact));
+                    terms.push(if prefix { QueryTerm::Prefix(last) } else { QueryTerm::Exact(last) });
+                    operator = None;
+                }
+            }
+        }
+        if let Some(operator) = operator {
+            return Err(ParseSearchError::DanglingOperator(operator.to_string()));
+        }
+        if terms.is_empty() {
+            return Err(ParseSearchError::Empty);
+        }
+        alternatives.push(terms);
+        Ok(SearchQuery { alternatives })
+    }
+}
+
+impl TryFrom<String> for SearchQuery {
+    type Error = ParseSearchError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SearchQuery::try_from(value.as_str())
+    }
+}
+
+// Query parameters of GET /tickets/search
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    // Number of results, `DEFAULT_PAGE_SIZE` by default and at most `MAX_PAGE_SIZE`
+    pub limit: Option<usize>,
+}
+
+// A ticket matching a search, and its score: the higher, the more relevant
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub ticket: Ticket,
+    pub score: f64,
+}
+
+// Inverted index of the terms of the tickets. Derived from the tickets: the stores rebuild it
+// when they are loaded, and update it whenever a ticket is added, patched or removed.
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    // The tickets that contain each term
+    postings: BTreeMap<String, BTreeSet<TicketId>>,
+    // The number of occurrences of each term in each ticket
+    documents: BTreeMap<TicketId, BTreeMap<String, u32>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Index the title and the description of a ticket, replacing what was indexed for it before
+    pub fn insert(&mut self, id: TicketId, title: &str, description: &str) {
+        self.remove(id);
+        let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+        for term in tokenize(title).chain(tokenize(description)) {
+            *counts.entry(term).or_default() += 1;
+        }
+        for term in counts.keys() {
+            self.postings.entry(term.clone()).or_default().insert(id);
+        }
+        self.documents.insert(id, counts);
+    }
+
+    pub fn index(&mut self, ticket: &Ticket) {
+        self.insert(ticket.id, ticket.title.as_str(), ticket.description.as_str());
+    }
+
+    pub fn remove(&mut self, id: TicketId) {
+        let Some(counts) = self.documents.remove(&id) else {
+            return;
+        };
+        for term in counts.keys() {
+            if let Some(ids) = self.postings.get_mut(term) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    // The tickets matching the query and their score, the best first (by id for equal scores)
+    pub fn search(&self, query: &SearchQuery) -> Vec<(TicketId, f64)> {
+        let mut matching = BTreeSet::new();
+        let mut terms = BTreeSet::new();
+        for alternative in &query.alternatives {
+            let mut ids: Option<BTreeSet<TicketId>> = None;
+            for term in alternative {
+                let expanded = self.expand(term);
+                let containing: BTreeSet<TicketId> = expanded
+                    .iter()
+                    .flat_map(|term| &self.postings[*term])
+                    .copied()
+                    .collect();
+                ids = Some(match ids {
+                    Some(ids) => ids.intersection(&containing).copied().collect(),
+                    None => containing,
+                });
+                terms.extend(expanded);
+            }
+            matching.extend(ids.unwrap_or_default());
+        }
+
+        let total = self.documents.len() as f64;
+        let mut hits: Vec<(TicketId, f64)> = matching
+            .into_iter()
+            .map(|id| {
+                let counts = &self.documents[&id];
+                let length: u32 = counts.values().sum();
+                let score = terms
+                    .iter()
+                    .filter_map(|&term| {
+                        let count = *counts.get(term)?;
+                        let frequency = f64::from(count) / f64::from(length);
+                        let rarity = (1.0 + total / self.postings[term].len() as f64).ln();
+                        Some(frequency * rarity)
+                    })
+                    .sum();
+                (id, score)
+            })
+            .collect();
+        hits.sort_by(|(a_id, a_score), (b_id, b_score)| b_score.total_cmp(a_score).then(a_id.cmp(b_id)));
+        hits
+    }
+
+    // The indexed terms a term of the query stands for
+    fn expand(&self, term: &QueryTerm) -> Vec<&String> {
+        match term {
+            QueryTerm::Exact(term) => self.postings.get_key_value(term).map(|(term, _)| term).into_iter().collect(),
+            QueryTerm::Prefix(prefix) => self
+                .postings
+                .range::<String, _>(prefix..)
+                .map(|(term, _)| term)
+                .take_while(|term| term.starts_with(prefix.as_str()))
+                .collect(),
+        }
+    }
+}
+
+// The terms of a text, in order
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
@@ -1,48 +1,274 @@
-use axum;
-use tokio;
-use hyper;
-use std::sync::{Arc, RwLock};
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::DefaultBodyLimit;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use tower::ServiceBuilder;
 
-use crate::api::{add_ticket, get_ticket, patch_ticket};
-use crate::store::TicketStore;
+use crate::api::{
+    add_comment, add_label, add_link, add_ticket, add_user, apply_batch, archive_ticket, delete_comment,
+    delete_ticket, delete_user, edit_comment, export_tickets, get_dependencies, get_ticket, get_ticket_history,
+    get_user, import_tickets, list_comments, list_links, list_tickets, list_users, patch_ticket, patch_ticket_by_id,
+    list_dead_letters, list_webhooks, redeliver_webhook, register_webhook, remove_label, remove_link, search_tickets,
+    stream_events, stream_events_ws, unregister_webhook,
+};
+use crate::error::ApiError;
+use crate::repository::{RepositoryError, TicketRepository};
+use crate::retry::RetryPolicy;
+use crate::webhook::{Webhooks, DEFAULT_WEBHOOK_RETRY};
 
 // API should expose endpoints to:
 //  - Create a ticket
 //  - Retrieve ticket details
 //  - Patch a ticket
+//  - List tickets
+//  - Search tickets by their title and description
+//  - Delete or archive a ticket
+//  - Comment on a ticket
+//  - Manage the users tickets are assigned to
+//  - Follow the changes to the tickets as they happen
+//  - Notify other services of the changes, through webhooks
 
-pub async fn start_server(url: &str) -> tokio::task::JoinHandle<Result<(), hyper::Error>> {
-    // Initialize an empty TicketStore wrapped in Arc and RwLock for shared access
-    let store = Arc::new(RwLock::new(TicketStore::new()));
+pub const DEFAULT_BIND: ([u8; 4], u16) = ([127, 0, 0, 1], 3000);
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+
+// How the server is started, e.g.
+//   ServerConfig::new().bind(([127, 0, 0, 1], 0)).request_timeout(Duration::from_secs(5))
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerConfig {
+    bind: SocketAddr,
+    request_timeout: Duration,
+    body_limit: usize,
+    webhook_retry: RetryPolicy,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Self {
+            bind: DEFAULT_BIND.into(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            body_limit: DEFAULT_BODY_LIMIT,
+            webhook_retry: DEFAULT_WEBHOOK_RETRY,
+        }
+    }
+
+    // Address to listen on. With port 0, the OS picks a free port: see `ServerHandle::addr`
+    pub fn bind(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.bind = addr.into();
+        self
+    }
+
+    // Requests taking longer than this are answered with a 408
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    // Maximum size of a request body, in bytes. Larger bodies are answered with a 413
+    pub fn body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = bytes;
+        self
+    }
+
+    // How failed webhook deliveries are retried, before they become dead letters
+    pub fn webhook_retry(mut self, policy: RetryPolicy) -> Self {
+        self.webhook_retry = policy;
+        self
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.bind
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("Cannot bind the server: {0}")]
+    Bind(hyper::Error),
+    #[error("The server failed: {0}")]
+    Serve(#[from] hyper::Error),
+    #[error("The server task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    // The webhooks can't follow the changes to the tickets
+    #[error("Cannot follow the changes to the tickets: {0}")]
+    Events(#[from] RepositoryError),
+}
+
+// A running server
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<Result<(), hyper::Error>>,
+    // Delivers the webhooks
+    dispatcher: tokio::task::JoinHandle<()>,
+    webhooks: Webhooks,
+}
+
+impl ServerHandle {
+    // The address the server is actually listening on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // The webhooks of the server, as seen by the API
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    // Stop accepting new connections, wait for the in-flight requests to complete,
+    // then stop the server
+    pub async fn shutdown(self) -> Result<(), ServerError> {
+        // If the server task is already gone, there is nothing to signal
+        let _ = self.shutdown.send(());
+        self.task.await??;
+        self.dispatcher.await?;
+        Ok(())
+    }
+}
+
+// Resolves once the server is shutting down. The streams of events end then: they would
+// otherwise keep their connection open, and the graceful shutdown waiting for them.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    pub async fn wait(mut self) {
+        // If the server is gone, so is the reason to wait
+        let _ = self.0.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
+
+// The server runs on top of any `TicketRepository`, e.g. an in-memory store:
+//   start_server(ServerConfig::new(), Arc::new(RwLock::new(TicketStore::new())))
+pub async fn start_server<R: TicketRepository>(
+    config: ServerConfig,
+    repository: R,
+) -> Result<ServerHandle, ServerError> {
+    let (shutting_down, shutdown_signal) = watch::channel(false);
+    let (webhooks, dispatcher) = Webhooks::new(config.webhook_retry);
+    let events = repository.events().await?;
 
     // Define routes
-    let app = create_app()
+    let app = create_app::<R>()
         // Add middleware that inserts the state into all incoming request's
         // extensions. This allows the handlers to access the state.
-        .layer(axum::extract::Extension(store));
+        .layer(axum::extract::Extension(repository))
+        .layer(axum::extract::Extension(ShutdownSignal(shutdown_signal.clone())))
+        .layer(axum::extract::Extension(webhooks.clone()))
+        .layer(DefaultBodyLimit::max(config.body_limit))
+        .layer(
+            ServiceBuilder::new()
+                // The timeout layer fails with an error, that we turn into a response
+                .layer(HandleErrorLayer::new(|err: tower::BoxError| async move {
+                    if err.is::<tower::timeout::error::Elapsed>() {
+                        ApiError::Timeout
+                    } else {
+                        ApiError::Internal(err.to_string())
+                    }
+                }))
+                .timeout(config.request_timeout),
+        );
 
-    // Create a server listening on localhost:3000
-    let addr = url.parse().unwrap();
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
-   
-    // tokio will continue to run the spawned task, in the background, concurrently with the task that spawned it
-    let server_handle = tokio::task::spawn(server);
+    // Once bound, the socket queues incoming connections: no need to wait for the
+    // server task to be scheduled before sending requests
+    let server = axum::Server::try_bind(&config.bind).map_err(ServerError::Bind)?;
+    let server = server.serve(app.into_make_service());
+    let addr = server.local_addr();
 
-    // Wait for the server to start
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let (shutdown, shutdown_requested) = oneshot::channel::<()>();
+    let server = server.with_graceful_shutdown(async move {
+        // Resolves on `ServerHandle::shutdown`, or when the handle is dropped
+        let _ = shutdown_requested.await;
+        let _ = shutting_down.send(true);
+    });
+
+    // tokio will continue to run the spawned task, in the background, concurrently with the task that spawned it
+    let task = tokio::task::spawn(server);
+    let dispatcher = tokio::task::spawn(dispatcher.run(events, ShutdownSignal(shutdown_signal)));
 
     println!("Server running at http://{}", addr);
 
-    server_handle
+    Ok(ServerHandle {
+        addr,
+        shutdown,
+        task,
+        dispatcher,
+        webhooks,
+    })
 }
 
-fn create_app() -> axum::Router {
+fn create_app<R: TicketRepository>() -> axum::Router {
     // Build the application with routes
-    axum::Router::new()                     
-        // POST /tickets
-        .route("/tickets", axum::routing::post(add_ticket))
+    axum::Router::new()
+        // POST /tickets, GET /tickets
+        .route("/tickets", axum::routing::post(add_ticket::<R>).get(list_tickets::<R>))
         // POST /tickets/patch
-        .route("/tickets/patch", axum::routing::post(patch_ticket))  
-        // GET /tickets/:id
-        .route("/tickets/:id", axum::routing::get(get_ticket))                              
-}
\ No newline at end of file
+        .route("/tickets/patch", axum::routing::post(patch_ticket::<R>))
+        // GET /tickets/search?q=
+        .route("/tickets/search", axum::routing::get(search_tickets::<R>))
+        // POST /tickets/batch
+        .route("/tickets/batch", axum::routing::post(apply_batch::<R>))
+        // GET /tickets/events (server-sent events), GET /tickets/events/ws (WebSocket)
+        .route("/tickets/events", axum::routing::get(stream_events::<R>))
+        .route("/tickets/events/ws", axum::routing::get(stream_events_ws::<R>))
+        // POST /tickets/import, GET /tickets/export
+        .route("/tickets/import", axum::routing::post(import_tickets::<R>))
+        .route("/tickets/export", axum::routing::get(export_tickets::<R>))
+        // GET /tickets/:id, PATCH /tickets/:id, DELETE /tickets/:id
+        .route(
+            "/tickets/:id",
+            axum::routing::get(get_ticket::<R>)
+                .patch(patch_ticket_by_id::<R>)
+                .delete(delete_ticket::<R>),
+        )
+        // POST /tickets/:id/archive
+        .route("/tickets/:id/archive", axum::routing::post(archive_ticket::<R>))
+        // GET /tickets/:id/history
+        .route("/tickets/:id/history", axum::routing::get(get_ticket_history::<R>))
+        // POST /tickets/:id/comments, GET /tickets/:id/comments
+        .route(
+            "/tickets/:id/comments",
+            axum::routing::post(add_comment::<R>).get(list_comments::<R>),
+        )
+        // PATCH /tickets/:id/comments/:comment_id, DELETE /tickets/:id/comments/:comment_id
+        .route(
+            "/tickets/:id/comments/:comment_id",
+            axum::routing::patch(edit_comment::<R>).delete(delete_comment::<R>),
+        )
+        // PUT /tickets/:id/labels/:label, DELETE /tickets/:id/labels/:label
+        .route(
+            "/tickets/:id/labels/:label",
+            axum::routing::put(add_label::<R>).delete(remove_label::<R>),
+        )
+        // GET /tickets/:id/links
+        .route("/tickets/:id/links", axum::routing::get(list_links::<R>))
+        // PUT /tickets/:id/links/:kind/:target, DELETE /tickets/:id/links/:kind/:target
+        .route(
+            "/tickets/:id/links/:kind/:target",
+            axum::routing::put(add_link::<R>).delete(remove_link::<R>),
+        )
+        // GET /tickets/:id/dependencies
+        .route("/tickets/:id/dependencies", axum::routing::get(get_dependencies::<R>))
+        // POST /users, GET /users
+        .route("/users", axum::routing::post(add_user::<R>).get(list_users::<R>))
+        // GET /users/:id, DELETE /users/:id
+        .route("/users/:id", axum::routing::get(get_user::<R>).delete(delete_user::<R>))
+        // POST /webhooks, GET /webhooks
+        .route("/webhooks", axum::routing::post(register_webhook).get(list_webhooks))
+        // DELETE /webhooks/:id
+        .route("/webhooks/:id", axum::routing::delete(unregister_webhook))
+        // GET /webhooks/dead-letters
+        .route("/webhooks/dead-letters", axum::routing::get(list_dead_letters))
+        // POST /webhooks/dead-letters/:delivery/redeliver
+        .route(
+            "/webhooks/dead-letters/:delivery/redeliver",
+            axum::routing::post(redeliver_webhook),
+        )
+}
@@ -0,0 +1,1253 @@
+use chrono::NaiveDate;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql, TransactionBehavior};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::batch::{BatchError, BatchOperation, BatchOutcome, OperationFailure};
+use crate::bulk::{self, Format, IdPolicy, ImportReport, ImportTarget, TicketRecord, TicketWriter};
+use crate::clock::{Clock, SystemClock};
+use crate::comment::{self, CommentDraft, CommentId, TicketComment};
+use crate::filter::{Condition, Filter};
+use crate::data::{OrderBy, Priority, SortOrder, Status, Ticket, TicketDraft, TicketPage, TicketPatch, TicketQuery};
+use crate::events::{EventBus, TicketEvent};
+use crate::history::{diff, Actor, FieldChange, HistoryEntry};
+use crate::link::{self, DependencyTree, LinkKind, TicketLink, TicketLinks};
+use crate::search::{SearchHit, SearchIndex, SearchQuery};
+use crate::store::{TicketId, TicketStoreError, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::user::{User, UserId, UserName};
+use crate::workflow::Workflow;
+use ticket_fields::{CommentBody, TicketDescription, TicketLabel, TicketTitle};
+
+// A ticket store backed by an embedded SQLite database.
+//
+// The schema is versioned with `PRAGMA user_version`: when a database is opened, the
+// migrations it hasn't seen yet are applied in order, in a single transaction.
+// New migrations must be appended to `MIGRATIONS`, never edited.
+const MIGRATIONS: &[&str] = &[
+    // 1: tickets, with an index to query them by status
+    "CREATE TABLE tickets (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        description TEXT NOT NULL,
+        status TEXT NOT NULL,
+        archived INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX tickets_by_status ON tickets (status, id);
+    -- Next id to assign: ids of removed tickets are never reused
+    CREATE TABLE ticket_counter (next_id INTEGER NOT NULL);
+    INSERT INTO ticket_counter (next_id) VALUES (0);",
+    // 2: ticket versions, for optimistic concurrency control
+    "ALTER TABLE tickets ADD COLUMN version INTEGER NOT NULL DEFAULT 0;",
+    // 3: change history, kept when tickets are removed.
+    // `changes` is the JSON array of the `FieldChange`s of the entry.
+    "CREATE TABLE ticket_history (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        ticket_id INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        changes TEXT NOT NULL
+    );
+    CREATE INDEX ticket_history_by_ticket ON ticket_history (ticket_id, seq);",
+    // 4: comments, removed with their ticket. AUTOINCREMENT: ids of deleted comments are never reused
+    "CREATE TABLE ticket_comments (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ticket_id INTEGER NOT NULL,
+        parent_id INTEGER,
+        author TEXT NOT NULL,
+        body TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        edited_at INTEGER
+    );
+    CREATE INDEX ticket_comments_by_ticket ON ticket_comments (ticket_id, id);",
+    // 5: users, and the users tickets are assigned to and reported by.
+    // The index serves queries like "the tickets assigned to alice in InProgress".
+    "CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    ALTER TABLE tickets ADD COLUMN assignee TEXT;
+    ALTER TABLE tickets ADD COLUMN reporter TEXT;
+    CREATE INDEX tickets_by_assignee ON tickets (assignee, status, id);",
+    // 6: labels. The primary key serves the labels of a ticket, the index the tickets with a label.
+    "CREATE TABLE ticket_labels (
+        ticket_id INTEGER NOT NULL,
+        label TEXT NOT NULL,
+        PRIMARY KEY (ticket_id, label)
+    ) WITHOUT ROWID;
+    CREATE INDEX ticket_labels_by_label ON ticket_labels (label, ticket_id);",
+    // 7: priorities and due dates. Priorities are stored as "P0".."P3", so that they sort
+    // from the most to the least urgent; due dates as "YYYY-MM-DD", so that they sort by date.
+    // The index serves the overdue tickets.
+    "ALTER TABLE tickets ADD COLUMN priority TEXT NOT NULL DEFAULT 'P2';
+    ALTER TABLE tickets ADD COLUMN due_date TEXT;
+    CREATE INDEX tickets_by_due_date ON tickets (due_date, status);",
+    // 8: links between tickets. The primary key serves the links from a ticket, the index the links to it.
+    "CREATE TABLE ticket_links (
+        source INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        target INTEGER NOT NULL,
+        PRIMARY KEY (source, kind, target)
+    ) WITHOUT ROWID;
+    CREATE INDEX ticket_links_by_target ON ticket_links (target, kind, source);",
+];
+
+// Version of the schema once every migration has been applied
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+#[derive(Debug, thiserror::Error)]
+pub enum SqliteError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    // A row that doesn't hold a valid ticket, e.g. an empty title written by another tool
+    #[error("Ticket {id} is corrupted in the database: {reason}")]
+    CorruptRow { id: u64, reason: String },
+    #[error("User {id} is corrupted in the database: {reason}")]
+    CorruptUser { id: String, reason: String },
+    #[error(transparent)]
+    Store(#[from] TicketStoreError),
+    // Reading an import or writing an export
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+}
+
+pub struct SqliteTicketStore {
+    conn: Connection,
+    workflow: Workflow,
+    clock: Arc<dyn Clock>,
+    // Full-text index of the titles and descriptions, built when the database is opened.
+    // Only the changes made through this store are indexed afterwards.
+    search: SearchIndex,
+    // Where the changes are announced, once committed. Like the search index, it only knows
+    // about the changes made through this store.
+    events: EventBus,
+}
+
+impl SqliteTicketStore {
+    // Open (or create) the database at `path` and bring its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SqliteError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, SqliteError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self, SqliteError> {
+        migrate(&mut conn)?;
+        register_functions(&conn)?;
+        let search = build_search_index(&conn)?;
+        Ok(Self {
+            conn,
+            workflow: Workflow::standard(),
+            clock: Arc::new(SystemClock),
+            search,
+            events: EventBus::new(),
+        })
+    }
+
+    // Status changes are checked against `workflow` (the standard one by default)
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    // Where the timestamps of the history and "today" come from (the system clock by default)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // The bus the changes are announced on (see `TicketStore::events`)
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    // Version of the schema, i.e. number of migrations applied
+    pub fn schema_version(&self) -> Result<usize, SqliteError> {
+        let version: usize = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok(version)
+    }
+
+    // Reclaim the space left by removed tickets
+    pub fn vacuum(&self) -> Result<(), SqliteError> {
+        self.conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    pub fn add_ticket(&mut self, draft: TicketDraft) -> Result<TicketId, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let id = insert_ticket(&tx, &draft)?;
+        let ticket = select_ticket(&tx, id)?.ok_or(TicketStoreError::NotFound(id))?;
+        tx.commit()?;
+        self.search.insert(id, draft.title.as_str(), draft.description.as_str());
+        self.events.publish(TicketEvent::Created { ticket });
+        Ok(id)
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, SqliteError> {
+        select_ticket(&self.conn, id)
+    }
+
+    pub fn get_mut(&mut self, patch: TicketPatch) -> Result<(), SqliteError> {
+        self.patch_by(patch, None, &Actor::system())?;
+        Ok(())
+    }
+
+    // See `TicketStore::patch_if_version`
+    pub fn patch_if_version(&mut self, patch: TicketPatch, expected: u64) -> Result<Ticket, SqliteError> {
+        self.patch_by(patch, Some(expected), &Actor::system())
+    }
+
+    // See `TicketStore::patch_by`
+    pub fn patch_by(
+        &mut self,
+        patch: TicketPatch,
+        expected: Option<u64>,
+        actor: &Actor,
+    ) -> Result<Ticket, SqliteError> {
+        // Take the write lock right away: the ticket can't change between the read and the update,
+        // even if another process uses the same database
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let (before, after) = update_ticket(&tx, &self.workflow, patch, expected, actor, self.clock.now_millis())?;
+        tx.commit()?;
+        if after.title != before.title || after.description != before.description {
+            self.search.index(&after);
+        }
+        if after.version != before.version {
+            self.events.publish(TicketEvent::patched(&before, after.clone()));
+        }
+        Ok(after)
+    }
+
+    // See `TicketStore::history`
+    pub fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, SqliteError> {
+        let mut statement = self.conn.prepare(
+            "SELECT version, timestamp, actor, changes FROM ticket_history
+            WHERE ticket_id = ?1 ORDER BY seq",
+        )?;
+        let rows = statement.query_map(params![id.0], |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (version, timestamp, actor, changes) = row?;
+            let changes: Vec<FieldChange> =
+                serde_json::from_str(&changes).map_err(|err| SqliteError::CorruptRow {
+                    id: id.0,
+                    reason: format!("invalid history entry: {}", err),
+                })?;
+            entries.push(HistoryEntry {
+                ticket_id: id,
+                version,
+                timestamp,
+                actor: Actor::new(actor),
+                changes,
+            });
+        }
+        Ok(entries)
+    }
+
+    // See `TicketStore::add_comment`
+    pub fn add_comment(
+        &mut self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: &Actor,
+    ) -> Result<TicketComment, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version = select_version(&tx, ticket_id)?.ok_or(TicketStoreError::NotFound(ticket_id))?;
+        if let Some(parent_id) = draft.parent_id {
+            if select_comment(&tx, ticket_id, parent_id)?.is_none() {
+                return Err(TicketStoreError::UnknownParent { ticket_id, parent_id }.into());
+            }
+        }
+        let timestamp = self.clock.now_millis();
+        tx.execute(
+            "INSERT INTO ticket_comments (ticket_id, parent_id, author, body, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                ticket_id.0,
+                draft.parent_id.map(|id| id.0),
+                actor.as_str(),
+                draft.body.as_str(),
+                timestamp
+            ],
+        )?;
+        let comment_id = CommentId(tx.last_insert_rowid() as u64);
+        let comment = select_comment(&tx, ticket_id, comment_id)?
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        let change = comment::change(comment_id, None, Some(&comment.body));
+        insert_history(&tx, ticket_id, version, actor, timestamp, &[change])?;
+        tx.commit()?;
+        Ok(comment)
+    }
+
+    // See `TicketStore::comments`
+    pub fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, SqliteError> {
+        select_version(&self.conn, ticket_id)?.ok_or(TicketStoreError::NotFound(ticket_id))?;
+        let mut statement = self.conn.prepare(
+            "SELECT id, ticket_id, parent_id, author, body, created_at, edited_at FROM ticket_comments
+            WHERE ticket_id = ?1 ORDER BY id",
+        )?;
+        let rows = statement.query_map(params![ticket_id.0], RawComment::from_row)?;
+        let mut comments = Vec::new();
+        for row in rows {
+            comments.push(TicketComment::try_from(row?)?);
+        }
+        Ok(comments)
+    }
+
+    // See `TicketStore::edit_comment`
+    pub fn edit_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: &Actor,
+    ) -> Result<TicketComment, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = select_comment(&tx, ticket_id, comment_id)?
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        if before.body == body {
+            return Ok(before);
+        }
+        let timestamp = self.clock.now_millis();
+        let after = TicketComment {
+            body,
+            edited_at: Some(timestamp),
+            ..before.clone()
+        };
+        tx.execute(
+            "UPDATE ticket_comments SET body = ?1, edited_at = ?2 WHERE id = ?3",
+            params![after.body.as_str(), after.edited_at, comment_id.0],
+        )?;
+        let version = select_version(&tx, ticket_id)?.unwrap_or_default();
+        let change = comment::change(comment_id, Some(&before.body), Some(&after.body));
+        insert_history(&tx, ticket_id, version, actor, timestamp, &[change])?;
+        tx.commit()?;
+        Ok(after)
+    }
+
+    // See `TicketStore::delete_comment`
+    pub fn delete_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: &Actor,
+    ) -> Result<TicketComment, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let comment = select_comment(&tx, ticket_id, comment_id)?
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        tx.execute("DELETE FROM ticket_comments WHERE id = ?1", params![comment_id.0])?;
+        tx.execute(
+            "UPDATE ticket_comments SET parent_id = ?1 WHERE parent_id = ?2",
+            params![comment.parent_id.map(|id| id.0), comment_id.0],
+        )?;
+        let version = select_version(&tx, ticket_id)?.unwrap_or_default();
+        let change = comment::change(comment_id, Some(&comment.body), None);
+        insert_history(&tx, ticket_id, version, actor, self.clock.now_millis(), &[change])?;
+        tx.commit()?;
+        Ok(comment)
+    }
+
+    // See `TicketStore::add_label`
+    pub fn add_label(&mut self, id: TicketId, label: TicketLabel, actor: &Actor) -> Result<Ticket, SqliteError> {
+        self.relabel(id, actor, |labels| labels.insert(label.clone()))
+    }
+
+    // See `TicketStore::remove_label`
+    pub fn remove_label(&mut self, id: TicketId, label: &TicketLabel, actor: &Actor) -> Result<Ticket, SqliteError> {
+        self.relabel(id, actor, |labels| labels.remove(label))
+    }
+
+    fn relabel(
+        &mut self,
+        id: TicketId,
+        actor: &Actor,
+        update: impl FnOnce(&mut BTreeSet<TicketLabel>) -> bool,
+    ) -> Result<Ticket, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = select_ticket(&tx, id)?.ok_or(TicketStoreError::NotFound(id))?;
+        let mut after = before.clone();
+        if !update(&mut after.labels) {
+            return Ok(before);
+        }
+        after.version += 1;
+        tx.execute("DELETE FROM ticket_labels WHERE ticket_id = ?1", params![id.0])?;
+        for label in &after.labels {
+            tx.execute(
+                "INSERT INTO ticket_labels (ticket_id, label) VALUES (?1, ?2)",
+                params![id.0, label.as_str()],
+            )?;
+        }
+        tx.execute("UPDATE tickets SET version = ?1 WHERE id = ?2", params![after.version, id.0])?;
+        insert_history(&tx, id, after.version, actor, self.clock.now_millis(), &diff(&before, &after))?;
+        tx.commit()?;
+        self.events.publish(TicketEvent::patched(&before, after.clone()));
+        Ok(after)
+    }
+
+    // See `TicketStore::add_link`
+    pub fn add_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version = select_version(&tx, link.source)?.ok_or(TicketStoreError::NotFound(link.source))?;
+        select_version(&tx, link.target)?.ok_or(TicketStoreError::NotFound(link.target))?;
+        if link.source == link.target {
+            return Err(TicketStoreError::SelfLink(link.source).into());
+        }
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM ticket_links WHERE source = ?1 AND kind = ?2 AND target = ?3",
+                params![link.source.0, link.kind.as_str(), link.target.0],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            if link.kind == LinkKind::ParentOf {
+                let parent: Option<u64> = tx
+                    .query_row(
+                        "SELECT source FROM ticket_links WHERE target = ?1 AND kind = ?2",
+                        params![link.target.0, LinkKind::ParentOf.as_str()],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if let Some(parent) = parent {
+                    return Err(TicketStoreError::ParentAlreadySet {
+                        child: link.target,
+                        parent: TicketId(parent),
+                    }
+                    .into());
+                }
+            }
+            if link.kind.is_acyclic() && reaches(&tx, link.target, link.kind, link.source)? {
+                return Err(TicketStoreError::LinkCycle(link).into());
+            }
+            tx.execute(
+                "INSERT INTO ticket_links (source, kind, target) VALUES (?1, ?2, ?3)",
+                params![link.source.0, link.kind.as_str(), link.target.0],
+            )?;
+            insert_history(&tx, link.source, version, actor, self.clock.now_millis(), &[link::change(&link, true)])?;
+        }
+        let links = select_links(&tx, link.source)?;
+        tx.commit()?;
+        Ok(links)
+    }
+
+    // See `TicketStore::remove_link`
+    pub fn remove_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version = select_version(&tx, link.source)?.ok_or(TicketStoreError::NotFound(link.source))?;
+        let removed = tx.execute(
+            "DELETE FROM ticket_links WHERE source = ?1 AND kind = ?2 AND target = ?3",
+            params![link.source.0, link.kind.as_str(), link.target.0],
+        )?;
+        if removed > 0 {
+            insert_history(&tx, link.source, version, actor, self.clock.now_millis(), &[link::change(&link, false)])?;
+        }
+        let links = select_links(&tx, link.source)?;
+        tx.commit()?;
+        Ok(links)
+    }
+
+    // See `TicketStore::links`
+    pub fn links(&self, id: TicketId) -> Result<TicketLinks, SqliteError> {
+        select_version(&self.conn, id)?.ok_or(TicketStoreError::NotFound(id))?;
+        select_links(&self.conn, id)
+    }
+
+    // See `TicketStore::dependencies`
+    pub fn dependencies(&self, id: TicketId) -> Result<DependencyTree, SqliteError> {
+        DependencyTree::build(id, &mut |id| {
+            let ticket = select_ticket(&self.conn, id)?.ok_or(TicketStoreError::NotFound(id))?;
+            Ok((ticket.title, ticket.status, select_links(&self.conn, id)?.blocked_by))
+        })
+    }
+
+    // See `TicketStore::add_user`
+    pub fn add_user(&mut self, user: User) -> Result<User, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if select_user(&tx, &user.id)?.is_some() {
+            return Err(TicketStoreError::UserAlreadyExists(user.id).into());
+        }
+        tx.execute(
+            "INSERT INTO users (id, name) VALUES (?1, ?2)",
+            params![user.id.as_str(), user.name.as_str()],
+        )?;
+        tx.commit()?;
+        Ok(user)
+    }
+
+    pub fn get_user(&self, id: &UserId) -> Result<Option<User>, SqliteError> {
+        select_user(&self.conn, id)
+    }
+
+    pub fn users(&self) -> Result<Vec<User>, SqliteError> {
+        let mut statement = self.conn.prepare("SELECT id, name FROM users ORDER BY id")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut users = Vec::new();
+        for row in rows {
+            let (id, name) = row?;
+            users.push(user_from_row(id, name)?);
+        }
+        Ok(users)
+    }
+
+    // See `TicketStore::remove_user`
+    pub fn remove_user(&mut self, id: &UserId) -> Result<User, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let user = select_user(&tx, id)?.ok_or_else(|| TicketStoreError::UserNotFound(id.clone()))?;
+        let ticket_id: Option<u64> = tx
+            .query_row(
+                "SELECT id FROM tickets WHERE assignee = ?1 OR reporter = ?1 ORDER BY id LIMIT 1",
+                params![id.as_str()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(ticket_id) = ticket_id {
+            return Err(TicketStoreError::UserInUse {
+                user: id.clone(),
+                ticket_id: TicketId(ticket_id),
+            }
+            .into());
+        }
+        tx.execute("DELETE FROM users WHERE id = ?1", params![id.as_str()])?;
+        tx.commit()?;
+        Ok(user)
+    }
+
+    // See `TicketStore::archive`
+    pub fn archive(&mut self, id: TicketId, actor: &Actor) -> Result<Ticket, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = select_ticket(&tx, id)?.ok_or(TicketStoreError::NotFound(id))?;
+        if before.archived {
+            return Err(TicketStoreError::AlreadyArchived(id).into());
+        }
+        tx.execute(
+            "UPDATE tickets SET archived = 1, version = version + 1 WHERE id = ?1",
+            params![id.0],
+        )?;
+        let after = select_ticket(&tx, id)?.ok_or(TicketStoreError::NotFound(id))?;
+        insert_history(&tx, id, after.version, actor, self.clock.now_millis(), &diff(&before, &after))?;
+        tx.commit()?;
+        self.events.publish(TicketEvent::patched(&before, after.clone()));
+        Ok(after)
+    }
+
+    pub fn remove(&mut self, id: TicketId) -> Result<Ticket, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let ticket = delete_ticket(&tx, id)?;
+        tx.commit()?;
+        self.search.remove(id);
+        self.events.publish(TicketEvent::Deleted { ticket: ticket.clone() });
+        Ok(ticket)
+    }
+
+    // See `TicketStore::apply_batch`. The batch runs in a single transaction, and each operation
+    // in a savepoint: a failed operation is rolled back on its own, so that the next ones can be
+    // checked too, then the whole transaction is rolled back.
+    pub fn apply_batch(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        actor: &Actor,
+    ) -> Result<Vec<BatchOutcome>, SqliteError> {
+        let total = operations.len();
+        let timestamp = self.clock.now_millis();
+        let mut tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut outcomes = Vec::new();
+        let mut events = Vec::new();
+        let mut failures = Vec::new();
+        for (index, operation) in operations.into_iter().enumerate() {
+            let savepoint = tx.savepoint()?;
+            let outcome = match operation {
+                BatchOperation::Create { draft } => insert_ticket(&savepoint, &draft).and_then(|id| {
+                    let ticket = select_ticket(&savepoint, id)?.ok_or(TicketStoreError::NotFound(id))?;
+                    Ok((BatchOutcome::Created { id }, Some(TicketEvent::Created { ticket })))
+                }),
+                BatchOperation::Patch { patch, version } => {
+                    update_ticket(&savepoint, &self.workflow, patch, version, actor, timestamp).map(|(before, after)| {
+                        // A patch that changed nothing has no event
+                        let changed = after.version != before.version;
+                        let event = changed.then(|| TicketEvent::patched(&before, after.clone()));
+                        (BatchOutcome::Patched { ticket: after }, event)
+                    })
+                }
+                BatchOperation::Delete { id } => delete_ticket(&savepoint, id).map(|ticket| {
+                    let event = TicketEvent::Deleted { ticket: ticket.clone() };
+                    (BatchOutcome::Deleted { ticket }, Some(event))
+                }),
+            };
+            match outcome {
+                Ok((outcome, event)) => {
+                    savepoint.commit()?;
+                    outcomes.push(outcome);
+                    events.extend(event);
+                }
+                // Dropping the savepoint rolls the operation back
+                Err(SqliteError::Store(error)) => failures.push(OperationFailure { index, error }),
+                Err(err) => return Err(err),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(BatchError { total, failures }.into());
+        }
+        tx.commit()?;
+
+        for event in events {
+            match &event {
+                TicketEvent::Created { ticket } | TicketEvent::Patched { ticket, .. } => self.search.index(ticket),
+                TicketEvent::Deleted { ticket } => self.search.remove(ticket.id),
+            }
+            self.events.publish(event);
+        }
+        Ok(outcomes)
+    }
+
+    pub fn list(&self, query: &TicketQuery) -> Result<TicketPage, SqliteError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        // Unset filters are NULL parameters, which match every row
+        let (comparison, order) = match query.sort {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        // When listing by priority, the cursor is compared with the position of the ticket it
+        // points to, which has to still be there
+        if let (OrderBy::Priority, Some(cursor)) = (query.order_by, query.cursor) {
+            if self.get(cursor)?.is_none() {
+                return Err(TicketStoreError::StaleCursor(cursor).into());
+            }
+        }
+        let (cursor_clause, order_clause) = match query.order_by {
+            OrderBy::Id => (format!("(?1 IS NULL OR id {comparison} ?1)"), format!("id {order}")),
+            OrderBy::Priority => (
+                format!(
+                    "(?1 IS NULL OR ({URGENCY}) {comparison} (SELECT {URGENCY} FROM tickets WHERE id = ?1))"
+                ),
+                format!("priority {order}, due_date IS NULL {order}, due_date {order}, id {order}"),
+            ),
+        };
+        // The parameters of the filter come after the 10 above
+        let mut filter_values = Vec::new();
+        let filter_clause = match &query.q {
+            Some(filter) => filter_sql(filter, 10, &mut filter_values),
+            None => "1".to_string(),
+        };
+        let sql = format!(
+            "SELECT {TICKET_COLUMNS} FROM tickets
+            WHERE {cursor_clause}
+                AND (?2 IS NULL OR status = ?2)
+                AND (?3 IS NULL OR instr(fold_case(title), ?3) > 0)
+                AND (?4 OR archived = 0)
+                AND (?6 IS NULL OR assignee = ?6)
+                AND (?7 IS NULL OR reporter = ?7)
+                AND (?8 IS NULL OR id IN (SELECT ticket_id FROM ticket_labels WHERE label = ?8))
+                AND (NOT ?9 OR (due_date < ?10 AND status NOT IN ('Done', 'Cancelled')))
+                AND {filter_clause}
+            ORDER BY {order_clause}
+            LIMIT ?5"
+        );
+
+        let mut statement = self.conn.prepare(&sql)?;
+        // Fetch one more ticket than needed, to know whether there is a next page
+        let base = params![
+                query.cursor.map(|cursor| cursor.0),
+                query.status.map(|status| status.as_str()),
+                query.title.as_ref().map(|title| title.to_lowercase()),
+                query.include_archived,
+                limit as u64 + 1,
+                query.assignee.as_ref().map(UserId::as_str),
+                query.reporter.as_ref().map(UserId::as_str),
+                query.label.as_ref().map(TicketLabel::as_str),
+                query.overdue,
+                self.clock.today().to_string()
+            ];
+        let all: Vec<&dyn ToSql> = base
+            .iter()
+            .copied()
+            .chain(filter_values.iter().map(|value| value as &dyn ToSql))
+            .collect();
+        let rows = statement.query_map(all.as_slice(), RawTicket::from_row)?;
+
+        let mut tickets = Vec::new();
+        for row in rows {
+            tickets.push(Ticket::try_from(row?)?);
+        }
+        let next_cursor = if tickets.len() > limit {
+            tickets.truncate(limit);
+            tickets.last().map(|ticket| ticket.id)
+        } else {
+            None
+        };
+
+        Ok(TicketPage { tickets, next_cursor })
+    }
+
+    // See `TicketStore::search`
+    pub fn search(&self, query: &SearchQuery, limit: Option<usize>) -> Result<Vec<SearchHit>, SqliteError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let mut hits = Vec::new();
+        for (id, score) in self.search.search(query) {
+            if hits.len() == limit {
+                break;
+            }
+            if let Some(ticket) = select_ticket(&self.conn, id)?.filter(|ticket| !ticket.archived) {
+                hits.push(SearchHit { ticket, score });
+            }
+        }
+        Ok(hits)
+    }
+
+    // See `TicketStore::import`. Each imported user, ticket (with its comments) and link
+    // is committed on its own.
+    pub fn import(&mut self, format: Format, reader: impl BufRead, ids: IdPolicy) -> Result<ImportReport, SqliteError> {
+        bulk::import_into(self, format, reader, ids)
+    }
+
+    // See `TicketStore::export`
+    pub fn export(&self, format: Format, writer: impl Write) -> Result<usize, SqliteError> {
+        let mut writer = TicketWriter::new(format, writer)?;
+        for user in self.users()? {
+            writer.write_user(&user)?;
+        }
+        let mut statement = self.conn.prepare(&format!("SELECT {TICKET_COLUMNS} FROM tickets ORDER BY id"))?;
+        let rows = statement.query_map([], RawTicket::from_row)?;
+        for row in rows {
+            let ticket = Ticket::try_from(row?)?;
+            let comments = self.comments(ticket.id)?;
+            let links = self.links(ticket.id)?.outgoing(ticket.id);
+            writer.write(&ticket, &comments, &links)?;
+        }
+        Ok(writer.finish()?)
+    }
+
+    fn insert_record(&mut self, mut record: TicketRecord) -> Result<TicketId, SqliteError> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let next_id: u64 = tx.query_row("SELECT next_id FROM ticket_counter", [], |row| row.get(0))?;
+        let id = record.id.unwrap_or(TicketId(next_id));
+        if select_version(&tx, id)?.is_some() {
+            return Err(TicketStoreError::AlreadyExists(id).into());
+        }
+        check_users(&tx, [record.assignee.as_ref(), record.reporter.as_ref()])?;
+        let comments = std::mem::take(&mut record.comments);
+        let ticket = record.into_ticket(id);
+        tx.execute(
+            "INSERT INTO tickets (id, title, description, status, archived, version, assignee, reporter, priority, due_date)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                id.0,
+                ticket.title.as_str(),
+                ticket.description.as_str(),
+                ticket.status.as_str(),
+                ticket.archived,
+                ticket.version,
+                ticket.assignee.as_ref().map(UserId::as_str),
+                ticket.reporter.as_ref().map(UserId::as_str),
+                ticket.priority.as_str(),
+                ticket.due_date.map(|date| date.to_string())
+            ],
+        )?;
+        for label in &ticket.labels {
+            tx.execute(
+                "INSERT INTO ticket_labels (ticket_id, label) VALUES (?1, ?2)",
+                params![id.0, label.as_str()],
+            )?;
+        }
+        tx.execute(
+            "UPDATE ticket_counter SET next_id = max(next_id, ?1)",
+            params![id.0.saturating_add(1)],
+        )?;
+        // The comments are numbered after the last id handed out, even if that comment is gone
+        let last_comment: u64 = tx.query_row(
+            "SELECT COALESCE((SELECT seq FROM sqlite_sequence WHERE name = 'ticket_comments'), 0)",
+            [],
+            |row| row.get(0),
+        )?;
+        for comment in bulk::number_comments(comments, id, last_comment + 1, self.clock.now_millis()) {
+            tx.execute(
+                "INSERT INTO ticket_comments (id, ticket_id, parent_id, author, body, created_at, edited_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    comment.id.0,
+                    id.0,
+                    comment.parent_id.map(|id| id.0),
+                    comment.author.as_str(),
+                    comment.body.as_str(),
+                    comment.created_at,
+                    comment.edited_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        self.search.index(&ticket);
+        self.events.publish(TicketEvent::Created { ticket });
+        Ok(id)
+    }
+}
+
+// The store errors are the reasons to reject an item; the others stop the import
+fn rejected<T>(result: Result<T, SqliteError>) -> Result<Result<T, TicketStoreError>, SqliteError> {
+    match result {
+        Ok(value) => Ok(Ok(value)),
+        Err(SqliteError::Store(err)) => Ok(Err(err)),
+        Err(err) => Err(err),
+    }
+}
+
+impl ImportTarget for SqliteTicketStore {
+    type Error = SqliteError;
+
+    fn insert_user(&mut self, user: User) -> Result<Result<(), TicketStoreError>, SqliteError> {
+        rejected(self.add_user(user).map(|_| ()))
+    }
+
+    fn insert_record(&mut self, record: TicketRecord) -> Result<Result<TicketId, TicketStoreError>, SqliteError> {
+        rejected(SqliteTicketStore::insert_record(self, record))
+    }
+
+    fn insert_link(&mut self, link: TicketLink) -> Result<Result<(), TicketStoreError>, SqliteError> {
+        rejected(self.add_link(link, &Actor::system()).map(|_| ()))
+    }
+}
+
+// The SQL condition of a filter. Its values are pushed to `values`, and numbered after
+// the `offset` parameters that come before them in the statement.
+// `IS` rather than `=` for the users: NOT assignee:alice must match the unassigned tickets.
+fn filter_sql(filter: &Filter, offset: usize, values: &mut Vec<Value>) -> String {
+    let condition = match filter {
+        Filter::Not(filter) => return format!("(NOT {})", filter_sql(filter, offset, values)),
+        Filter::And(left, right) => {
+            let left = filter_sql(left, offset, values);
+            return format!("({} AND {})", left, filter_sql(right, offset, values));
+        }
+        Filter::Or(left, right) => {
+            let left = filter_sql(left, offset, values);
+            return format!("({} OR {})", left, filter_sql(right, offset, values));
+        }
+        Filter::Condition(condition) => condition,
+    };
+    let (sql, value): (&str, Value) = match condition {
+        Condition::Status(status) => ("status = ?", status.as_str().to_string().into()),
+        Condition::Label(label) => (
+            "id IN (SELECT ticket_id FROM ticket_labels WHERE label = ?)",
+            label.as_str().to_string().into(),
+        ),
+        Condition::Assignee(user) => ("assignee IS ?", user.as_str().to_string().into()),
+        Condition::Reporter(user) => ("reporter IS ?", user.as_str().to_string().into()),
+        Condition::Priority(priority) => ("priority = ?", priority.as_str().to_string().into()),
+        Condition::Archived(archived) => ("archived = ?", i64::from(*archived).into()),
+        Condition::TitleContains(text) => ("instr(fold_case(title), ?) > 0", text.to_lowercase().into()),
+        Condition::DescriptionContains(text) => ("instr(fold_case(description), ?) > 0", text.to_lowercase().into()),
+    };
+    values.push(value);
+    format!("({})", sql.replace('?', &format!("?{}", offset + values.len())))
+}
+
+// Index the title and the description of every ticket, archived ones included
+fn build_search_index(conn: &Connection) -> Result<SearchIndex, SqliteError> {
+    let mut index = SearchIndex::new();
+    let mut statement = conn.prepare("SELECT id, title, description FROM tickets")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let title: String = row.get(1)?;
+        let description: String = row.get(2)?;
+        index.insert(TicketId(row.get(0)?), &title, &description);
+    }
+    Ok(index)
+}
+
+// The columns read by `RawTicket::from_row`. The labels of a ticket are joined with commas,
+// which they can't contain.
+const TICKET_COLUMNS: &str = "id, title, description, status, archived, version, assignee, reporter,
+    (SELECT group_concat(label, ',') FROM ticket_labels WHERE ticket_id = tickets.id), priority, due_date";
+
+// The position of a ticket when listing by priority, as a row value: the tickets without
+// a due date come after those with one
+const URGENCY: &str = "priority, due_date IS NULL, coalesce(due_date, ''), id";
+
+// See `TicketStore::add_ticket`
+fn insert_ticket(conn: &Connection, draft: &TicketDraft) -> Result<TicketId, SqliteError> {
+    check_users(conn, [draft.assignee.as_ref(), draft.reporter.as_ref()])?;
+    let id: u64 = conn.query_row("SELECT next_id FROM ticket_counter", [], |row| row.get(0))?;
+    conn.execute(
+        "INSERT INTO tickets (id, title, description, status, version, assignee, reporter, priority, due_date)
+        VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?7, ?8)",
+        params![
+            id,
+            draft.title.as_str(),
+            draft.description.as_str(),
+            Status::ToDo.as_str(),
+            draft.assignee.as_ref().map(UserId::as_str),
+            draft.reporter.as_ref().map(UserId::as_str),
+            draft.priority.as_str(),
+            draft.due_date.map(|date| date.to_string())
+        ],
+    )?;
+    conn.execute("UPDATE ticket_counter SET next_id = ?1", params![id + 1])?;
+    Ok(TicketId(id))
+}
+
+// See `TicketStore::patch_by`. Returns the ticket before and after the patch.
+fn update_ticket(
+    conn: &Connection,
+    workflow: &Workflow,
+    patch: TicketPatch,
+    expected: Option<u64>,
+    actor: &Actor,
+    timestamp: u64,
+) -> Result<(Ticket, Ticket), SqliteError> {
+    let id = patch.id;
+    let before = select_ticket(conn, id)?.ok_or(TicketStoreError::NotFound(id))?;
+    if let Some(expected) = expected {
+        if before.version != expected {
+            return Err(TicketStoreError::VersionMismatch {
+                id,
+                expected,
+                actual: before.version,
+            }
+            .into());
+        }
+    }
+    if let Some(status) = patch.status {
+        workflow
+            .check(before.status, status)
+            .map_err(|source| TicketStoreError::Workflow { id, source })?;
+    }
+    let assignee = patch.assignee.as_ref().and_then(Option::as_ref);
+    let reporter = patch.reporter.as_ref().and_then(Option::as_ref);
+    check_users(conn, [assignee, reporter])?;
+    if patch.status == Some(Status::Done) && before.status != Status::Done {
+        if let Some(blocker) = select_open_blocker(conn, id)? {
+            return Err(TicketStoreError::OpenBlocker { id, blocker }.into());
+        }
+    }
+
+    // Fields that are `None` in the patch keep their current value
+    let after = Ticket {
+        title: patch.title.unwrap_or_else(|| before.title.clone()),
+        description: patch.description.unwrap_or_else(|| before.description.clone()),
+        status: patch.status.unwrap_or(before.status),
+        assignee: patch.assignee.unwrap_or_else(|| before.assignee.clone()),
+        reporter: patch.reporter.unwrap_or_else(|| before.reporter.clone()),
+        priority: patch.priority.unwrap_or(before.priority),
+        due_date: patch.due_date.unwrap_or(before.due_date),
+        ..before.clone()
+    };
+    // A patch that changes nothing leaves the ticket alone, version included
+    let changes = diff(&before, &after);
+    if changes.is_empty() {
+        return Ok((before, after));
+    }
+    let after = Ticket {
+        version: before.version + 1,
+        ..after
+    };
+    conn.execute(
+        "UPDATE tickets SET title = ?1, description = ?2, status = ?3, version = ?4, assignee = ?5, reporter = ?6,
+            priority = ?7, due_date = ?8
+        WHERE id = ?9",
+        params![
+            after.title.as_str(),
+            after.description.as_str(),
+            after.status.as_str(),
+            after.version,
+            after.assignee.as_ref().map(UserId::as_str),
+            after.reporter.as_ref().map(UserId::as_str),
+            after.priority.as_str(),
+            after.due_date.map(|date| date.to_string()),
+            id.0
+        ],
+    )?;
+    insert_history(conn, id, after.version, actor, timestamp, &changes)?;
+    Ok((before, after))
+}
+
+// See `TicketStore::remove`
+fn delete_ticket(conn: &Connection, id: TicketId) -> Result<Ticket, SqliteError> {
+    let ticket = select_ticket(conn, id)?.ok_or(TicketStoreError::NotFound(id))?;
+    conn.execute("DELETE FROM tickets WHERE id = ?1", params![id.0])?;
+    conn.execute("DELETE FROM ticket_comments WHERE ticket_id = ?1", params![id.0])?;
+    conn.execute("DELETE FROM ticket_labels WHERE ticket_id = ?1", params![id.0])?;
+    conn.execute("DELETE FROM ticket_links WHERE source = ?1 OR target = ?1", params![id.0])?;
+    Ok(ticket)
+}
+
+fn select_ticket(conn: &Connection, id: TicketId) -> Result<Option<Ticket>, SqliteError> {
+    let row = conn
+        .query_row(
+            &format!("SELECT {TICKET_COLUMNS} FROM tickets WHERE id = ?1"),
+            params![id.0],
+            RawTicket::from_row,
+        )
+        .optional()?;
+    row.map(Ticket::try_from).transpose()
+}
+
+fn select_user(conn: &Connection, id: &UserId) -> Result<Option<User>, SqliteError> {
+    let row = conn
+        .query_row(
+            "SELECT id, name FROM users WHERE id = ?1",
+            params![id.as_str()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+    row.map(|(id, name)| user_from_row(id, name)).transpose()
+}
+
+fn user_from_row(id: String, name: String) -> Result<User, SqliteError> {
+    let corrupt = |reason: String| SqliteError::CorruptUser { id: id.clone(), reason };
+    let user_id = UserId::try_from(id.as_str()).map_err(|err| corrupt(err.to_string()))?;
+    let name = UserName::try_from(name).map_err(|err| corrupt(err.to_string()))?;
+    Ok(User { id: user_id, name })
+}
+
+// See `TicketStore::add_ticket`
+fn check_users(conn: &Connection, ids: [Option<&UserId>; 2]) -> Result<(), SqliteError> {
+    for id in ids.into_iter().flatten() {
+        if select_user(conn, id)?.is_none() {
+            return Err(TicketStoreError::UnknownUser(id.clone()).into());
+        }
+    }
+    Ok(())
+}
+
+fn select_version(conn: &Connection, id: TicketId) -> Result<Option<u64>, SqliteError> {
+    let version = conn
+        .query_row("SELECT version FROM tickets WHERE id = ?1", params![id.0], |row| row.get(0))
+        .optional()?;
+    Ok(version)
+}
+
+fn select_comment(
+    conn: &Connection,
+    ticket_id: TicketId,
+    comment_id: CommentId,
+) -> Result<Option<TicketComment>, SqliteError> {
+    let row = conn
+        .query_row(
+            "SELECT id, ticket_id, parent_id, author, body, created_at, edited_at FROM ticket_comments
+            WHERE id = ?1 AND ticket_id = ?2",
+            params![comment_id.0, ticket_id.0],
+            RawComment::from_row,
+        )
+        .optional()?;
+    row.map(TicketComment::try_from).transpose()
+}
+
+fn select_links(conn: &Connection, id: TicketId) -> Result<TicketLinks, SqliteError> {
+    let mut statement =
+        conn.prepare("SELECT source, kind, target FROM ticket_links WHERE source = ?1 OR target = ?1")?;
+    let rows = statement.query_map(params![id.0], |row| {
+        Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?, row.get::<_, u64>(2)?))
+    })?;
+    let mut links = Vec::new();
+    for row in rows {
+        let (source, kind, target) = row?;
+        let kind = LinkKind::try_from(kind).map_err(|err| SqliteError::CorruptRow {
+            id: id.0,
+            reason: format!("invalid link: {}", err),
+        })?;
+        links.push(TicketLink {
+            source: TicketId(source),
+            kind,
+            target: TicketId(target),
+        });
+    }
+    Ok(TicketLinks::of(id, links))
+}
+
+// Whether `to` can be reached from `from` by following links of the given kind.
+// UNION (rather than UNION ALL) drops the tickets already reached, so this ends even on a cycle.
+fn reaches(conn: &Connection, from: TicketId, kind: LinkKind, to: TicketId) -> Result<bool, SqliteError> {
+    let reached = conn.query_row(
+        "WITH RECURSIVE reachable (id) AS (
+            SELECT ?1
+            UNION
+            SELECT target FROM ticket_links JOIN reachable ON source = reachable.id WHERE kind = ?2
+        )
+        SELECT EXISTS (SELECT 1 FROM reachable WHERE id = ?3)",
+        params![from.0, kind.as_str(), to.0],
+        |row| row.get(0),
+    )?;
+    Ok(reached)
+}
+
+// See `TicketStore::patch_by`: a ticket can't be done while a ticket that blocks it is open
+fn select_open_blocker(conn: &Connection, id: TicketId) -> Result<Option<TicketId>, SqliteError> {
+    let blocker: Option<u64> = conn
+        .query_row(
+            "SELECT source FROM ticket_links JOIN tickets ON tickets.id = source
+            WHERE target = ?1 AND kind = ?2 AND status NOT IN ('Done', 'Cancelled')
+            ORDER BY source LIMIT 1",
+            params![id.0, LinkKind::Blocks.as_str()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(blocker.map(TicketId))
+}
+
+// `changes` is stored as JSON, see migration 3
+fn insert_history(
+    conn: &Connection,
+    id: TicketId,
+    version: u64,
+    actor: &Actor,
+    timestamp: u64,
+    changes: &[FieldChange],
+) -> Result<(), SqliteError> {
+    let changes = serde_json::to_string(changes).expect("field changes always serialize");
+    conn.execute(
+        "INSERT INTO ticket_history (ticket_id, version, timestamp, actor, changes)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id.0, version, timestamp, actor.as_str(), changes],
+    )?;
+    Ok(())
+}
+
+// SQLite's `lower` only folds ASCII letters: `fold_case` lowercases text the way Rust does,
+// so that the case-insensitive filters match the same tickets as on the in-memory store
+fn register_functions(conn: &Connection) -> Result<(), SqliteError> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("fold_case", 1, flags, |context| {
+        let text: String = context.get(0)?;
+        Ok(text.to_lowercase())
+    })?;
+    Ok(())
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), SqliteError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for migration in MIGRATIONS.iter().skip(version) {
+        tx.execute_batch(migration)?;
+    }
+    // PRAGMA doesn't support parameters
+    tx.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION.max(version)))?;
+    tx.commit()?;
+    Ok(())
+}
+
+// A row of the `tickets` table, before validation
+struct RawTicket {
+    id: u64,
+    title: String,
+    description: String,
+    status: String,
+    archived: bool,
+    version: u64,
+    assignee: Option<String>,
+    reporter: Option<String>,
+    labels: Option<String>,
+    priority: String,
+    due_date: Option<String>,
+}
+
+impl RawTicket {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            description: row.get(2)?,
+            status: row.get(3)?,
+            archived: row.get(4)?,
+            version: row.get(5)?,
+            assignee: row.get(6)?,
+            reporter: row.get(7)?,
+            labels: row.get(8)?,
+            priority: row.get(9)?,
+            due_date: row.get(10)?,
+        })
+    }
+}
+
+// Every field goes through the same validation as the API input,
+// so that a corrupted row is reported instead of being served as a ticket
+impl TryFrom<RawTicket> for Ticket {
+    type Error = SqliteError;
+
+    fn try_from(raw: RawTicket) -> Result<Self, Self::Error> {
+        let corrupt = |reason: String| SqliteError::CorruptRow { id: raw.id, reason };
+        Ok(Ticket {
+            id: TicketId(raw.id),
+            title: TicketTitle::try_from(raw.title).map_err(|err| corrupt(err.to_string()))?,
+            description: TicketDescription::try_from(raw.description)
+                .map_err(|err| corrupt(err.to_string()))?,
+            status: Status::try_from(raw.status.as_str()).map_err(|err| corrupt(err.to_string()))?,
+            archived: raw.archived,
+            version: raw.version,
+            assignee: raw.assignee.map(UserId::try_from).transpose().map_err(|err| corrupt(err.to_string()))?,
+            reporter: raw.reporter.map(UserId::try_from).transpose().map_err(|err| corrupt(err.to_string()))?,
+            labels: raw
+                .labels
+                .iter()
+                .flat_map(|labels| labels.split(','))
+                .map(TicketLabel::try_from)
+                .collect::<Result<_, _>>()
+                .map_err(|err| corrupt(err.to_string()))?,
+            priority: Priority::try_from(raw.priority.as_str()).map_err(|err| corrupt(err.to_string()))?,
+            due_date: raw
+                .due_date
+                .map(|date| date.parse::<NaiveDate>())
+                .transpose()
+                .map_err(|err| corrupt(format!("invalid due date: {}", err)))?,
+        })
+    }
+}
+
+// A row of the `ticket_comments` table, before validation
+struct RawComment {
+    id: u64,
+    ticket_id: u64,
+    parent_id: Option<u64>,
+    author: String,
+    body: String,
+    created_at: u64,
+    edited_at: Option<u64>,
+}
+
+impl RawComment {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            ticket_id: row.get(1)?,
+            parent_id: row.get(2)?,
+            author: row.get(3)?,
+            body: row.get(4)?,
+            created_at: row.get(5)?,
+            edited_at: row.get(6)?,
+        })
+    }
+}
+
+impl TryFrom<RawComment> for TicketComment {
+    type Error = SqliteError;
+
+    fn try_from(raw: RawComment) -> Result<Self, Self::Error> {
+        let body = CommentBody::try_from(raw.body).map_err(|err| SqliteError::CorruptRow {
+            id: raw.ticket_id,
+            reason: format!("comment {}: {}", raw.id, err),
+        })?;
+        Ok(TicketComment {
+            id: CommentId(raw.id),
+            ticket_id: TicketId(raw.ticket_id),
+            parent_id: raw.parent_id.map(CommentId),
+            author: Actor::new(raw.author),
+            body,
+            created_at: raw.created_at,
+            edited_at: raw.edited_at,
+        })
+    }
+}
@@ -1,16 +1,135 @@
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+use std::ops::{Bound, Deref};
 use std::sync::{Arc,RwLock};
 
-use crate::data::{Status,Ticket,TicketDraft,TicketPatch};
+use chrono::NaiveDate;
+
+use crate::batch::{BatchError, BatchOperation, BatchOutcome, OperationFailure};
+use crate::bulk::{self, Format, IdPolicy, ImportReport, ImportTarget, TicketRecord, TicketWriter};
+use crate::clock::{Clock, SystemClock};
+use crate::comment::{self, CommentDraft, CommentId, TicketComment};
+use crate::data::{OrderBy,Priority,SortOrder,Status,Ticket,TicketDraft,TicketPage,TicketPatch,TicketQuery};
+use crate::events::{EventBus, TicketEvent};
+use crate::filter::Filter;
+use crate::history::{diff, Actor, FieldChange, HistoryEntry};
+use crate::link::{self, DependencyTree, LinkKind, TicketLink, TicketLinks};
+use crate::search::{SearchHit, SearchIndex, SearchQuery};
+use crate::user::{User, UserId};
+use ticket_fields::{CommentBody, TicketLabel};
+use crate::workflow::{Workflow, WorkflowError};
 
 #[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Serialize,Deserialize)]
 pub struct TicketId(pub u64);
 
+impl std::fmt::Display for TicketId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Page size used when the query does not specify a limit, and the largest page we return
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TicketStoreError {
+    #[error("Ticket {0} not found")]
+    NotFound(TicketId),
+    // An imported ticket whose id is taken
+    #[error("Ticket {0} already exists")]
+    AlreadyExists(TicketId),
+    #[error("Ticket {0} is already archived")]
+    AlreadyArchived(TicketId),
+    #[error("Ticket {id} is at version {actual}, not {expected}")]
+    VersionMismatch { id: TicketId, expected: u64, actual: u64 },
+    // The status change is not allowed by the workflow of the store
+    #[error("Ticket {id}: {source}")]
+    Workflow { id: TicketId, source: WorkflowError },
+    #[error("Comment {comment_id} not found on ticket {ticket_id}")]
+    CommentNotFound { ticket_id: TicketId, comment_id: CommentId },
+    // A reply to a comment that doesn't belong to the ticket
+    #[error("Comment {parent_id} is not a comment of ticket {ticket_id}")]
+    UnknownParent { ticket_id: TicketId, parent_id: CommentId },
+    #[error("User {0} not found")]
+    UserNotFound(UserId),
+    // A ticket assigned to, or reported by, a user that isn't registered
+    #[error("User {0} is not registered")]
+    UnknownUser(UserId),
+    #[error("User {0} already exists")]
+    UserAlreadyExists(UserId),
+    // Users can't be removed while tickets refer to them
+    #[error("User {user} is the assignee or the reporter of ticket {ticket_id}")]
+    UserInUse { user: UserId, ticket_id: TicketId },
+    #[error("Ticket {0} can't be linked to itself")]
+    SelfLink(TicketId),
+    // Following the `parent_of` (or `blocks`) links would lead back to the source of the link
+    #[error("Linking tickets {} and {} would create a cycle of `{}` links", .0.source, .0.target, .0.kind)]
+    LinkCycle(TicketLink),
+    #[error("Ticket {child} already has a parent: ticket {parent}")]
+    ParentAlreadySet { child: TicketId, parent: TicketId },
+    // A ticket can't be done before the tickets that block it
+    #[error("Ticket {id} is blocked by ticket {blocker}, which is still open")]
+    OpenBlocker { id: TicketId, blocker: TicketId },
+    // Listing by priority resumes from the position of the cursor's ticket, which is gone
+    #[error("Ticket {0}, the cursor of the listing, no longer exists: list the tickets again from the start")]
+    StaleCursor(TicketId),
+}
+
+// Serializable image of a `TicketStore`, used to persist it to disk
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoreSnapshot {
+    pub counter: u64,
+    pub tickets: Vec<Ticket>,
+    // In the order the changes were made
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub comment_counter: u64,
+    #[serde(default)]
+    pub comments: Vec<TicketComment>,
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub links: Vec<TicketLink>,
+}
+
 #[derive(Clone)]
 pub struct TicketStore {
     tickets: BTreeMap<TicketId, Arc<RwLock<Ticket>>>,
     counter: u64,
+    workflow: Workflow,
+    // Change history of each ticket, oldest first
+    history: BTreeMap<TicketId, Vec<HistoryEntry>>,
+    // Comments of each ticket, in the order they were added
+    comments: BTreeMap<TicketId, BTreeMap<CommentId, TicketComment>>,
+    comment_counter: u64,
+    users: BTreeMap<UserId, User>,
+    // Inverted index of the labels: the tickets that have each label, so that listing
+    // the tickets with a given label doesn't scan the whole store.
+    // Derived from the tickets, hence not part of the snapshot.
+    labels: BTreeMap<TicketLabel, BTreeSet<TicketId>>,
+    // The links of each ticket, by source, and the same links by target
+    // (the latter derived from the former, hence not part of the snapshot)
+    links: BTreeMap<TicketId, BTreeSet<(LinkKind, TicketId)>>,
+    backlinks: BTreeMap<TicketId, BTreeSet<(LinkKind, TicketId)>>,
+    // Full-text index of the titles and descriptions, derived from the tickets as well
+    search: SearchIndex,
+    // Timestamps the changes, and tells which tickets are overdue
+    clock: Arc<dyn Clock>,
+    // Where the changes to the tickets are announced (see `events`)
+    events: EventBus,
+    // Changes not announced yet: the public methods announce them before returning,
+    // unless they're deferred (see `defer_events`)
+    pending: Vec<TicketEvent>,
+    defer_events: bool,
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TicketStore {
@@ -18,10 +137,148 @@ impl TicketStore {
         Self {
             tickets: BTreeMap::new(),
             counter: 0,
+            workflow: Workflow::standard(),
+            history: BTreeMap::new(),
+            comments: BTreeMap::new(),
+            comment_counter: 0,
+            users: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            links: BTreeMap::new(),
+            backlinks: BTreeMap::new(),
+            search: SearchIndex::new(),
+            clock: Arc::new(SystemClock),
+            events: EventBus::new(),
+            pending: Vec::new(),
+            defer_events: false,
+        }
+    }
+
+    // Status changes are checked against `workflow` (the standard one by default)
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    pub fn workflow(&self) -> &Workflow {
+        &self.workflow
+    }
+
+    // The system clock by default: tests inject a `ManualClock`
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    // The bus the changes to the tickets are announced on
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    // Announce the changes on `events`, instead of a bus of the store's own
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    // Hold the changes back until `flush_events` is called: for the stores that announce
+    // a change only once it is durable (see `PersistentTicketStore`)
+    pub(crate) fn defer_events(mut self) -> Self {
+        self.defer_events = true;
+        self
+    }
+
+    // Announce the changes held back
+    pub(crate) fn flush_events(&mut self) {
+        for event in self.pending.drain(..) {
+            self.events.publish(event);
+        }
+    }
+
+    // Forget the changes held back, e.g. those replayed from a log: they were announced already
+    pub(crate) fn discard_events(&mut self) {
+        self.pending.clear();
+    }
+
+    fn publish_events(&mut self) {
+        if !self.defer_events {
+            self.flush_events();
+        }
+    }
+
+    pub fn from_snapshot(snapshot: StoreSnapshot) -> Self {
+        let mut labels: BTreeMap<TicketLabel, BTreeSet<TicketId>> = BTreeMap::new();
+        let mut search = SearchIndex::new();
+        for ticket in &snapshot.tickets {
+            for label in &ticket.labels {
+                labels.entry(label.clone()).or_default().insert(ticket.id);
+            }
+            search.index(ticket);
+        }
+        let tickets = snapshot
+            .tickets
+            .into_iter()
+            .map(|ticket| (ticket.id, Arc::new(RwLock::new(ticket))))
+            .collect();
+        let mut history: BTreeMap<TicketId, Vec<HistoryEntry>> = BTreeMap::new();
+        for entry in snapshot.history {
+            history.entry(entry.ticket_id).or_default().push(entry);
+        }
+        let mut comments: BTreeMap<TicketId, BTreeMap<CommentId, TicketComment>> = BTreeMap::new();
+        for comment in snapshot.comments {
+            comments.entry(comment.ticket_id).or_default().insert(comment.id, comment);
+        }
+        let mut store = Self {
+            tickets,
+            counter: snapshot.counter,
+            workflow: Workflow::standard(),
+            history,
+            comments,
+            comment_counter: snapshot.comment_counter,
+            users: snapshot.users.into_iter().map(|user| (user.id.clone(), user)).collect(),
+            labels,
+            links: BTreeMap::new(),
+            backlinks: BTreeMap::new(),
+            search,
+            clock: Arc::new(SystemClock),
+            events: EventBus::new(),
+            pending: Vec::new(),
+            defer_events: false,
+        };
+        for link in snapshot.links {
+            store.index_link(link);
+        }
+        store
+    }
+
+    pub fn snapshot(&self) -> StoreSnapshot {
+        StoreSnapshot {
+            counter: self.counter,
+            tickets: self
+                .tickets
+                .values()
+                .map(|ticket| ticket.read().unwrap().clone())
+                .collect(),
+            history: self.history.values().flatten().cloned().collect(),
+            comment_counter: self.comment_counter,
+            comments: self.comments.values().flat_map(|comments| comments.values()).cloned().collect(),
+            users: self.users.values().cloned().collect(),
+            links: self
+                .links
+                .iter()
+                .flat_map(|(&source, links)| {
+                    links.iter().map(move |&(kind, target)| TicketLink { source, kind, target })
+                })
+                .collect(),
         }
     }
 
-    pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
+    // Fails if the assignee or the reporter isn't a registered user
+    pub fn add_ticket(&mut self, ticket: TicketDraft) -> Result<TicketId, TicketStoreError> {
+        check_users(&self.users, [ticket.assignee.as_ref(), ticket.reporter.as_ref()])?;
         let id = TicketId(self.counter);
         self.counter += 1;
         let ticket = Ticket {
@@ -29,32 +286,936 @@ impl TicketStore {
             title: ticket.title,
             description: ticket.description,
             status: Status::ToDo,
+            archived: false,
+            version: 1,
+            assignee: ticket.assignee,
+            reporter: ticket.reporter,
+            labels: BTreeSet::new(),
+            priority: ticket.priority,
+            due_date: ticket.due_date,
         };
+        self.search.index(&ticket);
+        self.pending.push(TicketEvent::Created { ticket: ticket.clone() });
         // each ticket is protected by its own lock 
         let ticket = Arc::new(RwLock::new(ticket));
         self.tickets.insert(id, ticket);
-        id
+        self.publish_events();
+        Ok(id)
     }
 
     pub fn get(&self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
         self.tickets.get(&id).cloned()
     }
 
-    pub fn get_mut(&mut self, patch: TicketPatch) -> () {
-        // get a mutable ticket, using the ticket id of the patch
-        let ticket_mut: &mut Arc<RwLock<Ticket>> = self.tickets.get_mut(&patch.id).expect("Ticket not found");
+    pub fn get_mut(&mut self, patch: TicketPatch) -> Result<(), TicketStoreError> {
+        self.patch_by(patch, None, &Actor::system())?;
+        Ok(())
+    }
+
+    // Compare-and-swap: apply the patch only if the ticket is still at version `expected`,
+    // i.e. if nobody changed it since the caller read it. Returns the updated ticket.
+    pub fn patch_if_version(&mut self, patch: TicketPatch, expected: u64) -> Result<Ticket, TicketStoreError> {
+        self.patch_by(patch, Some(expected), &Actor::system())
+    }
+
+    // Apply the patch on behalf of `actor` (if the ticket is at version `expected`, when set),
+    // record the change in the history of the ticket, and return the updated ticket.
+    // A ticket can't move to `Done` while a ticket that blocks it is open.
+    pub fn patch_by(
+        &mut self,
+        patch: TicketPatch,
+        expected: Option<u64>,
+        actor: &Actor,
+    ) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.patch_at(patch, expected, actor, self.clock.now_millis())?;
+        self.publish_events();
+        Ok(ticket)
+    }
+
+    // Same as `patch_by`, at a given time: used to replay the changes recorded in a log
+    pub(crate) fn patch_at(
+        &mut self,
+        patch: TicketPatch,
+        expected: Option<u64>,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<Ticket, TicketStoreError> {
+        // get the ticket to modify, using the ticket id of the patch
+        let ticket_lock: &Arc<RwLock<Ticket>> = self
+            .tickets
+            .get(&patch.id)
+            .ok_or(TicketStoreError::NotFound(patch.id))?;
+
+        let mut ticket = ticket_lock.write().unwrap(); // Acquire a write lock to modify the Ticket
+        if let Some(expected) = expected {
+            if ticket.version != expected {
+                return Err(TicketStoreError::VersionMismatch {
+                    id: patch.id,
+                    expected,
+                    actual: ticket.version,
+                });
+            }
+        }
+        let before = ticket.clone();
+        let assignee = patch.assignee.as_ref().and_then(Option::as_ref);
+        let reporter = patch.reporter.as_ref().and_then(Option::as_ref);
+        check_users(&self.users, [assignee, reporter])?;
+        if patch.status == Some(Status::Done) && ticket.status != Status::Done {
+            // The ticket itself is locked, but it can't block itself
+            if let Some(blocker) = self.open_blocker(ticket.id) {
+                return Err(TicketStoreError::OpenBlocker { id: ticket.id, blocker });
+            }
+        }
+        apply_patch(&self.workflow, &mut ticket, patch)?;
+
+        if ticket.title != before.title || ticket.description != before.description {
+            self.search.index(&ticket);
+        }
+        // A patch that changes nothing leaves the ticket alone: no new version, entry or event
+        let changes = diff(&before, &ticket);
+        if !changes.is_empty() {
+            ticket.version += 1;
+            self.history.entry(ticket.id).or_default().push(HistoryEntry {
+                ticket_id: ticket.id,
+                version: ticket.version,
+                timestamp,
+                actor: actor.clone(),
+                changes,
+            });
+            self.pending.push(TicketEvent::patched(&before, ticket.clone()));
+        }
+        Ok(ticket.clone())
+    }
+
+    // Apply the operations in order, on behalf of `actor`, all of them or none (see `batch`)
+    pub fn apply_batch(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        actor: &Actor,
+    ) -> Result<Vec<BatchOutcome>, BatchError> {
+        let outcomes = self.apply_batch_at(operations, actor, self.clock.now_millis())?;
+        self.publish_events();
+        Ok(outcomes)
+    }
+
+    // Same as `apply_batch`, at a given time: used to replay the batches recorded in a log
+    pub(crate) fn apply_batch_at(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<Vec<BatchOutcome>, BatchError> {
+        // The operations apply to the store itself, each ticket they touch being backed up first:
+        // if any of them fails, the tickets are put back as they were, and nothing is announced.
+        let defer_events = std::mem::replace(&mut self.defer_events, true);
+        let (counter, pending) = (self.counter, self.pending.len());
+        let mut backups: BTreeMap<TicketId, TicketBackup> = BTreeMap::new();
+        let mut outcomes = Vec::with_capacity(operations.len());
+        let mut failures = Vec::new();
+        let total = operations.len();
+        for (index, operation) in operations.into_iter().enumerate() {
+            let id = match &operation {
+                BatchOperation::Create { .. } => TicketId(self.counter),
+                BatchOperation::Patch { patch, .. } => patch.id,
+                BatchOperation::Delete { id } => *id,
+            };
+            backups.entry(id).or_insert_with(|| self.backup(id));
+            match self.apply_operation(operation, actor, timestamp) {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(error) => failures.push(OperationFailure { index, error }),
+            }
+        }
+        self.defer_events = defer_events;
+        if failures.is_empty() {
+            return Ok(outcomes);
+        }
+        for (id, backup) in backups {
+            self.restore_backup(id, backup);
+        }
+        self.counter = counter;
+        self.pending.truncate(pending);
+        Err(BatchError { total, failures })
+    }
+
+    // What an operation of a batch can change about a ticket
+    fn backup(&self, id: TicketId) -> TicketBackup {
+        TicketBackup {
+            ticket: self.tickets.get(&id).map(|ticket| ticket.read().unwrap().clone()),
+            history: self.history.get(&id).map(Vec::len),
+            comments: self.comments.get(&id).cloned(),
+            links: self.links.get(&id).cloned().unwrap_or_default(),
+            backlinks: self.backlinks.get(&id).cloned().unwrap_or_default(),
+        }
+    }
+
+    fn restore_backup(&mut self, id: TicketId, backup: TicketBackup) {
+        match backup.history {
+            Some(len) => self.history.entry(id).or_default().truncate(len),
+            None => {
+                self.history.remove(&id);
+            }
+        }
+        let Some(ticket) = backup.ticket else {
+            // Created by the batch
+            self.tickets.remove(&id);
+            self.search.remove(id);
+            return;
+        };
+        self.search.index(&ticket);
+        for label in &ticket.labels {
+            self.labels.entry(label.clone()).or_default().insert(id);
+        }
+        match self.tickets.get(&id) {
+            Some(current) => *current.write().unwrap() = ticket,
+            None => {
+                self.tickets.insert(id, Arc::new(RwLock::new(ticket)));
+            }
+        }
+        if let Some(comments) = backup.comments {
+            self.comments.insert(id, comments);
+        }
+        // A batch only ever removes links
+        for (kind, target) in backup.links {
+            self.index_link(TicketLink { source: id, kind, target });
+        }
+        for (kind, source) in backup.backlinks {
+            self.index_link(TicketLink { source, kind, target: id });
+        }
+    }
+
+    fn apply_operation(
+        &mut self,
+        operation: BatchOperation,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<BatchOutcome, TicketStoreError> {
+        match operation {
+            BatchOperation::Create { draft } => Ok(BatchOutcome::Created {
+                id: self.add_ticket(draft)?,
+            }),
+            BatchOperation::Patch { patch, version } => Ok(BatchOutcome::Patched {
+                ticket: self.patch_at(patch, version, actor, timestamp)?,
+            }),
+            BatchOperation::Delete { id } => Ok(BatchOutcome::Deleted { ticket: self.remove(id)? }),
+        }
+    }
+
+    // The changes made to a ticket, oldest first. Still available after the ticket is removed.
+    pub fn history(&self, id: TicketId) -> &[HistoryEntry] {
+        self.history.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // Add a comment to a ticket, on behalf of `actor`
+    pub fn add_comment(
+        &mut self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: &Actor,
+    ) -> Result<TicketComment, TicketStoreError> {
+        self.add_comment_at(ticket_id, draft, actor, self.clock.now_millis())
+    }
+
+    // The `_at` variants of the comment operations are used to replay a log, like `patch_at`
+    pub(crate) fn add_comment_at(
+        &mut self,
+        ticket_id: TicketId,
+        draft: CommentDraft,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<TicketComment, TicketStoreError> {
+        if !self.tickets.contains_key(&ticket_id) {
+            return Err(TicketStoreError::NotFound(ticket_id));
+        }
+        let comments = self.comments.entry(ticket_id).or_default();
+        if let Some(parent_id) = draft.parent_id {
+            if !comments.contains_key(&parent_id) {
+                return Err(TicketStoreError::UnknownParent { ticket_id, parent_id });
+            }
+        }
+
+        let id = CommentId(self.comment_counter);
+        self.comment_counter += 1;
+        let comment = TicketComment {
+            id,
+            ticket_id,
+            parent_id: draft.parent_id,
+            author: actor.clone(),
+            body: draft.body,
+            created_at: timestamp,
+            edited_at: None,
+        };
+        comments.insert(id, comment.clone());
+        self.record(ticket_id, actor, timestamp, comment::change(id, None, Some(&comment.body)));
+        Ok(comment)
+    }
+
+    // The comments of a ticket, in the order they were added
+    pub fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, TicketStoreError> {
+        if !self.tickets.contains_key(&ticket_id) {
+            return Err(TicketStoreError::NotFound(ticket_id));
+        }
+        let comments = self.comments.get(&ticket_id);
+        Ok(comments.into_iter().flat_map(|comments| comments.values()).cloned().collect())
+    }
+
+    pub fn edit_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: &Actor,
+    ) -> Result<TicketComment, TicketStoreError> {
+        self.edit_comment_at(ticket_id, comment_id, body, actor, self.clock.now_millis())
+    }
+
+    pub(crate) fn edit_comment_at(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<TicketComment, TicketStoreError> {
+        let comment = self
+            .comments
+            .get_mut(&ticket_id)
+            .and_then(|comments| comments.get_mut(&comment_id))
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        if comment.body == body {
+            return Ok(comment.clone());
+        }
+        let before = std::mem::replace(&mut comment.body, body);
+        comment.edited_at = Some(timestamp);
+        let comment = comment.clone();
+        self.record(ticket_id, actor, timestamp, comment::change(comment_id, Some(&before), Some(&comment.body)));
+        Ok(comment)
+    }
+
+    // Delete a comment, returning it. Its replies now reply to its own parent,
+    // so that the rest of the thread stays in place.
+    pub fn delete_comment(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: &Actor,
+    ) -> Result<TicketComment, TicketStoreError> {
+        self.delete_comment_at(ticket_id, comment_id, actor, self.clock.now_millis())
+    }
+
+    pub(crate) fn delete_comment_at(
+        &mut self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<TicketComment, TicketStoreError> {
+        let comments = self
+            .comments
+            .get_mut(&ticket_id)
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        let comment = comments
+            .remove(&comment_id)
+            .ok_or(TicketStoreError::CommentNotFound { ticket_id, comment_id })?;
+        for reply in comments.values_mut() {
+            if reply.parent_id == Some(comment_id) {
+                reply.parent_id = comment.parent_id;
+            }
+        }
+        self.record(ticket_id, actor, timestamp, comment::change(comment_id, Some(&comment.body), None));
+        Ok(comment)
+    }
+
+    // Record a change that doesn't affect the ticket itself (i.e. its comments)
+    fn record(&mut self, ticket_id: TicketId, actor: &Actor, timestamp: u64, change: FieldChange) {
+        let version = self
+            .tickets
+            .get(&ticket_id)
+            .map(|ticket| ticket.read().unwrap().version)
+            .unwrap_or_default();
+        self.history.entry(ticket_id).or_default().push(HistoryEntry {
+            ticket_id,
+            version,
+            timestamp,
+            actor: actor.clone(),
+            changes: vec![change],
+        });
+    }
+
+    // Label a ticket, on behalf of `actor`. Adding a label the ticket already has changes nothing.
+    pub fn add_label(&mut self, id: TicketId, label: TicketLabel, actor: &Actor) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.add_label_at(id, label, actor, self.clock.now_millis())?;
+        self.publish_events();
+        Ok(ticket)
+    }
+
+    pub(crate) fn add_label_at(
+        &mut self,
+        id: TicketId,
+        label: TicketLabel,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.relabel(id, actor, timestamp, |labels| labels.insert(label.clone()))?;
+        self.labels.entry(label).or_default().insert(id);
+        Ok(ticket)
+    }
+
+    // Remove a label from a ticket. Removing a label the ticket doesn't have changes nothing.
+    pub fn remove_label(&mut self, id: TicketId, label: &TicketLabel, actor: &Actor) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.remove_label_at(id, label, actor, self.clock.now_millis())?;
+        self.publish_events();
+        Ok(ticket)
+    }
+
+    pub(crate) fn remove_label_at(
+        &mut self,
+        id: TicketId,
+        label: &TicketLabel,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.relabel(id, actor, timestamp, |labels| labels.remove(label))?;
+        self.unindex_label(label, id);
+        Ok(ticket)
+    }
+
+    // Change the labels of a ticket with `update`, that returns whether they changed.
+    // Like a patch, a change bumps the version of the ticket and is recorded in its history.
+    fn relabel(
+        &mut self,
+        id: TicketId,
+        actor: &Actor,
+        timestamp: u64,
+        update: impl FnOnce(&mut BTreeSet<TicketLabel>) -> bool,
+    ) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.tickets.get(&id).ok_or(TicketStoreError::NotFound(id))?;
+        let mut ticket = ticket.write().unwrap();
+        let before = ticket.clone();
+        if update(&mut ticket.labels) {
+            ticket.version += 1;
+            self.history.entry(id).or_default().push(HistoryEntry {
+                ticket_id: id,
+                version: ticket.version,
+                timestamp,
+                actor: actor.clone(),
+                changes: diff(&before, &ticket),
+            });
+            self.pending.push(TicketEvent::patched(&before, ticket.clone()));
+        }
+        Ok(ticket.clone())
+    }
+
+    fn unindex_label(&mut self, label: &TicketLabel, id: TicketId) {
+        if let Some(ids) = self.labels.get_mut(label) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.labels.remove(label);
+            }
+        }
+    }
+
+    // Link two tickets, on behalf of `actor`, and return the links of the source ticket.
+    // Adding a link that already exists changes nothing.
+    pub fn add_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, TicketStoreError> {
+        self.add_link_at(link, actor, self.clock.now_millis())
+    }
+
+    pub(crate) fn add_link_at(
+        &mut self,
+        link: TicketLink,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<TicketLinks, TicketStoreError> {
+        for id in [link.source, link.target] {
+            if !self.tickets.contains_key(&id) {
+                return Err(TicketStoreError::NotFound(id));
+            }
+        }
+        if link.source == link.target {
+            return Err(TicketStoreError::SelfLink(link.source));
+        }
+        let exists = self
+            .links
+            .get(&link.source)
+            .is_some_and(|links| links.contains(&(link.kind, link.target)));
+        if !exists {
+            if link.kind == LinkKind::ParentOf {
+                if let Some(parent) = self.linked_to(link.target, LinkKind::ParentOf).next() {
+                    return Err(TicketStoreError::ParentAlreadySet {
+                        child: link.target,
+                        parent,
+                    });
+                }
+            }
+            if link.kind.is_acyclic() && self.reaches(link.target, link.kind, link.source) {
+                return Err(TicketStoreError::LinkCycle(link));
+            }
+            self.index_link(link);
+            self.record(link.source, actor, timestamp, link::change(&link, true));
+        }
+        self.links(link.source)
+    }
+
+    // Remove a link, and return the links of the source ticket.
+    // Removing a link that doesn't exist changes nothing.
+    pub fn remove_link(&mut self, link: TicketLink, actor: &Actor) -> Result<TicketLinks, TicketStoreError> {
+        self.remove_link_at(link, actor, self.clock.now_millis())
+    }
+
+    pub(crate) fn remove_link_at(
+        &mut self,
+        link: TicketLink,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<TicketLinks, TicketStoreError> {
+        if !self.tickets.contains_key(&link.source) {
+            return Err(TicketStoreError::NotFound(link.source));
+        }
+        if self.unindex_link(link) {
+            self.record(link.source, actor, timestamp, link::change(&link, false));
+        }
+        self.links(link.source)
+    }
+
+    // The links of a ticket, in both directions
+    pub fn links(&self, id: TicketId) -> Result<TicketLinks, TicketStoreError> {
+        if !self.tickets.contains_key(&id) {
+            return Err(TicketStoreError::NotFound(id));
+        }
+        let outgoing = self.links.get(&id).into_iter().flatten().map(|&(kind, target)| TicketLink {
+            source: id,
+            kind,
+            target,
+        });
+        let incoming = self.backlinks.get(&id).into_iter().flatten().map(|&(kind, source)| TicketLink {
+            source,
+            kind,
+            target: id,
+        });
+        Ok(TicketLinks::of(id, outgoing.chain(incoming)))
+    }
+
+    // The tickets that block a ticket, recursively
+    pub fn dependencies(&self, id: TicketId) -> Result<DependencyTree, TicketStoreError> {
+        DependencyTree::build(id, &mut |id| {
+            let ticket = self.tickets.get(&id).ok_or(TicketStoreError::NotFound(id))?;
+            let ticket = ticket.read().unwrap();
+            let blockers = self.linked_to(id, LinkKind::Blocks).collect();
+            Ok((ticket.title.clone(), ticket.status, blockers))
+        })
+    }
+
+    // The sources of the links of the given kind to ticket `id`
+    fn linked_to(&self, id: TicketId, kind: LinkKind) -> impl Iterator<Item = TicketId> + '_ {
+        self.backlinks
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter(move |(link_kind, _)| *link_kind == kind)
+            .map(|&(_, source)| source)
+    }
+
+    // Whether `to` can be reached from `from` by following links of the given kind
+    fn reaches(&self, from: TicketId, kind: LinkKind, to: TicketId) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![from];
+        while let Some(id) = pending.pop() {
+            if id == to {
+                return true;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            let targets = self.links.get(&id).into_iter().flatten();
+            pending.extend(targets.filter(|(link_kind, _)| *link_kind == kind).map(|&(_, target)| target));
+        }
+        false
+    }
+
+    // The first ticket that blocks ticket `id` and isn't closed yet
+    fn open_blocker(&self, id: TicketId) -> Option<TicketId> {
+        self.linked_to(id, LinkKind::Blocks).find(|blocker| {
+            self.tickets
+                .get(blocker)
+                .is_some_and(|ticket| !ticket.read().unwrap().status.is_closed())
+        })
+    }
+
+    fn index_link(&mut self, link: TicketLink) {
+        self.links.entry(link.source).or_default().insert((link.kind, link.target));
+        self.backlinks.entry(link.target).or_default().insert((link.kind, link.source));
+    }
+
+    // Returns whether the link existed
+    fn unindex_link(&mut self, link: TicketLink) -> bool {
+        let removed = remove_entry(&mut self.links, link.source, (link.kind, link.target));
+        remove_entry(&mut self.backlinks, link.target, (link.kind, link.source));
+        removed
+    }
+
+    // Register a user, so that tickets can be assigned to them
+    pub fn add_user(&mut self, user: User) -> Result<User, TicketStoreError> {
+        if self.users.contains_key(&user.id) {
+            return Err(TicketStoreError::UserAlreadyExists(user.id));
+        }
+        self.users.insert(user.id.clone(), user.clone());
+        Ok(user)
+    }
+
+    pub fn get_user(&self, id: &UserId) -> Option<User> {
+        self.users.get(id).cloned()
+    }
+
+    // Every registered user, by id
+    pub fn users(&self) -> Vec<User> {
+        self.users.values().cloned().collect()
+    }
 
-        let mut ticket = ticket_mut.write().unwrap(); // Acquire a write lock to modify the Ticket
+    // Unregister a user, returning it. Fails if a ticket (even an archived one) still refers to them.
+    pub fn remove_user(&mut self, id: &UserId) -> Result<User, TicketStoreError> {
+        if !self.users.contains_key(id) {
+            return Err(TicketStoreError::UserNotFound(id.clone()));
+        }
+        for ticket in self.tickets.values() {
+            let ticket = ticket.read().unwrap();
+            if ticket.assignee.as_ref() == Some(id) || ticket.reporter.as_ref() == Some(id) {
+                return Err(TicketStoreError::UserInUse {
+                    user: id.clone(),
+                    ticket_id: ticket.id,
+                });
+            }
+        }
+        Ok(self.users.remove(id).expect("checked above"))
+    }
 
-        // Only update fields that are `Some` in the patch
-        if let Some(new_title) = patch.title {
-            ticket.title = new_title;
+    // Remove a ticket for good, along with its comments and its links, returning it
+    pub fn remove(&mut self, id: TicketId) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.tickets.remove(&id).ok_or(TicketStoreError::NotFound(id))?;
+        self.comments.remove(&id);
+        for (kind, target) in self.links.remove(&id).unwrap_or_default() {
+            remove_entry(&mut self.backlinks, target, (kind, id));
         }
-        if let Some(new_description) = patch.description {
-            ticket.description = new_description;
+        for (kind, source) in self.backlinks.remove(&id).unwrap_or_default() {
+            remove_entry(&mut self.links, source, (kind, id));
+        }
+        let ticket = ticket.read().unwrap().clone();
+        for label in &ticket.labels {
+            self.unindex_label(label, id);
+        }
+        self.search.remove(id);
+        self.pending.push(TicketEvent::Deleted { ticket: ticket.clone() });
+        self.publish_events();
+        Ok(ticket)
+    }
+
+    // Soft-delete a ticket, on behalf of `actor`: it stays in the store, but is excluded
+    // from listings by default
+    pub fn archive(&mut self, id: TicketId, actor: &Actor) -> Result<Ticket, TicketStoreError> {
+        let ticket = self.archive_at(id, actor, self.clock.now_millis())?;
+        self.publish_events();
+        Ok(ticket)
+    }
+
+    pub(crate) fn archive_at(
+        &mut self,
+        id: TicketId,
+        actor: &Actor,
+        timestamp: u64,
+    ) -> Result<Ticket, TicketStoreError> {
+        let ticket_mut = self.tickets.get_mut(&id).ok_or(TicketStoreError::NotFound(id))?;
+        let mut ticket = ticket_mut.write().unwrap();
+        if ticket.archived {
+            return Err(TicketStoreError::AlreadyArchived(id));
         }
-        if let Some(new_status) = patch.status {
-            ticket.status = new_status;
+        let before = ticket.clone();
+        ticket.archived = true;
+        ticket.version += 1;
+        let after = ticket.clone();
+        drop(ticket);
+        self.history.entry(id).or_default().push(HistoryEntry {
+            ticket_id: id,
+            version: after.version,
+            timestamp,
+            actor: actor.clone(),
+            changes: diff(&before, &after),
+        });
+        self.pending.push(TicketEvent::patched(&before, after.clone()));
+        Ok(after)
+    }
+
+    pub fn list(&self, query: &TicketQuery) -> Result<TicketPage, TicketStoreError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let title = query.title.as_ref().map(|title| title.to_lowercase());
+        let today = self.clock.today();
+        let is_match = |ticket: &Ticket| {
+            (query.include_archived || !ticket.archived)
+                && query.status.is_none_or(|status| ticket.status == status)
+                && (query.assignee.is_none() || ticket.assignee == query.assignee)
+                && (query.reporter.is_none() || ticket.reporter == query.reporter)
+                && (!query.overdue || ticket.is_overdue(today))
+                && query.q.as_ref().is_none_or(|filter| filter.matches(ticket))
+                && title
+                    .as_ref()
+                    .is_none_or(|title| ticket.title.as_str().to_lowercase().contains(title))
+        };
+
+        let mut matching: Box<dyn Iterator<Item = Ticket>> = match query.order_by {
+            OrderBy::Id => {
+                // The BTreeMap is ordered by id, so the cursor translates directly into a range:
+                // no need to scan the tickets that were already returned in previous pages
+                let range = match (query.sort, query.cursor) {
+                    (SortOrder::Asc, Some(cursor)) => (Bound::Excluded(cursor), Bound::Unbounded),
+                    (SortOrder::Desc, Some(cursor)) => (Bound::Unbounded, Bound::Excluded(cursor)),
+                    (_, None) => (Bound::Unbounded, Bound::Unbounded),
+                };
+                let tickets = self
+                    .candidates(query.label.as_ref(), range, query.sort)
+                    .filter_map(|id| self.tickets.get(id))
+                    .map(|ticket| ticket.read().unwrap().clone())
+                    .filter(is_match);
+                Box::new(tickets)
+            }
+            // There is no index on the priority: the matching tickets are sorted on every request
+            OrderBy::Priority => {
+                let mut tickets: Vec<Ticket> = self
+                    .candidates(query.label.as_ref(), (Bound::Unbounded, Bound::Unbounded), SortOrder::Asc)
+                    .filter_map(|id| self.tickets.get(id))
+                    .map(|ticket| ticket.read().unwrap().clone())
+                    .filter(is_match)
+                    .collect();
+                tickets.sort_by_key(urgency);
+                if query.sort == SortOrder::Desc {
+                    tickets.reverse();
+                }
+                if let Some(cursor) = query.cursor {
+                    let ticket = self.tickets.get(&cursor).ok_or(TicketStoreError::StaleCursor(cursor))?;
+                    let cursor = urgency(&ticket.read().unwrap());
+                    tickets.retain(|ticket| match query.sort {
+                        SortOrder::Asc => urgency(ticket) > cursor,
+                        SortOrder::Desc => urgency(ticket) < cursor,
+                    });
+                }
+                Box::new(tickets.into_iter())
+            }
         };
+
+        let tickets: Vec<Ticket> = matching.by_ref().take(limit).collect();
+        // Only hand out a cursor if there is at least one more matching ticket
+        let next_cursor = match matching.next() {
+            Some(_) => tickets.last().map(|ticket| ticket.id),
+            None => None,
+        };
+
+        Ok(TicketPage { tickets, next_cursor })
+    }
+
+    // The tickets matching a filter, archived ones included, in id order.
+    // Each ticket is read-locked while the caller holds it, rather than copied.
+    pub fn filter<'a>(&'a self, filter: &'a Filter) -> impl Iterator<Item = impl Deref<Target = Ticket> + 'a> + 'a {
+        self.tickets
+            .values()
+            .map(|ticket| ticket.read().unwrap())
+            .filter(|ticket| filter.matches(ticket))
+    }
+
+    // The tickets whose title or description match the query, the most relevant first.
+    // Archived tickets are left out.
+    pub fn search(&self, query: &SearchQuery, limit: Option<usize>) -> Vec<SearchHit> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        self.search
+            .search(query)
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let ticket = self.tickets.get(&id)?.read().unwrap().clone();
+                (!ticket.archived).then_some(SearchHit { ticket, score })
+            })
+            .take(limit)
+            .collect()
+    }
+
+    // Add the users and tickets of a JSON Lines or CSV document (see `bulk`). The records that
+    // can't be imported are listed in the report, with the reason why.
+    pub fn import(&mut self, format: Format, reader: impl BufRead, ids: IdPolicy) -> io::Result<ImportReport> {
+        let report = bulk::import_into(self, format, reader, ids);
+        // Even if reading failed midway, the tickets imported until then are there
+        self.publish_events();
+        report
+    }
+
+    // Write every user, then every ticket (archived ones included) in id order, with its
+    // comments and links. Returns the number of tickets.
+    pub fn export(&self, format: Format, writer: impl Write) -> io::Result<usize> {
+        let mut writer = TicketWriter::new(format, writer)?;
+        for user in self.users.values() {
+            writer.write_user(user)?;
+        }
+        for (id, ticket) in &self.tickets {
+            let comments: Vec<TicketComment> =
+                self.comments.get(id).into_iter().flat_map(|comments| comments.values()).cloned().collect();
+            let links: Vec<TicketLink> = self
+                .links
+                .get(id)
+                .into_iter()
+                .flatten()
+                .map(|&(kind, target)| TicketLink { source: *id, kind, target })
+                .collect();
+            writer.write(&ticket.read().unwrap(), &comments, &links)?;
+        }
+        writer.finish()
+    }
+
+    // Insert an imported ticket, with the id of the record if it has one, and its comments
+    pub(crate) fn insert_record(
+        &mut self,
+        mut record: TicketRecord,
+    ) -> Result<(Ticket, Vec<TicketComment>), TicketStoreError> {
+        let id = record.id.unwrap_or(TicketId(self.counter));
+        let comments = std::mem::take(&mut record.comments);
+        let ticket = record.into_ticket(id);
+        self.restore(ticket.clone())?;
+        let comments = bulk::number_comments(comments, id, self.comment_counter, self.clock.now_millis());
+        for comment in &comments {
+            self.restore_comment(comment.clone());
+        }
+        self.pending.push(TicketEvent::Created { ticket: ticket.clone() });
+        Ok((ticket, comments))
+    }
+
+    // Insert a comment of a restored ticket as it is. The ids handed out afterwards come after its own.
+    pub(crate) fn restore_comment(&mut self, comment: TicketComment) {
+        self.comment_counter = self.comment_counter.max(comment.id.0.saturating_add(1));
+        self.comments.entry(comment.ticket_id).or_default().insert(comment.id, comment);
+    }
+
+    // Insert a ticket as it is. The ids handed out afterwards come after its own.
+    pub(crate) fn restore(&mut self, ticket: Ticket) -> Result<(), TicketStoreError> {
+        if self.tickets.contains_key(&ticket.id) {
+            return Err(TicketStoreError::AlreadyExists(ticket.id));
+        }
+        check_users(&self.users, [ticket.assignee.as_ref(), ticket.reporter.as_ref()])?;
+        self.counter = self.counter.max(ticket.id.0.saturating_add(1));
+        for label in &ticket.labels {
+            self.labels.entry(label.clone()).or_default().insert(ticket.id);
+        }
+        self.search.index(&ticket);
+        self.tickets.insert(ticket.id, Arc::new(RwLock::new(ticket)));
+        Ok(())
+    }
+
+    // The ids of the tickets a listing has to look at, in `range` and in the given order.
+    // With a label, only the tickets of its index entry (also ordered by id) are visited.
+    fn candidates(
+        &self,
+        label: Option<&TicketLabel>,
+        range: (Bound<TicketId>, Bound<TicketId>),
+        sort: SortOrder,
+    ) -> Box<dyn Iterator<Item = &TicketId> + '_> {
+        match label {
+            Some(label) => match self.labels.get(label) {
+                Some(ids) => in_order(ids.range(range), sort),
+                None => Box::new(std::iter::empty()),
+            },
+            None => in_order(self.tickets.range(range).map(|(id, _)| id), sort),
+        }
+    }
+}
+
+impl ImportTarget for TicketStore {
+    type Error = io::Error;
+
+    fn insert_user(&mut self, user: User) -> io::Result<Result<(), TicketStoreError>> {
+        Ok(self.add_user(user).map(|_| ()))
+    }
+
+    fn insert_record(&mut self, record: TicketRecord) -> io::Result<Result<TicketId, TicketStoreError>> {
+        Ok(TicketStore::insert_record(self, record).map(|(ticket, _)| ticket.id))
+    }
+
+    fn insert_link(&mut self, link: TicketLink) -> io::Result<Result<(), TicketStoreError>> {
+        Ok(self.add_link(link, &Actor::system()).map(|_| ()))
+    }
+}
+
+// The position of a ticket when listing by priority: the most urgent first,
+// and the tickets without a due date after those with one
+fn urgency(ticket: &Ticket) -> (Priority, bool, Option<NaiveDate>, TicketId) {
+    (ticket.priority, ticket.due_date.is_none(), ticket.due_date, ticket.id)
+}
+
+fn in_order<'a>(
+    ids: impl DoubleEndedIterator<Item = &'a TicketId> + 'a,
+    sort: SortOrder,
+) -> Box<dyn Iterator<Item = &'a TicketId> + 'a> {
+    match sort {
+        SortOrder::Asc => Box::new(ids),
+        SortOrder::Desc => Box::new(ids.rev()),
+    }
+}
+
+fn apply_patch(workflow: &Workflow, ticket: &mut Ticket, patch: TicketPatch) -> Result<(), TicketStoreError> {
+    // Check the transition first: a rejected patch leaves the ticket untouched
+    if let Some(new_status) = patch.status {
+        workflow
+            .check(ticket.status, new_status)
+            .map_err(|source| TicketStoreError::Workflow { id: ticket.id, source })?;
+    }
+
+    // Only update fields that are `Some` in the patch
+    if let Some(new_title) = patch.title {
+        ticket.title = new_title;
+    }
+    if let Some(new_description) = patch.description {
+        ticket.description = new_description;
+    }
+    if let Some(new_status) = patch.status {
+        ticket.status = new_status;
+    };
+    if let Some(new_assignee) = patch.assignee {
+        ticket.assignee = new_assignee;
+    }
+    if let Some(new_reporter) = patch.reporter {
+        ticket.reporter = new_reporter;
+    }
+    if let Some(new_priority) = patch.priority {
+        ticket.priority = new_priority;
+    }
+    if let Some(new_due_date) = patch.due_date {
+        ticket.due_date = new_due_date;
+    }
+    Ok(())
+}
+
+// A ticket as it was before a batch touched it, with what goes away when it is removed.
+// `ticket` is `None` for the tickets the batch creates.
+struct TicketBackup {
+    ticket: Option<Ticket>,
+    // The length of the history of the ticket, if it had one
+    history: Option<usize>,
+    comments: Option<BTreeMap<CommentId, TicketComment>>,
+    links: BTreeSet<(LinkKind, TicketId)>,
+    backlinks: BTreeSet<(LinkKind, TicketId)>,
+}
+
+// Check that the users a ticket refers to are registered
+fn check_users(users: &BTreeMap<UserId, User>, ids: [Option<&UserId>; 2]) -> Result<(), TicketStoreError> {
+    match ids.into_iter().flatten().find(|id| !users.contains_key(*id)) {
+        Some(id) => Err(TicketStoreError::UnknownUser(id.clone())),
+        None => Ok(()),
+    }
+}
+
+// Remove `entry` from the set of `id`, dropping the set once empty. Returns whether it was there.
+fn remove_entry(
+    sets: &mut BTreeMap<TicketId, BTreeSet<(LinkKind, TicketId)>>,
+    id: TicketId,
+    entry: (LinkKind, TicketId),
+) -> bool {
+    let Some(set) = sets.get_mut(&id) else {
+        return false;
+    };
+    let removed = set.remove(&entry);
+    if set.is_empty() {
+        sets.remove(&id);
     }
+    removed
 }
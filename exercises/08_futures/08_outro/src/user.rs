@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+// The users of the workspace: tickets are reported by and assigned to them.
+// A user is identified by a handle, e.g. "alice", the same name they use as `X-Actor`.
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UserId(String);
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum UserIdError {
+    #[error("The user id cannot be empty")]
+    Empty,
+    #[error("The user id cannot be longer than 32 bytes")]
+    TooLong,
+    #[error("The user id can only contain lowercase letters, digits, '.', '-' and '_', not {0:?}")]
+    InvalidCharacter(char),
+}
+
+impl UserId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for UserId {
+    type Error = UserIdError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for UserId {
+    type Error = UserIdError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        validate(value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl From<UserId> for String {
+    fn from(id: UserId) -> Self {
+        id.0
+    }
+}
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn validate(id: &str) -> Result<(), UserIdError> {
+    if id.is_empty() {
+        return Err(UserIdError::Empty);
+    }
+    if id.len() > 32 {
+        return Err(UserIdError::TooLong);
+    }
+    match id
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '_')))
+    {
+        Some(c) => Err(UserIdError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+// The display name of a user, e.g. "Alice Smith": anything but blank
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UserName(String);
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum UserNameError {
+    #[error("The name cannot be empty")]
+    Empty,
+}
+
+impl UserName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for UserName {
+    type Error = UserNameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.trim().is_empty() {
+            return Err(UserNameError::Empty);
+        }
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for UserName {
+    type Error = UserNameError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from(value.to_string())
+    }
+}
+
+impl From<UserName> for String {
+    fn from(name: UserName) -> Self {
+        name.0
+    }
+}
+
+impl std::fmt::Display for UserName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: UserId,
+    pub name: UserName,
+}
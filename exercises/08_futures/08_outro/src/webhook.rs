@@ -0,0 +1,447 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::data::Ticket;
+use crate::events::{EventBus, EventFilter, ResumeError, SequencedEvent, Subscription, TicketEvent};
+use crate::history::FieldChange;
+use crate::retry::RetryPolicy;
+use crate::server::ShutdownSignal;
+
+// Webhooks: URLs that the server POSTs a JSON payload to (see `WebhookPayload`) whenever a ticket
+// is created, patched, or moves to another status. Deliveries follow the change-feed of the store
+// (see `events`) and are sent in the background, by `WebhookDispatcher::run`.
+//
+// Each delivery is signed with the secret of its webhook: `X-Ticket-Signature` holds "sha256="
+// followed by the hex HMAC-SHA256 of the body, which receivers check with `verify`.
+// A delivery that fails (no answer in time, or a status other than 2xx) is retried with an
+// exponential backoff. Once out of retries, it becomes a dead letter, that can be redelivered.
+// Events the dispatcher falls too far behind to read are not delivered, only counted.
+//
+// Webhooks and dead letters are kept in memory: they are lost, along with the deliveries still
+// waiting for a retry, when the server stops.
+
+pub const SIGNATURE_HEADER: &str = "x-ticket-signature";
+pub const EVENT_HEADER: &str = "x-ticket-event";
+pub const DELIVERY_HEADER: &str = "x-ticket-delivery";
+
+// Receivers that take longer than this to answer have failed the attempt
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// About two minutes of retries before giving up on a delivery
+pub const DEFAULT_WEBHOOK_RETRY: RetryPolicy = RetryPolicy {
+    max_retries: 6,
+    initial_backoff: Duration::from_secs(1),
+    max_backoff: Duration::from_secs(60),
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct WebhookId(pub u64);
+
+impl std::fmt::Display for WebhookId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Identifies a delivery across its attempts and redeliveries, so that receivers can ignore duplicates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DeliveryId(pub u64);
+
+impl std::fmt::Display for DeliveryId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Created,
+    // Any change to the ticket: a patch, a label added or removed, the ticket archived
+    Patched,
+    // A patch that changed the status
+    StatusChanged,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [WebhookEvent::Created, WebhookEvent::Patched, WebhookEvent::StatusChanged];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Created => "created",
+            WebhookEvent::Patched => "patched",
+            WebhookEvent::StatusChanged => "status_changed",
+        }
+    }
+
+    // The webhook events of a change: a patch of the status is both `patched` and `status_changed`.
+    // Deleted tickets have none.
+    pub fn of(event: &TicketEvent) -> Vec<WebhookEvent> {
+        match event {
+            TicketEvent::Created { .. } => vec![WebhookEvent::Created],
+            TicketEvent::Patched { changes, .. } if changes.iter().any(|change| change.field == "status") => {
+                vec![WebhookEvent::Patched, WebhookEvent::StatusChanged]
+            }
+            TicketEvent::Patched { .. } => vec![WebhookEvent::Patched],
+            TicketEvent::Deleted { .. } => Vec::new(),
+        }
+    }
+}
+
+// Body of POST /webhooks, e.g.
+// {"url": "https://ci.example.com/hooks/tickets", "secret": "s3cr3t", "events": ["status_changed"]}
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookDraft {
+    pub url: String,
+    pub secret: String,
+    // The events to deliver: all of them if left empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+// A registered webhook. Its secret is never sent back.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+// The body of a delivery, e.g.
+// {"delivery": 7, "event": "status_changed", "seq": 12, "ticket": {...},
+//  "changes": [{"field": "status", "before": "ToDo", "after": "InProgress"}]}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub delivery: DeliveryId,
+    pub event: WebhookEvent,
+    // Sequence number of the change in the change-feed
+    pub seq: u64,
+    // The ticket after the change
+    pub ticket: Ticket,
+    // Empty for `created`
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+}
+
+// A delivery that failed every attempt
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub webhook: WebhookId,
+    pub url: String,
+    pub payload: WebhookPayload,
+    pub attempts: u32,
+    // Why the last attempt failed
+    pub error: String,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook URL {url:?}: {reason}")]
+    InvalidUrl { url: String, reason: String },
+    #[error("The secret of a webhook cannot be empty")]
+    EmptySecret,
+    #[error("Webhook {0} not found")]
+    NotFound(WebhookId),
+    #[error("Delivery {0} is not a dead letter")]
+    DeadLetterNotFound(DeliveryId),
+}
+
+// The webhooks of the server, shared by the API handlers and the dispatcher. A clone is a new
+// handle to the same webhooks.
+#[derive(Clone, Debug)]
+pub struct Webhooks {
+    registry: Arc<Mutex<Registry>>,
+    // Dead letters handed back to the dispatcher
+    redeliveries: mpsc::UnboundedSender<Delivery>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    next_delivery: u64,
+    webhooks: BTreeMap<WebhookId, Registration>,
+    dead_letters: BTreeMap<DeliveryId, DeadLetter>,
+    // Events the dispatcher fell too far behind to deliver (see `skipped_events`)
+    skipped_events: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Registration {
+    webhook: Webhook,
+    secret: String,
+}
+
+// A payload on its way to a webhook
+#[derive(Debug)]
+struct Delivery {
+    registration: Registration,
+    payload: WebhookPayload,
+}
+
+impl Webhooks {
+    // The webhooks, and the dispatcher that delivers their events: see `WebhookDispatcher::run`
+    pub fn new(retry: RetryPolicy) -> (Webhooks, WebhookDispatcher) {
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let (redeliveries, redelivered) = mpsc::unbounded_channel();
+        let http = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .expect("the HTTP client has a valid configuration");
+        let dispatcher = WebhookDispatcher {
+            registry: registry.clone(),
+            redelivered,
+            http,
+            retry,
+        };
+        (Webhooks { registry, redeliveries }, dispatcher)
+    }
+
+    pub fn register(&self, draft: WebhookDraft) -> Result<Webhook, WebhookError> {
+        let url = reqwest::Url::parse(&draft.url).map_err(|err| WebhookError::InvalidUrl {
+            url: draft.url.clone(),
+            reason: err.to_string(),
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(WebhookError::InvalidUrl {
+                url: draft.url,
+                reason: "only http and https URLs can receive deliveries".to_string(),
+            });
+        }
+        if draft.secret.is_empty() {
+            return Err(WebhookError::EmptySecret);
+        }
+        let events = if draft.events.is_empty() {
+            WebhookEvent::ALL.to_vec()
+        } else {
+            WebhookEvent::ALL
+                .into_iter()
+                .filter(|event| draft.events.contains(event))
+                .collect()
+        };
+
+        let mut registry = self.registry.lock().unwrap();
+        let id = WebhookId(registry.next_id);
+        registry.next_id += 1;
+        let webhook = Webhook {
+            id,
+            url: draft.url,
+            events,
+        };
+        let registration = Registration {
+            webhook: webhook.clone(),
+            secret: draft.secret,
+        };
+        registry.webhooks.insert(id, registration);
+        Ok(webhook)
+    }
+
+    // Deliveries already under way still complete
+    pub fn unregister(&self, id: WebhookId) -> Result<(), WebhookError> {
+        let mut registry = self.registry.lock().unwrap();
+        registry.webhooks.remove(&id).ok_or(WebhookError::NotFound(id))?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        let registry = self.registry.lock().unwrap();
+        registry.webhooks.values().map(|registration| registration.webhook.clone()).collect()
+    }
+
+    // Oldest first
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.registry.lock().unwrap().dead_letters.values().cloned().collect()
+    }
+
+    // How many events were never delivered because the dispatcher fell too far behind the store:
+    // they were dropped from the change-feed before it could read them
+    pub fn skipped_events(&self) -> u64 {
+        self.registry.lock().unwrap().skipped_events
+    }
+
+    // Try a dead letter again, with the same delivery id and a fresh set of retries. It goes to
+    // the current URL and secret of its webhook, which must still be registered.
+    pub fn redeliver(&self, delivery: DeliveryId) -> Result<(), WebhookError> {
+        let mut registry = self.registry.lock().unwrap();
+        let dead_letter = registry
+            .dead_letters
+            .get(&delivery)
+            .ok_or(WebhookError::DeadLetterNotFound(delivery))?;
+        let registration = registry
+            .webhooks
+            .get(&dead_letter.webhook)
+            .ok_or(WebhookError::NotFound(dead_letter.webhook))?
+            .clone();
+        let dead_letter = registry.dead_letters.remove(&delivery).expect("found above");
+        // If the dispatcher is gone, so is the server: nothing will be delivered anymore
+        let _ = self.redeliveries.send(Delivery {
+            registration,
+            payload: dead_letter.payload,
+        });
+        Ok(())
+    }
+}
+
+// Sends the events of the store to the webhooks, in the background
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    registry: Arc<Mutex<Registry>>,
+    redelivered: mpsc::UnboundedReceiver<Delivery>,
+    http: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl WebhookDispatcher {
+    // Deliver the events published on `bus` from now on, until the server shuts down.
+    // Each delivery runs on its own task, so that a slow receiver doesn't hold back the others.
+    pub async fn run(mut self, bus: EventBus, shutdown: ShutdownSignal) {
+        let mut last_seq = bus.last_seq();
+        let mut subscription = self.subscribe(&bus, last_seq);
+        let mut deliveries = JoinSet::new();
+        let shutdown = shutdown.wait();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                event = subscription.next() => match event {
+                    Some(event) => {
+                        last_seq = event.seq;
+                        for delivery in self.deliveries(&event) {
+                            deliveries.spawn(deliver(self.http.clone(), self.retry, delivery));
+                        }
+                    }
+                    // Fell behind: pick up where we left off
+                    None => subscription = self.subscribe(&bus, last_seq),
+                },
+                Some(delivery) = self.redelivered.recv() => {
+                    deliveries.spawn(deliver(self.http.clone(), self.retry, delivery));
+                }
+                Some(result) = deliveries.join_next() => {
+                    if let Ok(Err(dead_letter)) = result {
+                        let mut registry = self.registry.lock().unwrap();
+                        registry.dead_letters.insert(dead_letter.payload.delivery, dead_letter);
+                    }
+                }
+                // Dropping `deliveries` abandons the ones waiting for a retry
+                _ = &mut shutdown => return,
+            }
+        }
+    }
+
+    // Every event after `since`, or those still available if some were already dropped
+    fn subscribe(&self, bus: &EventBus, mut since: u64) -> Subscription {
+        loop {
+            match bus.subscribe(Some(since), EventFilter::default()) {
+                Ok(subscription) => return subscription,
+                Err(ResumeError::Expired { oldest, .. }) => {
+                    self.registry.lock().unwrap().skipped_events += oldest - 1 - since;
+                    since = oldest - 1;
+                }
+                // The bus started over: nothing was dropped
+                Err(ResumeError::Unknown { last, .. }) => since = last,
+            }
+        }
+    }
+
+    // A delivery for each webhook interested in the event
+    fn deliveries(&self, event: &SequencedEvent) -> Vec<Delivery> {
+        let (ticket, changes) = match &event.event {
+            TicketEvent::Patched { ticket, changes } => (ticket, changes.clone()),
+            event => (event.ticket(), Vec::new()),
+        };
+        let mut registry = self.registry.lock().unwrap();
+        let mut deliveries = Vec::new();
+        for kind in WebhookEvent::of(&event.event) {
+            let registrations: Vec<Registration> = registry
+                .webhooks
+                .values()
+                .filter(|registration| registration.webhook.events.contains(&kind))
+                .cloned()
+                .collect();
+            for registration in registrations {
+                let delivery = DeliveryId(registry.next_delivery);
+                registry.next_delivery += 1;
+                let payload = WebhookPayload {
+                    delivery,
+                    event: kind,
+                    seq: event.seq,
+                    ticket: ticket.clone(),
+                    changes: changes.clone(),
+                };
+                deliveries.push(Delivery { registration, payload });
+            }
+        }
+        deliveries
+    }
+}
+
+// POST the payload until the receiver accepts it, or the retries run out
+async fn deliver(http: reqwest::Client, retry: RetryPolicy, delivery: Delivery) -> Result<(), DeadLetter> {
+    let Delivery { registration, payload } = delivery;
+    let body = serde_json::to_vec(&payload).expect("payloads serialize to JSON");
+    let signature = sign(&registration.secret, &body);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let request = http
+            .post(&registration.webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, payload.event.as_str())
+            .header(DELIVERY_HEADER, payload.delivery.to_string())
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone());
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => format!("The receiver answered {}", response.status()),
+            Err(err) => format!("Request failed: {}", err),
+        };
+        if attempts > retry.max_retries {
+            return Err(DeadLetter {
+                webhook: registration.webhook.id,
+                url: registration.webhook.url,
+                payload,
+                attempts,
+                error,
+            });
+        }
+        tokio::time::sleep(retry.backoff(attempts)).await;
+    }
+}
+
+// The value of `X-Ticket-Signature` for a body: "sha256=" followed by the hex HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+// Whether `signature` is the one of `body`, for receivers. The comparison takes the same time
+// however many bytes match, so that it gives no hint about the right signature.
+pub fn verify(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(bytes) = signature.strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    mac.verify_slice(&bytes).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(hex.get(start..start + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::data::Status;
+
+// The statuses a workspace uses, and the transitions allowed between them.
+//
+// The standard workflow only enables `ToDo`, `InProgress` and `Done`, with the transitions
+// ToDo <-> InProgress -> Done, and Done -> InProgress to reopen a ticket:
+// a done ticket can't go straight back to the backlog, nor can a new ticket be done right away.
+//
+// `Blocked`, `InReview` and `Cancelled` are opt-in, see `Workflow::enable`: once enabled,
+// they come with their own standard transitions (e.g. InProgress -> InReview -> Done).
+// Transitions from or to a status that isn't enabled are always rejected.
+//
+// Setting a ticket to the status it already has is not a transition, and is always accepted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Workflow {
+    enabled: BTreeSet<Status>,
+    // `None` means that every transition between enabled statuses is allowed
+    transitions: Option<BTreeSet<(Status, Status)>>,
+}
+
+// The standard transitions, between core and optional statuses
+const STANDARD_TRANSITIONS: &[(Status, Status)] = &[
+    (Status::ToDo, Status::InProgress),
+    (Status::InProgress, Status::ToDo),
+    (Status::InProgress, Status::Done),
+    // Reopen a ticket: it goes back to work, not to the backlog
+    (Status::Done, Status::InProgress),
+    (Status::ToDo, Status::Blocked),
+    (Status::InProgress, Status::Blocked),
+    (Status::Blocked, Status::ToDo),
+    (Status::Blocked, Status::InProgress),
+    (Status::InProgress, Status::InReview),
+    (Status::InReview, Status::InProgress),
+    (Status::InReview, Status::Done),
+    (Status::ToDo, Status::Cancelled),
+    (Status::InProgress, Status::Cancelled),
+    (Status::Blocked, Status::Cancelled),
+    (Status::Cancelled, Status::ToDo),
+];
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum WorkflowError {
+    #[error("Status {} is not enabled in this workspace", .0.as_str())]
+    StatusNotEnabled(Status),
+    #[error("A ticket can't go from {} to {}", .from.as_str(), .to.as_str())]
+    InvalidTransition { from: Status, to: Status },
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Workflow {
+    pub fn standard() -> Self {
+        Self {
+            enabled: Status::CORE.iter().copied().collect(),
+            transitions: Some(STANDARD_TRANSITIONS.iter().copied().collect()),
+        }
+    }
+
+    // Every status is enabled, and a ticket can go from any status to any other.
+    // Used to restore tickets (replaying a log, importing an export) whatever their history.
+    pub fn unrestricted() -> Self {
+        Self {
+            enabled: Status::ALL.iter().copied().collect(),
+            transitions: None,
+        }
+    }
+
+    // Enable an optional status (`Blocked`, `InReview` or `Cancelled`)
+    pub fn enable(mut self, status: Status) -> Self {
+        self.enabled.insert(status);
+        self
+    }
+
+    pub fn allow(mut self, from: Status, to: Status) -> Self {
+        if let Some(transitions) = &mut self.transitions {
+            transitions.insert((from, to));
+        }
+        self
+    }
+
+    pub fn forbid(mut self, from: Status, to: Status) -> Self {
+        let transitions = self.transitions.get_or_insert_with(|| {
+            Status::ALL
+                .iter()
+                .flat_map(|from| Status::ALL.iter().map(move |to| (*from, *to)))
+                .collect()
+        });
+        transitions.remove(&(from, to));
+        self
+    }
+
+    pub fn is_enabled(&self, status: Status) -> bool {
+        self.enabled.contains(&status)
+    }
+
+    // The enabled statuses, in declaration order
+    pub fn statuses(&self) -> impl Iterator<Item = Status> + '_ {
+        self.enabled.iter().copied()
+    }
+
+    pub fn check(&self, from: Status, to: Status) -> Result<(), WorkflowError> {
+        // Staying put is always allowed, even in a status that has been disabled since
+        if from == to {
+            return Ok(());
+        }
+        if !self.is_enabled(to) {
+            return Err(WorkflowError::StatusNotEnabled(to));
+        }
+        // A ticket may be in a status that has been disabled since: it can't move anymore,
+        // until the status is enabled again
+        if !self.is_enabled(from) {
+            return Err(WorkflowError::StatusNotEnabled(from));
+        }
+        match &self.transitions {
+            Some(transitions) if !transitions.contains(&(from, to)) => {
+                Err(WorkflowError::InvalidTransition { from, to })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// The `[workflow]` table of the config file, e.g.
+//
+//   [workflow]
+//   extra_statuses = ["Blocked", "InReview"]
+//   allow = [["Done", "ToDo"]]
+//   forbid = [["InProgress", "ToDo"]]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkflowConfig {
+    pub extra_statuses: Vec<Status>,
+    pub allow: Vec<(Status, Status)>,
+    pub forbid: Vec<(Status, Status)>,
+}
+
+impl WorkflowConfig {
+    pub fn workflow(&self) -> Workflow {
+        let mut workflow = Workflow::standard();
+        for status in &self.extra_statuses {
+            workflow = workflow.enable(*status);
+        }
+        for (from, to) in &self.allow {
+            workflow = workflow.allow(*from, *to);
+        }
+        for (from, to) in &self.forbid {
+            workflow = workflow.forbid(*from, *to);
+        }
+        workflow
+    }
+}
@@ -0,0 +1,278 @@
+use reqwest::StatusCode;
+use std::sync::{Arc, Mutex, RwLock};
+
+use ticket_fields::{CommentBody, TicketDescription, TicketLabel, TicketTitle};
+
+use outro_08::batch::{BatchError, BatchOperation, BatchOutcome, OperationFailure};
+use outro_08::client::{ClientError, TicketApiClient};
+use outro_08::comment::CommentDraft;
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::history::Actor;
+use outro_08::link::{LinkKind, TicketLink};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::repository::{RepositoryError, TicketRepository};
+use outro_08::search::SearchQuery;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("Found while triaging").unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn create(title: &str) -> BatchOperation {
+    BatchOperation::Create { draft: draft(title) }
+}
+
+fn move_to(id: TicketId, status: Status) -> BatchOperation {
+    BatchOperation::Patch {
+        patch: TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(status),
+            assignee: None,
+            reporter: None,
+            priority: None,
+            due_date: None,
+        },
+        version: None,
+    }
+}
+
+fn delete(id: TicketId) -> BatchOperation {
+    BatchOperation::Delete { id }
+}
+
+fn triage() -> Actor {
+    Actor::new("triage-bot")
+}
+
+// The batch error of a repository, if it failed with one
+fn batch_error(result: Result<Vec<BatchOutcome>, RepositoryError>) -> BatchError {
+    match result {
+        Err(RepositoryError::Batch(err)) => err,
+        other => panic!("expected a batch error, got {:?}", other),
+    }
+}
+
+// The same scenario on every backend: two tickets, a failed batch that changes nothing,
+// then a batch that applies entirely
+async fn check_batches<R: TicketRepository>(repository: &R) {
+    let first = repository.insert(draft("Flaky login test")).await.unwrap();
+    let second = repository.insert(draft("Checkout crash")).await.unwrap();
+
+    // Every failure is reported: the patch of a ticket deleted earlier in the batch,
+    // a transition the workflow doesn't allow, and an unknown ticket
+    let failed = vec![
+        create("Never created"),
+        delete(first),
+        move_to(first, Status::InProgress),
+        move_to(second, Status::Done),
+        delete(TicketId(99)),
+    ];
+    let err = batch_error(repository.apply_batch(failed, triage()).await);
+    assert_eq!(err.total, 5);
+    let failures: Vec<usize> = err.failures.iter().map(|failure| failure.index).collect();
+    assert_eq!(failures, [2, 3, 4]);
+    assert_eq!(
+        err.failures[0],
+        OperationFailure {
+            index: 2,
+            error: TicketStoreError::NotFound(first)
+        }
+    );
+    assert_eq!(err.failures[2].error, TicketStoreError::NotFound(TicketId(99)));
+
+    // Nothing changed, not even the next id
+    let page = repository.list(TicketQuery::default()).await.unwrap();
+    let ids: Vec<TicketId> = page.tickets.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(ids, [first, second]);
+    assert!(repository.history(first).await.unwrap().is_empty());
+    let search = SearchQuery::try_from("never").unwrap();
+    assert!(repository.search(search, None).await.unwrap().is_empty());
+
+    let outcomes = repository
+        .apply_batch(
+            vec![
+                create("Update the docs"),
+                move_to(second, Status::InProgress),
+                move_to(second, Status::Done),
+                delete(first),
+            ],
+            triage(),
+        )
+        .await
+        .unwrap();
+    let third = TicketId(second.0 + 1);
+    assert_eq!(outcomes[0], BatchOutcome::Created { id: third });
+    match &outcomes[2] {
+        BatchOutcome::Patched { ticket } => {
+            assert_eq!(ticket.status, Status::Done);
+            assert_eq!(ticket.version, 3);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(matches!(&outcomes[3], BatchOutcome::Deleted { ticket } if ticket.id == first));
+
+    let page = repository.list(TicketQuery::default()).await.unwrap();
+    let ids: Vec<TicketId> = page.tickets.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(ids, [second, third]);
+    let history = repository.history(second).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].actor, triage());
+    let search = SearchQuery::try_from("docs").unwrap();
+    assert_eq!(repository.search(search, None).await.unwrap()[0].ticket.id, third);
+    let search = SearchQuery::try_from("flaky").unwrap();
+    assert!(repository.search(search, None).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_memory_batches() {
+    check_batches(&Arc::new(RwLock::new(TicketStore::new()))).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_batches() {
+    check_batches(&Arc::new(Mutex::new(SqliteTicketStore::open_in_memory().unwrap()))).await;
+}
+
+#[tokio::test]
+async fn test_persistent_batches() {
+    let dir = tempfile::tempdir().unwrap();
+    let repository = Arc::new(RwLock::new(PersistentTicketStore::open(dir.path()).unwrap()));
+    check_batches(&repository).await;
+    let before = repository.list(TicketQuery::default()).await.unwrap();
+    let history = repository.history(TicketId(1)).await.unwrap();
+    drop(repository);
+
+    // The batches are replayed from the log, failed ones excluded
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.list(&TicketQuery::default()).unwrap(), before);
+    assert_eq!(store.history(TicketId(1)), history);
+}
+
+#[test]
+fn test_expected_versions() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft("Flaky login test")).unwrap();
+    let BatchOperation::Patch { patch, .. } = move_to(id, Status::InProgress) else {
+        unreachable!()
+    };
+    let stale = BatchOperation::Patch {
+        patch: patch.clone(),
+        version: Some(0),
+    };
+    let err = store.apply_batch(vec![stale], &triage()).unwrap_err();
+    assert_eq!(
+        err.failures[0].error,
+        TicketStoreError::VersionMismatch {
+            id,
+            expected: 0,
+            actual: 1
+        }
+    );
+    let current = BatchOperation::Patch {
+        patch,
+        version: Some(1),
+    };
+    assert_eq!(store.apply_batch(vec![current], &triage()).unwrap().len(), 1);
+}
+
+#[test]
+fn test_failed_batch_restores_the_tickets() {
+    let mut store = TicketStore::new();
+    let first = store.add_ticket(draft("Flaky login test")).unwrap();
+    let second = store.add_ticket(draft("Checkout crash")).unwrap();
+    store.add_label(first, TicketLabel::try_from("ci").unwrap(), &triage()).unwrap();
+    let comment = CommentDraft {
+        body: CommentBody::try_from("Fails once a week").unwrap(),
+        parent_id: None,
+    };
+    store.add_comment(first, comment, &triage()).unwrap();
+    let link = TicketLink {
+        source: second,
+        kind: LinkKind::Blocks,
+        target: first,
+    };
+    store.add_link(link, &triage()).unwrap();
+    let before = store.snapshot();
+    let last_seq = store.events().last_seq();
+
+    // The first ticket is patched, then deleted along with its comment and its link,
+    // before the last operation fails
+    let failed = vec![
+        move_to(first, Status::InProgress),
+        create("Never created"),
+        delete(first),
+        move_to(second, Status::Done),
+    ];
+    let err = store.apply_batch(failed, &triage()).unwrap_err();
+    assert_eq!(err.failures.len(), 1);
+    assert_eq!(store.snapshot(), before);
+    let query = TicketQuery {
+        label: Some(TicketLabel::try_from("ci").unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(store.list(&query).unwrap().tickets.len(), 1);
+    assert_eq!(store.links(second).unwrap().blocks, [first]);
+    let search = SearchQuery::try_from("never").unwrap();
+    assert!(store.search(&search, None).is_empty());
+    // Nothing was announced
+    assert_eq!(store.events().last_seq(), last_seq);
+    assert_eq!(store.add_ticket(draft("Next")).unwrap(), TicketId(2));
+}
+
+#[tokio::test]
+async fn test_batch_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+    let id = client.create(&draft("Flaky login test")).await.unwrap();
+
+    let outcomes = client
+        .apply_batch(vec![create("Checkout crash"), move_to(id, Status::InProgress)])
+        .await
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(client.get(id).await.unwrap().status, Status::InProgress);
+
+    let err = client
+        .apply_batch(vec![delete(id), move_to(id, Status::Done), move_to(TicketId(1), Status::Done)])
+        .await
+        .unwrap_err();
+    let ClientError::Api { status, body } = err else {
+        panic!("expected an API error, got {:?}", err);
+    };
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body.code, "batch_failed");
+    assert_eq!(body.message, "2 of the 3 operations of the batch failed: none was applied");
+    let failures: Vec<(usize, &str)> = body
+        .operations
+        .iter()
+        .map(|failure| (failure.index, failure.code.as_str()))
+        .collect();
+    assert_eq!(failures, [(1, "not_found"), (2, "invalid_transition")]);
+    assert!(client.get(id).await.is_ok());
+
+    let operations: Vec<serde_json::Value> = (0..501)
+        .map(|_| serde_json::json!({"op": "delete", "id": 0}))
+        .collect();
+    let response = reqwest::Client::new()
+        .post(format!("{}/tickets/batch", url))
+        .json(&serde_json::json!({ "operations": operations }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    server.shutdown().await.unwrap();
+}
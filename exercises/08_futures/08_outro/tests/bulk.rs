@@ -0,0 +1,389 @@
+use reqwest::StatusCode;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::{CommentBody, TicketDescription, TicketLabel, TicketTitle};
+
+use outro_08::bulk::{ExportParams, Format, IdPolicy, ImportParams, ImportReport, LineError};
+use outro_08::client::TicketApiClient;
+use outro_08::comment::{CommentDraft, CommentId, TicketComment};
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch};
+use outro_08::history::Actor;
+use outro_08::link::{LinkKind, TicketLink};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+use outro_08::user::{User, UserId, UserName};
+
+fn draft(title: &str, description: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from(description).unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn user(id: &str) -> User {
+    User {
+        id: UserId::try_from(id).unwrap(),
+        name: UserName::try_from(id).unwrap(),
+    }
+}
+
+fn export(store: &TicketStore, format: Format) -> String {
+    let mut out = Vec::new();
+    store.export(format, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// Two tickets: the second one with text that has to be quoted in CSV.
+// The first one blocks the second, and has a thread of two comments.
+fn store_with_tickets() -> TicketStore {
+    let mut store = TicketStore::new();
+    store.add_user(user("alice")).unwrap();
+    let first = store
+        .add_ticket(TicketDraft {
+            assignee: Some(UserId::try_from("alice").unwrap()),
+            priority: Priority::P0,
+            due_date: Some("2026-11-01".parse().unwrap()),
+            ..draft("Login error", "The login page shows an error")
+        })
+        .unwrap();
+    let second = store.add_ticket(draft("Crash, again", "It says \"boom\"\nthen exits")).unwrap();
+    for label in ["backend", "urgent"] {
+        store.add_label(first, TicketLabel::try_from(label).unwrap(), &Actor::system()).unwrap();
+    }
+    store
+        .get_mut(TicketPatch {
+            id: second,
+            title: None,
+            description: None,
+            status: Some(Status::InProgress),
+            assignee: None,
+            reporter: None,
+            priority: None,
+            due_date: None,
+        })
+        .unwrap();
+    store.archive(second, &Actor::system()).unwrap();
+    let comment = |body: &str, parent_id| CommentDraft {
+        body: CommentBody::try_from(body).unwrap(),
+        parent_id,
+    };
+    let question = store.add_comment(first, comment("Still failing?", None), &Actor::new("alice")).unwrap();
+    store
+        .add_comment(first, comment("Yes", Some(question.id)), &Actor::new("bob"))
+        .unwrap();
+    let link = TicketLink {
+        source: first,
+        kind: LinkKind::Blocks,
+        target: second,
+    };
+    store.add_link(link, &Actor::system()).unwrap();
+    store
+}
+
+// What a JSON Lines import brings along with the tickets
+fn comments_and_links(store: &TicketStore) -> (Vec<TicketComment>, Vec<TicketId>) {
+    let comments = store.comments(TicketId(0)).unwrap();
+    (comments, store.links(TicketId(0)).unwrap().blocks)
+}
+
+const CSV: &str = "id,title,description,status,archived,assignee,reporter,labels,priority,due_date
+0,Login error,The login page shows an error,ToDo,false,alice,,\"backend,urgent\",P0,2026-11-01
+1,\"Crash, again\",\"It says \"\"boom\"\"
+then exits\",InProgress,true,,,,P2,
+";
+
+#[test]
+fn test_export() {
+    let store = store_with_tickets();
+    assert_eq!(export(&store, Format::Csv), CSV);
+
+    let jsonl = export(&store, Format::Jsonl);
+    let lines: Vec<serde_json::Value> = jsonl.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 3);
+    // The users come first
+    assert_eq!(lines[0], serde_json::json!({"id": "alice", "name": "alice"}));
+    assert_eq!(lines[1]["labels"], serde_json::json!(["backend", "urgent"]));
+    assert_eq!(lines[1]["comments"][1]["parent_id"], lines[1]["comments"][0]["id"]);
+    assert_eq!(lines[1]["links"], serde_json::json!([{"source": 0, "kind": "blocks", "target": 1}]));
+    assert_eq!(lines[2]["description"], "It says \"boom\"\nthen exits");
+    assert_eq!(lines[2]["archived"], true);
+    assert_eq!(lines[2]["comments"], serde_json::json!([]));
+}
+
+#[test]
+fn test_round_trip() {
+    let store = store_with_tickets();
+    for format in [Format::Jsonl, Format::Csv] {
+        let mut copy = TicketStore::new();
+        copy.add_user(user("alice")).unwrap();
+        let report = copy.import(format, export(&store, format).as_bytes(), IdPolicy::Preserve).unwrap();
+        assert_eq!(report.imported, [TicketId(0), TicketId(1)]);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        // Imported tickets start over at version 1 (which CSV leaves out), without history
+        assert_eq!(export(&copy, Format::Csv), CSV);
+        assert_eq!(copy.get(TicketId(1)).unwrap().read().unwrap().version, 1);
+        assert!(copy.history(TicketId(1)).is_empty());
+        // JSON Lines bring the comments and the links along
+        if format == Format::Jsonl {
+            assert_eq!(comments_and_links(&copy), comments_and_links(&store));
+        } else {
+            assert_eq!(comments_and_links(&copy), (vec![], vec![]));
+        }
+    }
+}
+
+#[test]
+fn test_import_users_comments_and_links() {
+    let mut store = TicketStore::new();
+    store.add_ticket(draft("Existing", "Already there")).unwrap();
+    let jsonl = r#"{"id": "carol", "name": "Carol"}
+{"id": 5, "title": "Parent", "description": "Of 7", "links": [{"source": 5, "kind": "parent_of", "target": 7}, {"source": 5, "kind": "blocks", "target": 42}], "comments": [{"id": 9, "author": "carol", "body": "First", "created_at": 10}, {"id": 10, "parent_id": 9, "body": "Reply"}]}
+{"id": 6, "title": "Orphan", "description": "Comment", "comments": [{"parent_id": 3, "body": "Lost"}]}
+{"id": 7, "title": "Child", "description": "Of 5", "reporter": "carol", "links": [{"source": 7, "kind": "blocks", "target": 7}]}
+{"id": "carol", "name": "Someone else"}
+"#;
+    let report = store.import(Format::Jsonl, jsonl.as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.users, [UserId::try_from("carol").unwrap()]);
+    assert_eq!(report.imported, [TicketId(1), TicketId(2)]);
+    let messages: Vec<(usize, &str)> = report
+        .errors
+        .iter()
+        .map(|error| (error.line, error.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (3, "comments: comment 0: replies to comment 3, which doesn't come before it"),
+            (4, "link 2 blocks 2: Ticket 2 can't be linked to itself"),
+        ]
+    );
+    // A user that already exists is kept as it is
+    assert_eq!(store.get_user(&UserId::try_from("carol").unwrap()).unwrap().name.as_str(), "Carol");
+
+    // The links follow the new ids; the one to a ticket that isn't in the file is dropped
+    let links = store.links(TicketId(1)).unwrap();
+    assert_eq!((links.children, links.blocks), (vec![TicketId(2)], vec![]));
+
+    // The comments get new ids, and keep their thread and author
+    let comments = store.comments(TicketId(1)).unwrap();
+    let summary: Vec<_> = comments
+        .iter()
+        .map(|comment| (comment.id, comment.parent_id, comment.author.as_str(), comment.body.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (CommentId(0), None, "carol", "First"),
+            (CommentId(1), Some(CommentId(0)), "system", "Reply"),
+        ]
+    );
+    assert_eq!(comments[0].created_at, 10);
+    assert!(comments[1].created_at > 10);
+}
+
+#[test]
+fn test_import_report() {
+    let mut store = TicketStore::new();
+    // A quoted cell spans lines 3 and 4: the next record starts on line 5
+    let csv = "title,description,priority,labels
+
+Valid,\"Two
+lines\",P1,\"a,b\"
+,Empty title,P9,
+Missing cell,Only two
+\"Unclosed,Oops,P0,
+";
+    let report = store.import(Format::Csv, csv.as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported, [TicketId(0)]);
+    let messages: Vec<(usize, &str)> = report
+        .errors
+        .iter()
+        .map(|error| (error.line, error.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                5,
+                "title: The title cannot be empty; priority: `P9` is not a valid priority. Use one of: P0, P1, P2, P3"
+            ),
+            (6, "expected 4 cells, found 2"),
+            (7, "the quoted cell is not closed"),
+        ]
+    );
+    let ticket = store.get(TicketId(0)).unwrap().read().unwrap().clone();
+    assert_eq!(ticket.description.as_str(), "Two\nlines");
+    assert_eq!(ticket.priority, Priority::P1);
+    assert_eq!(ticket.labels.len(), 2);
+    assert_eq!(ticket.status, Status::ToDo);
+
+    // A bad header stops the import; JSON Lines are checked line by line
+    let report = store.import(Format::Csv, "title,body\nA,B\n".as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported, []);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].line, 1);
+    let jsonl = b"{\"title\": \"Fine\", \"description\": \"Fine\"}\nnot json\n{\"title\": \"\xff\"}\n";
+    let report = store.import(Format::Jsonl, &jsonl[..], IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported, [TicketId(1)]);
+    assert_eq!(report.errors[0].line, 2);
+    assert_eq!(
+        report.errors[1],
+        LineError {
+            line: 3,
+            message: "the line is not valid UTF-8".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_id_policies() {
+    let mut store = TicketStore::new();
+    store.add_ticket(draft("Existing", "Already there")).unwrap();
+    let jsonl = "{\"id\": 0, \"title\": \"Taken\", \"description\": \"Same id\"}
+{\"id\": 7, \"title\": \"Free\", \"description\": \"New id\"}
+{\"title\": \"No id\", \"description\": \"Needs one\", \"reporter\": \"bob\"}
+";
+    let report = store.import(Format::Jsonl, jsonl.as_bytes(), IdPolicy::Preserve).unwrap();
+    assert_eq!(report.imported, [TicketId(7)]);
+    assert_eq!(
+        report.errors,
+        [
+            LineError {
+                line: 1,
+                message: "Ticket 0 already exists".to_string()
+            },
+            LineError {
+                line: 3,
+                message: "id: missing, while the ids are preserved".to_string()
+            },
+        ]
+    );
+    // The ids handed out afterwards come after the imported ones
+    assert_eq!(store.add_ticket(draft("Next", "After 7")).unwrap(), TicketId(8));
+
+    let report = store.import(Format::Jsonl, jsonl.as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported, [TicketId(9), TicketId(10)]);
+    // Users are checked by the store, once the record is valid
+    assert_eq!(
+        report.errors,
+        [LineError {
+            line: 3,
+            message: "User bob is not registered".to_string()
+        }]
+    );
+}
+
+#[test]
+fn test_persistent_import() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    store.add_user(user("alice")).unwrap();
+    let report = store.import(Format::Csv, CSV.as_bytes(), IdPolicy::Preserve).unwrap();
+    assert_eq!(report.imported, [TicketId(0), TicketId(1)]);
+    let jsonl = export(&store_with_tickets(), Format::Jsonl);
+    let report = store.import(Format::Jsonl, jsonl.as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported, [TicketId(2), TicketId(3)]);
+    drop(store);
+
+    // Replayed from the log, comments and links included
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    let mut out = Vec::new();
+    store.export(Format::Csv, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with(CSV));
+    let comments = store.comments(TicketId(2)).unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[1].parent_id, Some(comments[0].id));
+    assert_eq!(store.links(TicketId(2)).unwrap().blocks, [TicketId(3)]);
+}
+
+#[test]
+fn test_sqlite_import() {
+    let mut store = SqliteTicketStore::open_in_memory().unwrap();
+    store.add_user(user("alice")).unwrap();
+    let report = store.import(Format::Csv, CSV.as_bytes(), IdPolicy::Preserve).unwrap();
+    assert_eq!(report.imported, [TicketId(0), TicketId(1)]);
+    let report = store.import(Format::Csv, CSV.as_bytes(), IdPolicy::Preserve).unwrap();
+    assert_eq!(report.imported, []);
+    assert_eq!(report.errors[1].message, "Ticket 1 already exists");
+
+    let mut out = Vec::new();
+    store.export(Format::Csv, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), CSV);
+    let ticket = store.get(TicketId(0)).unwrap().unwrap();
+    assert_eq!(ticket.labels.len(), 2);
+    assert_eq!(store.add_ticket(draft("Next", "After 1")).unwrap(), TicketId(2));
+
+    // The JSON Lines of the in-memory store, comments and links included
+    let memory = store_with_tickets();
+    let mut store = SqliteTicketStore::open_in_memory().unwrap();
+    let report = store.import(Format::Jsonl, export(&memory, Format::Jsonl).as_bytes(), IdPolicy::Preserve).unwrap();
+    assert_eq!(report.users, [UserId::try_from("alice").unwrap()]);
+    let (comments, blocks) = comments_and_links(&memory);
+    // SQLite numbers the comments on its own, the thread stays the same
+    let imported = store.comments(TicketId(0)).unwrap();
+    let body = |comments: &[TicketComment]| -> Vec<_> {
+        comments.iter().map(|comment| (comment.author.clone(), comment.body.clone(), comment.created_at)).collect()
+    };
+    assert_eq!(body(&imported), body(&comments));
+    assert_eq!(imported[1].parent_id, Some(imported[0].id));
+    assert_eq!(store.links(TicketId(0)).unwrap().blocks, blocks);
+    let mut out = Vec::new();
+    store.export(Format::Jsonl, &mut out).unwrap();
+    let lines: Vec<serde_json::Value> =
+        String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines[1]["comments"].as_array().unwrap().len(), 2);
+    assert_eq!(lines[1]["links"][0]["target"], 1);
+}
+
+#[tokio::test]
+async fn test_bulk_endpoints() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+    client.create_user(&user("alice")).await.unwrap();
+
+    let params = ImportParams {
+        format: Format::Csv,
+        ids: IdPolicy::Preserve,
+    };
+    let report = client.import(&params, CSV).await.unwrap();
+    assert_eq!(
+        report,
+        ImportReport {
+            imported: vec![TicketId(0), TicketId(1)],
+            users: vec![],
+            errors: vec![],
+        }
+    );
+    let exported = client.export(&ExportParams { format: Format::Csv }).await.unwrap();
+    assert_eq!(exported, CSV);
+
+    let response = reqwest::get(format!("{}/tickets/export", url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    // The user, then the tickets
+    assert_eq!(response.text().await.unwrap().lines().count(), 3);
+
+    // By default, ids are remapped and the body is JSON Lines
+    let response = reqwest::Client::new()
+        .post(format!("{}/tickets/import", url))
+        .body("{\"id\": 0, \"title\": \"Copy\", \"description\": \"Of ticket 0\"}\n")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: ImportReport = response.json().await.unwrap();
+    assert_eq!(report.imported, [TicketId(2)]);
+
+    let response = reqwest::get(format!("{}/tickets/export?format=xml", url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    server.shutdown().await.unwrap();
+}
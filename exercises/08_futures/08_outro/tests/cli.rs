@@ -0,0 +1,139 @@
+use std::fs;
+use std::process::Command;
+
+use outro_08::data::{Status, Ticket};
+use outro_08::user::User;
+
+fn ticket_server() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ticket-server"));
+    // Don't pick up the settings of the machine running the tests
+    command.env_remove("TICKET_SERVER_CONFIG");
+    command
+}
+
+// Users are exported before the tickets
+fn read_tickets(path: &std::path::Path) -> Vec<Ticket> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .skip_while(|line| serde_json::from_str::<User>(line).is_ok())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_import_export_compact() {
+    for storage in ["wal", "sqlite"] {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("data");
+        let input = dir.path().join("input.jsonl");
+        let output = dir.path().join("output.jsonl");
+
+        fs::write(
+            &input,
+            concat!(
+                r#"{"id": "alice", "name": "Alice"}"#,
+                "\n",
+                r#"{"title": "A draft", "description": "A description", "assignee": "alice"}"#,
+                "\n",
+                r#"{"id": 7, "title": "A ticket", "description": "A description", "status": "Done", "archived": true, "labels": ["bug", "area/api"], "comments": ["#,
+                r#"{"id": 3, "ticket_id": 7, "parent_id": null, "author": "alice", "body": "Done?", "created_at": 1, "edited_at": null},"#,
+                r#"{"id": 4, "ticket_id": 7, "parent_id": 3, "author": "bob", "body": "Done!", "created_at": 2, "edited_at": null}"#,
+                "]}",
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let status = ticket_server()
+            .args(["--storage", storage, "--data-dir"])
+            .arg(&data_dir)
+            .arg("import")
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let status = ticket_server()
+            .args(["--storage", storage, "compact", "--data-dir"])
+            .arg(&data_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let status = ticket_server()
+            .args(["--storage", storage, "--data-dir"])
+            .arg(&data_dir)
+            .arg("export")
+            .arg(&output)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let tickets = read_tickets(&output);
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].title.as_str(), "A draft");
+        assert_eq!(tickets[0].status, Status::ToDo);
+        assert_eq!(tickets[0].assignee.as_ref().map(|user| user.as_str()), Some("alice"));
+        // Imported tickets get new ids, but keep their status
+        assert_eq!(tickets[1].id.0, 1);
+        assert_eq!(tickets[1].status, Status::Done);
+        assert!(tickets[1].archived);
+        let labels: Vec<_> = tickets[1].labels.iter().map(|label| label.as_str()).collect();
+        assert_eq!(labels, ["area/api", "bug"]);
+
+        // Comments are exported along with their ticket, and keep their thread
+        let exported: serde_json::Value =
+            serde_json::from_str(fs::read_to_string(&output).unwrap().lines().nth(2).unwrap()).unwrap();
+        let comments = exported["comments"].as_array().unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!((&comments[0]["author"], &comments[0]["body"]), (&"alice".into(), &"Done?".into()));
+        assert_eq!(comments[1]["parent_id"], comments[0]["id"]);
+        assert_eq!(comments[1]["author"], "bob");
+
+        let exported = fs::read_to_string(&output).unwrap();
+        let user: User = serde_json::from_str(exported.lines().next().unwrap()).unwrap();
+        assert_eq!(user.name.as_str(), "Alice");
+    }
+}
+
+#[test]
+fn test_data_dir_from_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output.jsonl");
+
+    let status = ticket_server()
+        .env("TICKET_SERVER_DATA_DIR", dir.path().join("data"))
+        .arg("export")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(read_tickets(&output).is_empty());
+}
+
+#[test]
+fn test_errors() {
+    // Without a data directory, there is nothing to export
+    let output = ticket_server()
+        .env_remove("TICKET_SERVER_DATA_DIR")
+        .arg("export")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a data directory"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.jsonl");
+    fs::write(&input, "{\"title\": \"\", \"description\": \"A description\"}\n").unwrap();
+
+    let output = ticket_server()
+        .arg("--data-dir")
+        .arg(dir.path().join("data"))
+        .arg("import")
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 1"));
+}
@@ -0,0 +1,148 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Status, Ticket, TicketDraft};
+use outro_08::error::ErrorBody;
+use outro_08::retry::RetryPolicy;
+use outro_08::store::TicketId;
+
+// A stand-in for the ticket server, mounted under `/api`, that fails the first
+// `failures` requests with a 503. `calls` counts the requests it received.
+async fn flaky_server(failures: usize, calls: Arc<AtomicUsize>) -> SocketAddr {
+    let get_calls = calls.clone();
+    let get_ticket = move |Path(id): Path<u64>| async move {
+        if get_calls.fetch_add(1, Ordering::SeqCst) < failures {
+            return Err(unavailable());
+        }
+        Ok(Json(Ticket {
+            id: TicketId(id),
+            title: ticket_title(),
+            description: ticket_description(),
+            status: Status::ToDo,
+            archived: false,
+            version: 1,
+            assignee: None,
+            reporter: None,
+            labels: Default::default(),
+            priority: Default::default(),
+            due_date: None,
+        }))
+    };
+    let create_ticket = move |Json(_): Json<TicketDraft>| async move {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err::<Json<TicketId>, _>(unavailable())
+    };
+
+    let api = Router::new()
+        .route("/tickets", post(create_ticket))
+        .route("/tickets/:id", get(get_ticket));
+    let app = Router::new().nest("/api", api);
+
+    let server = axum::Server::bind(&([127, 0, 0, 1], 0).into()).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+fn unavailable() -> (StatusCode, Json<ErrorBody>) {
+    let body = ErrorBody {
+        code: "unavailable".to_string(),
+        message: "Try again later".to_string(),
+        fields: Vec::new(),
+        operations: Vec::new(),
+    };
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+}
+
+fn fast_retries(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+    }
+}
+
+#[tokio::test]
+async fn test_get_is_retried_on_server_errors() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let addr = flaky_server(2, calls.clone()).await;
+    let client = TicketApiClient::new(format!("http://{}", addr))
+        .base_path("/api/")
+        .retry(fast_retries(3));
+
+    let ticket = client.get(TicketId(7)).await.unwrap();
+    assert_eq!(ticket.id, TicketId(7));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retries_give_up_with_the_last_error() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let addr = flaky_server(10, calls.clone()).await;
+    let client = TicketApiClient::new(format!("http://{}", addr))
+        .base_path("api")
+        .retry(fast_retries(2));
+
+    let error = client.get(TicketId(7)).await.unwrap_err();
+    assert_eq!(error.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(error.code(), Some("unavailable"));
+    // The first attempt, then 2 retries
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_create_is_not_retried() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let addr = flaky_server(10, calls.clone()).await;
+    let client = TicketApiClient::new(format!("http://{}", addr))
+        .base_path("/api")
+        .retry(fast_retries(3));
+
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    };
+    let error = client.create(&draft).await.unwrap_err();
+    assert_eq!(error.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_without_base_path_routes_are_not_found() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let addr = flaky_server(0, calls.clone()).await;
+    let client = TicketApiClient::new(format!("http://{}/", addr));
+
+    let error = client.get(TicketId(7)).await.unwrap_err();
+    assert!(error.is_not_found());
+    // The router's 404 has no JSON body
+    assert_eq!(error.code(), Some("unknown"));
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_backoff_doubles_up_to_the_maximum() {
+    let policy = RetryPolicy {
+        max_retries: 10,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.backoff(4), Duration::from_millis(500));
+    assert_eq!(policy.backoff(40), Duration::from_millis(500));
+}
@@ -0,0 +1,269 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::CommentBody;
+
+use outro_08::client::TicketApiClient;
+use outro_08::clock::Clock;
+use outro_08::comment::{CommentDraft, CommentEdit, CommentId};
+use outro_08::data::TicketDraft;
+use outro_08::history::Actor;
+use outro_08::persistent::{PersistenceError, PersistentTicketStore};
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::{SqliteError, SqliteTicketStore};
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn body(text: &str) -> CommentBody {
+    text.try_into().unwrap()
+}
+
+fn comment(text: &str, parent_id: Option<CommentId>) -> CommentDraft {
+    CommentDraft {
+        body: body(text),
+        parent_id,
+    }
+}
+
+#[test]
+fn test_threads() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft()).unwrap();
+    let other = store.add_ticket(draft()).unwrap();
+    let alice = Actor::new("alice");
+    let bob = Actor::new("bob");
+
+    let question = store.add_comment(id, comment("Any news?", None), &alice).unwrap();
+    let answer = store.add_comment(id, comment("Almost done", Some(question.id)), &bob).unwrap();
+    let thanks = store.add_comment(id, comment("Thanks", Some(answer.id)), &alice).unwrap();
+    assert_eq!(question.author, alice);
+    assert_eq!(answer.parent_id, Some(question.id));
+    assert_eq!(store.comments(id).unwrap(), [question.clone(), answer.clone(), thanks.clone()]);
+    assert!(store.comments(other).unwrap().is_empty());
+
+    // Replies stay on the ticket of their parent
+    assert_eq!(
+        store.add_comment(other, comment("Me too", Some(question.id)), &bob),
+        Err(TicketStoreError::UnknownParent {
+            ticket_id: other,
+            parent_id: question.id
+        })
+    );
+
+    let edited = store.edit_comment(id, answer.id, body("Done"), &bob).unwrap();
+    assert_eq!(edited.body, body("Done"));
+    assert!(edited.edited_at.is_some());
+
+    // The replies of a deleted comment move up the thread
+    let deleted = store.delete_comment(id, answer.id, &bob).unwrap();
+    assert_eq!(deleted, edited);
+    let comments = store.comments(id).unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[1].parent_id, Some(question.id));
+    assert_eq!(
+        store.delete_comment(id, answer.id, &bob),
+        Err(TicketStoreError::CommentNotFound {
+            ticket_id: id,
+            comment_id: answer.id
+        })
+    );
+
+    // Comments are removed with their ticket
+    store.remove(id).unwrap();
+    assert_eq!(store.comments(id), Err(TicketStoreError::NotFound(id)));
+    assert!(store.add_comment(id, comment("Hello?", None), &bob).is_err());
+}
+
+#[test]
+fn test_comments_are_in_the_history() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft()).unwrap();
+    let alice = Actor::new("alice");
+
+    let added = store.add_comment(id, comment("First", None), &alice).unwrap();
+    // Editing a comment without changing it isn't recorded
+    store.edit_comment(id, added.id, body("First"), &alice).unwrap();
+    store.edit_comment(id, added.id, body("Second"), &alice).unwrap();
+    store.delete_comment(id, added.id, &alice).unwrap();
+
+    let field = format!("comments/{}", added.id);
+    let changes: Vec<_> = store
+        .history(id)
+        .iter()
+        .map(|entry| {
+            assert_eq!((entry.version, &entry.actor), (1, &alice));
+            assert_eq!(entry.changes[0].field, field);
+            (entry.changes[0].before.clone(), entry.changes[0].after.clone())
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (Value::Null, "First".into()),
+            ("First".into(), "Second".into()),
+            ("Second".into(), Value::Null),
+        ]
+    );
+    // Comments don't change the version of the ticket
+    assert_eq!(store.get(id).unwrap().read().unwrap().version, 1);
+}
+
+#[test]
+fn test_persistent_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let alice = Actor::new("alice");
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(3);
+    let id = store.add_ticket(draft()).unwrap();
+    let first = store.add_comment(id, comment("First", None), &alice).unwrap();
+    // Compacted here: the next operations are only in the log
+    let reply = store.add_comment(id, comment("Reply", Some(first.id)), &alice).unwrap();
+    store.edit_comment(id, reply.id, body("Edited"), &alice).unwrap();
+    store.add_comment(id, comment("Last", None), &alice).unwrap();
+    store.delete_comment(id, first.id, &alice).unwrap();
+    let comments = store.comments(id).unwrap();
+    let history = store.history(id);
+    drop(store);
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.comments(id).unwrap(), comments);
+    assert_eq!(store.history(id), history);
+    // Ids of deleted comments are not reused
+    let next = store.add_comment(id, comment("Next", None), &alice).unwrap();
+    assert!(next.id > comments[1].id);
+    assert!(matches!(
+        store.edit_comment(id, first.id, body("Nope"), &alice),
+        Err(PersistenceError::Store(TicketStoreError::CommentNotFound { .. }))
+    ));
+}
+
+#[test]
+fn test_sqlite_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+    let alice = Actor::new("alice");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+    let other = store.add_ticket(draft()).unwrap();
+    let first = store.add_comment(id, comment("First", None), &alice).unwrap();
+    let reply = store.add_comment(id, comment("Reply", Some(first.id)), &alice).unwrap();
+    let last = store.add_comment(id, comment("Last", None), &alice).unwrap();
+    assert!(matches!(
+        store.add_comment(other, comment("Elsewhere", Some(first.id)), &alice),
+        Err(SqliteError::Store(TicketStoreError::UnknownParent { .. }))
+    ));
+    let edited = store.edit_comment(id, reply.id, body("Edited"), &alice).unwrap();
+    store.delete_comment(id, first.id, &alice).unwrap();
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let comments = store.comments(id).unwrap();
+    assert_eq!(
+        comments,
+        [
+            outro_08::comment::TicketComment {
+                parent_id: None,
+                ..edited
+            },
+            last.clone()
+        ]
+    );
+    assert_eq!(store.history(id).unwrap().len(), 5);
+    assert!(matches!(
+        store.delete_comment(other, last.id, &alice),
+        Err(SqliteError::Store(TicketStoreError::CommentNotFound { .. }))
+    ));
+
+    store.remove(id).unwrap();
+    assert!(matches!(store.comments(id), Err(SqliteError::Store(TicketStoreError::NotFound(_)))));
+}
+
+// A clock that moves forward every time it is read
+struct Ticking(AtomicU64);
+
+impl Clock for Ticking {
+    fn now_millis(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn test_sqlite_comment_timestamps() {
+    let mut store = SqliteTicketStore::open_in_memory()
+        .unwrap()
+        .with_clock(Ticking(AtomicU64::new(1)));
+    let id = store.add_ticket(draft()).unwrap();
+    let alice = Actor::new("alice");
+
+    // The comment and its history entry are stamped with the same time
+    let added = store.add_comment(id, comment("First", None), &alice).unwrap();
+    let edited = store.edit_comment(id, added.id, body("Edited"), &alice).unwrap();
+    let history = store.history(id).unwrap();
+    assert_eq!(history[0].timestamp, added.created_at);
+    assert_eq!(Some(history[1].timestamp), edited.edited_at);
+}
+
+#[tokio::test]
+async fn test_comment_endpoints() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let alice = TicketApiClient::new(url.clone()).actor(Actor::new("alice"));
+    let bob = TicketApiClient::new(url.clone()).actor(Actor::new("bob"));
+
+    let id = alice.create(&draft()).await.unwrap();
+    let question = alice.add_comment(id, &comment("Any news?", None)).await.unwrap();
+    let answer = bob.add_comment(id, &comment("Almost", Some(question.id))).await.unwrap();
+    assert_eq!(answer.author, Actor::new("bob"));
+    assert_eq!(answer.parent_id, Some(question.id));
+
+    let edit = CommentEdit { body: body("Done") };
+    let edited = bob.edit_comment(id, answer.id, &edit).await.unwrap();
+    assert_eq!(alice.comments(id).await.unwrap(), [question.clone(), edited]);
+
+    bob.delete_comment(id, answer.id).await.unwrap();
+    assert_eq!(alice.comments(id).await.unwrap(), [question]);
+    let actors: Vec<_> = alice
+        .history(id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.actor.as_str().to_string())
+        .collect();
+    assert_eq!(actors, ["alice", "bob", "bob", "bob"]);
+
+    // Unknown tickets and comments
+    let error = bob.delete_comment(id, answer.id).await.unwrap_err();
+    assert!(error.is_not_found());
+    assert_eq!(error.to_string(), format!("Comment {} not found on ticket {} (404 Not Found)", answer.id, id));
+    assert!(alice.comments(TicketId(42)).await.unwrap_err().is_not_found());
+    let error = alice.add_comment(TicketId(42), &comment("Hello?", None)).await.unwrap_err();
+    assert!(error.is_not_found());
+
+    // Invalid comments
+    let error = alice.add_comment(id, &comment("Reply", Some(CommentId(42)))).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    let response = reqwest::Client::new()
+        .post(format!("{}/tickets/{}/comments", url, id))
+        .json(&serde_json::json!({"body": ""}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use outro_08::config::{ConfigError, StorageKind, TicketServerConfig};
+
+#[test]
+fn test_defaults() {
+    let config = TicketServerConfig::from_toml("").unwrap();
+    assert_eq!(config, TicketServerConfig::default());
+    assert_eq!(config.bind.to_string(), "127.0.0.1:3000");
+    assert_eq!(config.data_dir, None);
+    assert_eq!(config.storage, StorageKind::Wal);
+}
+
+#[test]
+fn test_from_toml() {
+    let config = TicketServerConfig::from_toml(
+        r#"
+        bind = "0.0.0.0:8080"
+        data_dir = "/var/lib/tickets"
+        storage = "sqlite"
+        request_timeout_secs = 10
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.bind.to_string(), "0.0.0.0:8080");
+    assert_eq!(config.data_dir, Some(PathBuf::from("/var/lib/tickets")));
+    assert_eq!(config.storage, StorageKind::Sqlite);
+    assert_eq!(config.request_timeout_secs, 10);
+    assert_eq!(config.server_config().bind_addr().to_string(), "0.0.0.0:8080");
+
+    // Typos are reported instead of being silently ignored
+    assert!(matches!(
+        TicketServerConfig::from_toml("data_directory = \"/tmp\""),
+        Err(ConfigError::Parse(_))
+    ));
+}
+
+#[test]
+fn test_env_overrides() {
+    let env: HashMap<&str, &str> = [
+        ("TICKET_SERVER_BIND", "127.0.0.1:0"),
+        ("TICKET_SERVER_STORAGE", "SQLite"),
+        ("TICKET_SERVER_BODY_LIMIT", "1024"),
+    ]
+    .into_iter()
+    .collect();
+
+    // Env var overrides config
+    let config = TicketServerConfig::from_toml("bind = \"0.0.0.0:8080\"\ndata_dir = \"/tmp/tickets\"")
+        .unwrap()
+        .with_env_overrides(|name| env.get(name).map(|value| value.to_string()))
+        .unwrap();
+
+    assert_eq!(config.bind.to_string(), "127.0.0.1:0");
+    assert_eq!(config.storage, StorageKind::Sqlite);
+    assert_eq!(config.body_limit, 1024);
+    assert_eq!(config.data_dir, Some(PathBuf::from("/tmp/tickets")));
+
+    let err = TicketServerConfig::default()
+        .with_env_overrides(|name| (name == "TICKET_SERVER_BIND").then(|| "nowhere".to_string()))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Invalid value `nowhere` for `bind`");
+}
@@ -0,0 +1,317 @@
+use futures_util::{SinkExt, StreamExt};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+use ticket_fields::{TicketDescription, TicketLabel, TicketTitle};
+
+use outro_08::batch::BatchOperation;
+use outro_08::bulk::{Format, IdPolicy};
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch};
+use outro_08::events::{
+    EventBus, EventFilter, EventsParams, ResumeError, SequencedEvent, Subscription, TicketEvent,
+};
+use outro_08::history::Actor;
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::repository::TicketRepository;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("Found while triaging").unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn move_to(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn label(label: &str) -> TicketLabel {
+    TicketLabel::try_from(label).unwrap()
+}
+
+// Fails the test instead of hanging if the event never comes
+async fn within<T>(future: impl Future<Output = T>) -> T {
+    tokio::time::timeout(Duration::from_secs(5), future)
+        .await
+        .expect("timed out")
+}
+
+async fn next(subscription: &mut Subscription) -> SequencedEvent {
+    within(subscription.next()).await.expect("the subscription ended")
+}
+
+// Sequence number, kind and ticket of an event
+fn summary(event: &SequencedEvent) -> (u64, &'static str, TicketId) {
+    (event.seq, event.event.kind(), event.event.ticket().id)
+}
+
+// The same scenario on every backend: what a subscriber sees of the changes made through the repository
+async fn check_events<R: TicketRepository>(repository: &R) {
+    let bus = repository.events().await.unwrap();
+    let mut subscription = bus.subscribe(None, EventFilter::default()).unwrap();
+
+    let id = repository.insert(draft("Flaky login test")).await.unwrap();
+    repository
+        .patch(move_to(id, Status::InProgress), Actor::new("alice"))
+        .await
+        .unwrap();
+    // A patch that changes nothing is not announced, and keeps the version
+    let ticket = repository
+        .patch(move_to(id, Status::InProgress), Actor::new("alice"))
+        .await
+        .unwrap();
+    assert_eq!(ticket.version, 2);
+    repository.add_label(id, label("backend"), Actor::new("alice")).await.unwrap();
+    repository.archive(id, Actor::system()).await.unwrap();
+    repository.delete(id).await.unwrap();
+    // A failed change is not announced
+    assert!(repository.patch(move_to(id, Status::Done), Actor::system()).await.is_err());
+    let batch = vec![
+        BatchOperation::Create {
+            draft: draft("Checkout crash"),
+        },
+        BatchOperation::Delete { id: TicketId(99) },
+    ];
+    assert!(repository.apply_batch(batch, Actor::system()).await.is_err());
+    let batch = vec![BatchOperation::Create {
+        draft: draft("Update the docs"),
+    }];
+    repository.apply_batch(batch, Actor::system()).await.unwrap();
+
+    let mut events = Vec::new();
+    for _ in 0..6 {
+        events.push(next(&mut subscription).await);
+    }
+    let summaries: Vec<_> = events.iter().map(summary).collect();
+    let docs = TicketId(id.0 + 1);
+    assert_eq!(
+        summaries,
+        [
+            (1, "created", id),
+            (2, "patched", id),
+            (3, "patched", id),
+            (4, "patched", id),
+            (5, "deleted", id),
+            (6, "created", docs),
+        ]
+    );
+    let TicketEvent::Patched { ticket, changes } = &events[1].event else {
+        unreachable!()
+    };
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].field, "status");
+    assert_eq!(changes[0].before, "ToDo");
+    let TicketEvent::Patched { changes, .. } = &events[3].event else {
+        unreachable!()
+    };
+    assert_eq!(changes[0].field, "archived");
+    assert_eq!(events[4].event.ticket().version, 4);
+    assert_eq!(bus.last_seq(), 6);
+}
+
+#[tokio::test]
+async fn test_memory_events() {
+    check_events(&Arc::new(RwLock::new(TicketStore::new()))).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_events() {
+    check_events(&Arc::new(Mutex::new(SqliteTicketStore::open_in_memory().unwrap()))).await;
+}
+
+#[tokio::test]
+async fn test_persistent_events() {
+    let dir = tempfile::tempdir().unwrap();
+    check_events(&Arc::new(RwLock::new(PersistentTicketStore::open(dir.path()).unwrap()))).await;
+
+    // The changes replayed from the log aren't announced again
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.events().last_seq(), 0);
+    let mut subscription = store.events().subscribe(None, EventFilter::default()).unwrap();
+    let id = store.add_ticket(draft("After the restart")).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (1, "created", id));
+}
+
+#[tokio::test]
+async fn test_imports_are_announced() {
+    let mut store = TicketStore::new();
+    let mut subscription = store.events().subscribe(None, EventFilter::default()).unwrap();
+    let jsonl = concat!(
+        "{\"title\": \"First\", \"description\": \"Imported\"}\n",
+        "not json\n",
+        "{\"title\": \"Second\", \"description\": \"Imported\"}\n",
+    );
+    let report = store.import(Format::Jsonl, jsonl.as_bytes(), IdPolicy::Remap).unwrap();
+    assert_eq!(report.imported.len(), 2);
+    assert_eq!(summary(&next(&mut subscription).await), (1, "created", TicketId(0)));
+    assert_eq!(summary(&next(&mut subscription).await), (2, "created", TicketId(1)));
+}
+
+#[tokio::test]
+async fn test_resume() {
+    let bus = EventBus::with_capacity(2);
+    let mut store = TicketStore::new().with_events(bus.clone());
+    let ids: Vec<TicketId> = ["One", "Two", "Three"]
+        .into_iter()
+        .map(|title| store.add_ticket(draft(title)).unwrap())
+        .collect();
+
+    // Only the last two events are kept
+    let mut subscription = bus.subscribe(Some(1), EventFilter::default()).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (2, "created", ids[1]));
+    assert_eq!(summary(&next(&mut subscription).await), (3, "created", ids[2]));
+    assert_eq!(
+        bus.subscribe(Some(0), EventFilter::default()).unwrap_err(),
+        ResumeError::Expired { since: 0, oldest: 2 }
+    );
+    assert_eq!(
+        bus.subscribe(Some(7), EventFilter::default()).unwrap_err(),
+        ResumeError::Unknown { since: 7, last: 3 }
+    );
+
+    // Up to date: the next event is the next change
+    let mut subscription = bus.subscribe(Some(3), EventFilter::default()).unwrap();
+    store.remove(ids[0]).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (4, "deleted", ids[0]));
+
+    // A subscriber that falls too far behind is disconnected, and has to resume
+    let mut late = bus.subscribe(None, EventFilter::default()).unwrap();
+    for title in ["Four", "Five", "Six"] {
+        store.add_ticket(draft(title)).unwrap();
+    }
+    assert_eq!(within(late.next()).await, None);
+}
+
+#[tokio::test]
+async fn test_filters() {
+    let mut store = TicketStore::new();
+    let in_progress = EventFilter {
+        status: Some(Status::InProgress),
+        label: None,
+    };
+    let mut subscription = store.events().subscribe(None, in_progress).unwrap();
+    let first = store.add_ticket(draft("Flaky login test")).unwrap();
+    let second = store.add_ticket(draft("Checkout crash")).unwrap();
+    store.get_mut(move_to(second, Status::InProgress)).unwrap();
+    store.add_label(first, label("backend"), &Actor::system()).unwrap();
+    // Leaving the status is announced too
+    store.get_mut(move_to(second, Status::Done)).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (3, "patched", second));
+    assert_eq!(summary(&next(&mut subscription).await), (5, "patched", second));
+
+    let backend = EventFilter {
+        status: None,
+        label: Some(label("backend")),
+    };
+    let mut subscription = store.events().subscribe(Some(0), backend).unwrap();
+    store.remove_label(first, &label("backend"), &Actor::system()).unwrap();
+    store.add_label(second, label("frontend"), &Actor::system()).unwrap();
+    store.remove(first).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (4, "patched", first));
+    assert_eq!(summary(&next(&mut subscription).await), (6, "patched", first));
+    // Nothing more: the ticket had lost its label when it was removed
+    store.add_label(second, label("backend"), &Actor::system()).unwrap();
+    assert_eq!(summary(&next(&mut subscription).await), (9, "patched", second));
+}
+
+#[tokio::test]
+async fn test_events_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let params = EventsParams {
+        status: Some(Status::InProgress),
+        ..EventsParams::default()
+    };
+    let mut events = client.events(&params).await.unwrap();
+    let first = client.create(&draft("Flaky login test")).await.unwrap();
+    let second = client.create(&draft("Checkout crash")).await.unwrap();
+    client.patch(&move_to(second, Status::InProgress)).await.unwrap();
+    let event = within(events.next()).await.unwrap().unwrap();
+    assert_eq!(summary(&event), (3, "patched", second));
+    assert_eq!(event.event.ticket().status, Status::InProgress);
+
+    // Browsers resume with the `Last-Event-ID` header
+    let mut response = reqwest::Client::new()
+        .get(format!("{}/tickets/events", url))
+        .header("last-event-id", "1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let chunk = within(response.chunk()).await.unwrap().unwrap();
+    let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+    assert!(chunk.contains("id:2\n"), "{}", chunk);
+    assert!(chunk.contains("event:created\n"), "{}", chunk);
+    drop(response);
+
+    let since = EventsParams {
+        since: Some(first.0 + 10),
+        ..EventsParams::default()
+    };
+    let err = client.events(&since).await.err().unwrap();
+    assert_eq!(err.status(), Some(StatusCode::GONE));
+    assert_eq!(err.code(), Some("gone"));
+
+    // Shutting down ends the streams, instead of waiting for them
+    let shutdown = tokio::spawn(server.shutdown());
+    assert!(within(events.next()).await.unwrap().is_none());
+    within(shutdown).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_websocket_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    let id = client.create(&draft("Flaky login test")).await.unwrap();
+
+    let url = format!("ws://{}/tickets/events/ws?since=0&label=backend", server.addr());
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    client.add_label(id, &label("frontend")).await.unwrap();
+    client.add_label(id, &label("backend")).await.unwrap();
+    // The messages of the client are ignored
+    socket.send(Message::Text("hello".to_string())).await.unwrap();
+    let Message::Text(text) = within(socket.next()).await.unwrap().unwrap() else {
+        panic!("expected a text message");
+    };
+    let event: SequencedEvent = serde_json::from_str(&text).unwrap();
+    assert_eq!(summary(&event), (3, "patched", id));
+    assert!(event.event.ticket().labels.contains(&label("backend")));
+
+    // Events that can't be resumed are refused before upgrading
+    let url = format!("ws://{}/tickets/events/ws?since=42", server.addr());
+    let err = tokio_tungstenite::connect_async(url).await.unwrap_err();
+    assert!(err.to_string().contains("410"), "{}", err);
+
+    let shutdown = tokio::spawn(server.shutdown());
+    let Message::Close(Some(frame)) = within(socket.next()).await.unwrap().unwrap() else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(u16::from(frame.code), 1001);
+    within(shutdown).await.unwrap().unwrap();
+}
@@ -0,0 +1,323 @@
+use reqwest::StatusCode;
+use std::sync::{Arc, Mutex, RwLock};
+
+use ticket_fields::test_helpers::ticket_description;
+use ticket_fields::{TicketLabel, TicketTitle};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::error::ErrorBody;
+use outro_08::filter::{Condition, Filter, ParseFilterError, ParseFilterErrorKind};
+use outro_08::history::Actor;
+use outro_08::repository::TicketRepository;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+use outro_08::user::{User, UserId, UserName};
+
+fn filter(filter: &str) -> Filter {
+    Filter::try_from(filter).unwrap()
+}
+
+fn condition(condition: Condition) -> Box<Filter> {
+    Box::new(Filter::Condition(condition))
+}
+
+fn label(label: &str) -> TicketLabel {
+    TicketLabel::try_from(label).unwrap()
+}
+
+fn user(id: &str) -> UserId {
+    UserId::try_from(id).unwrap()
+}
+
+fn draft(title: &str, assignee: Option<&str>, priority: Priority) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: ticket_description(),
+        assignee: assignee.map(user),
+        reporter: None,
+        priority,
+        due_date: None,
+    }
+}
+
+fn move_to(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn filtered(q: &str) -> TicketQuery {
+    TicketQuery {
+        q: Some(filter(q)),
+        ..Default::default()
+    }
+}
+
+// The same tickets in every store:
+//  0. "Flaky login test", InProgress, backend, alice, P1
+//  1. "Checkout crash", InProgress, backend, unassigned, P0
+//  2. "Update the docs of the Éditeur", ToDo, docs, bob, P2
+//  3. "Flaky deploy", Done, backend, alice, P2
+//  4. "Old login page", ToDo, archived, unassigned, P3
+async fn add_tickets<R: TicketRepository>(repository: &R) -> Vec<TicketId> {
+    for id in ["alice", "bob"] {
+        let user = User {
+            id: user(id),
+            name: UserName::try_from(id).unwrap(),
+        };
+        repository.insert_user(user).await.unwrap();
+    }
+    let drafts = [
+        (draft("Flaky login test", Some("alice"), Priority::P1), "backend"),
+        (draft("Checkout crash", None, Priority::P0), "backend"),
+        (draft("Update the docs of the Éditeur", Some("bob"), Priority::P2), "docs"),
+        (draft("Flaky deploy", Some("alice"), Priority::P2), "backend"),
+        (draft("Old login page", None, Priority::P3), "frontend"),
+    ];
+    let mut ids = Vec::new();
+    for (draft, ticket_label) in drafts {
+        let id = repository.insert(draft).await.unwrap();
+        repository.add_label(id, label(ticket_label), Actor::system()).await.unwrap();
+        ids.push(id);
+    }
+    for (id, status) in [(0, Status::InProgress), (1, Status::InProgress), (3, Status::InProgress), (3, Status::Done)] {
+        repository.patch(move_to(ids[id], status), Actor::system()).await.unwrap();
+    }
+    repository.archive(ids[4], Actor::system()).await.unwrap();
+    ids
+}
+
+// The positions, in `add_tickets`, of the tickets listed with the query
+async fn listed<R: TicketRepository>(repository: &R, ids: &[TicketId], query: TicketQuery) -> Vec<usize> {
+    let page = repository.list(query).await.unwrap();
+    page.tickets
+        .iter()
+        .map(|ticket| ids.iter().position(|id| *id == ticket.id).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_parse_filters() {
+    assert_eq!(
+        filter(r#"status:InProgress AND label:backend AND NOT title~"flaky""#),
+        Filter::And(
+            Box::new(Filter::And(
+                condition(Condition::Status(Status::InProgress)),
+                condition(Condition::Label(label("backend")))
+            )),
+            Box::new(Filter::Not(condition(Condition::TitleContains("flaky".to_string()))))
+        )
+    );
+    // NOT binds tighter than AND, which binds tighter than OR; keywords are case-insensitive
+    assert_eq!(
+        filter("priority:p0 or priority:P1 and not archived:true"),
+        Filter::Or(
+            condition(Condition::Priority(Priority::P0)),
+            Box::new(Filter::And(
+                condition(Condition::Priority(Priority::P1)),
+                Box::new(Filter::Not(condition(Condition::Archived(true))))
+            ))
+        )
+    );
+    assert_eq!(
+        filter("(assignee:alice OR reporter:bob) AND description~\"say \\\"hi\\\"\""),
+        Filter::And(
+            Box::new(Filter::Or(
+                condition(Condition::Assignee(user("alice"))),
+                condition(Condition::Reporter(user("bob")))
+            )),
+            condition(Condition::DescriptionContains("say \"hi\"".to_string()))
+        )
+    );
+
+    // The display of a filter parses back into the same filter
+    for text in [
+        "status:Done",
+        "NOT NOT label:\"area:billing\"",
+        "title~\"a (quoted) \\\\ title\" OR (status:ToDo AND priority:P3) OR archived:false",
+    ] {
+        let parsed = filter(text);
+        assert_eq!(filter(&parsed.to_string()), parsed, "{}", parsed);
+    }
+    assert_eq!(
+        filter("status:todo or title~\"two words\"").to_string(),
+        "(status:ToDo OR title~\"two words\")"
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    let cases = [
+        ("", 0, ParseFilterErrorKind::Empty),
+        ("status:", 7, ParseFilterErrorKind::UnexpectedEnd),
+        ("status:Done AND", 15, ParseFilterErrorKind::UnexpectedEnd),
+        ("(status:Done", 12, ParseFilterErrorKind::UnexpectedEnd),
+        ("title~\"flaky", 6, ParseFilterErrorKind::UnterminatedString),
+        ("owner:alice", 0, ParseFilterErrorKind::UnknownField("owner".to_string())),
+        (
+            "status:Done AND title:flaky",
+            16,
+            ParseFilterErrorKind::UnsupportedOperator {
+                field: "title".to_string(),
+                operator: ':',
+            },
+        ),
+        (
+            "status:Done label:bug",
+            12,
+            ParseFilterErrorKind::Unexpected {
+                expected: "AND, OR or the end of the filter",
+                found: "label".to_string(),
+            },
+        ),
+        (
+            "(status:Done))",
+            13,
+            ParseFilterErrorKind::Unexpected {
+                expected: "AND, OR or the end of the filter",
+                found: ")".to_string(),
+            },
+        ),
+        (
+            "NOT AND status:Done",
+            4,
+            ParseFilterErrorKind::Unexpected {
+                expected: "a field or `(`",
+                found: "AND".to_string(),
+            },
+        ),
+        (
+            "status label:bug",
+            7,
+            ParseFilterErrorKind::Unexpected {
+                expected: "`:` or `~`",
+                found: "label".to_string(),
+            },
+        ),
+        (
+            "archived:yes",
+            9,
+            ParseFilterErrorKind::InvalidValue("`yes` is not a boolean. Use true or false".to_string()),
+        ),
+    ];
+    for (text, position, kind) in cases {
+        assert_eq!(Filter::try_from(text), Err(ParseFilterError { position, kind }), "{}", text);
+    }
+
+    let err = Filter::try_from("status:Started").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid filter at position 7: `Started` is not a valid status. \
+        Use one of: ToDo, InProgress, Done, Blocked, InReview, Cancelled"
+    );
+}
+
+#[tokio::test]
+async fn test_store_filter() {
+    let store = Arc::new(RwLock::new(TicketStore::new()));
+    let ids = add_tickets(&store).await;
+    let store = store.read().unwrap();
+
+    let flaky = filter("title~FLAKY");
+    let tickets: Vec<TicketId> = store.filter(&flaky).map(|ticket| ticket.id).collect();
+    assert_eq!(tickets, [ids[0], ids[3]]);
+    // Archived tickets are included
+    let login = filter("title~login AND NOT status:InProgress");
+    let titles: Vec<String> = store.filter(&login).map(|ticket| ticket.title.as_str().to_string()).collect();
+    assert_eq!(titles, ["Old login page"]);
+}
+
+#[tokio::test]
+async fn test_filtered_listing() {
+    let memory = Arc::new(RwLock::new(TicketStore::new()));
+    let sqlite = Arc::new(Mutex::new(SqliteTicketStore::open_in_memory().unwrap()));
+    let memory_ids = add_tickets(&memory).await;
+    let sqlite_ids = add_tickets(&sqlite).await;
+
+    let cases: [(&str, &[usize]); 10] = [
+        (r#"status:InProgress AND label:backend AND NOT title~"flaky""#, &[1]),
+        ("label:backend", &[0, 1, 3]),
+        ("priority:P0 OR priority:P1", &[0, 1]),
+        // The unassigned tickets aren't assigned to alice
+        ("NOT assignee:alice", &[1, 2]),
+        ("assignee:alice AND NOT (status:Done OR label:docs)", &[0]),
+        ("description~\"DESCRIPTION\"", &[0, 1, 2, 3]),
+        ("title~login", &[0]),
+        // Case is ignored beyond ASCII too
+        ("title~\"éditeur\"", &[2]),
+        ("title~\"ÉDITEUR\"", &[2]),
+        ("archived:true", &[]),
+    ];
+    for (q, expected) in cases {
+        assert_eq!(listed(&memory, &memory_ids, filtered(q)).await, expected, "{}", q);
+        assert_eq!(listed(&sqlite, &sqlite_ids, filtered(q)).await, expected, "{}", q);
+    }
+
+    let query = TicketQuery {
+        title: Some("éDITEUR".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(listed(&memory, &memory_ids, query.clone()).await, [2]);
+    assert_eq!(listed(&sqlite, &sqlite_ids, query).await, [2]);
+
+    // Combined with the other parameters
+    let query = TicketQuery {
+        include_archived: true,
+        ..filtered("title~login")
+    };
+    assert_eq!(listed(&memory, &memory_ids, query.clone()).await, [0, 4]);
+    assert_eq!(listed(&sqlite, &sqlite_ids, query).await, [0, 4]);
+    let query = TicketQuery {
+        limit: Some(2),
+        ..filtered("label:backend")
+    };
+    for (repository_ids, page) in [
+        (&memory_ids, memory.list(query.clone()).await.unwrap()),
+        (&sqlite_ids, sqlite.list(query.clone()).await.unwrap()),
+    ] {
+        assert_eq!(page.next_cursor, Some(repository_ids[1]));
+    }
+}
+
+#[tokio::test]
+async fn test_filter_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let repository = Arc::new(RwLock::new(TicketStore::new()));
+    let ids = add_tickets(&repository).await;
+    let server = start_server(config, repository).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let page = client.list(&filtered("label:backend AND NOT status:Done")).await.unwrap();
+    let listed: Vec<TicketId> = page.tickets.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(listed, [ids[0], ids[1]]);
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/tickets", url))
+        .query(&[("q", "status:InProgress AND title~\"flaky\"")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/tickets", url))
+        .query(&[("q", "status:InProgress AND")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert!(body.message.contains("Invalid filter at position 21"), "{}", body.message);
+
+    server.shutdown().await.unwrap();
+}
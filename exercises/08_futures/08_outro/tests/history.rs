@@ -0,0 +1,147 @@
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Status, TicketDraft, TicketPatch};
+use outro_08::history::{Actor, FieldChange};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn patch(id: TicketId, title: Option<&str>, status: Option<Status>) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: title.map(|title| title.try_into().unwrap()),
+        description: None,
+        status,
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn change(field: &str, before: &str, after: &str) -> FieldChange {
+    FieldChange {
+        field: field.to_string(),
+        before: before.into(),
+        after: after.into(),
+    }
+}
+
+#[test]
+fn test_store_history() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft()).unwrap();
+    let alice = Actor::new("alice");
+    assert!(store.history(id).is_empty());
+
+    store.patch_by(patch(id, None, Some(Status::InProgress)), None, &alice).unwrap();
+    store.get_mut(patch(id, Some("Renamed"), Some(Status::InProgress))).unwrap();
+    // A patch that doesn't change anything isn't recorded
+    store.get_mut(patch(id, Some("Renamed"), None)).unwrap();
+    // Neither is a rejected one
+    assert!(store.patch_by(patch(id, Some("Nope"), None), Some(1), &alice).is_err());
+
+    let history = store.history(id);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].actor, alice);
+    assert_eq!(history[0].version, 2);
+    assert_eq!(history[0].changes, [change("status", "ToDo", "InProgress")]);
+    assert_eq!(history[1].actor, Actor::system());
+    assert_eq!(history[1].changes, [change("title", ticket_title().as_str(), "Renamed")]);
+    assert!(history[0].timestamp <= history[1].timestamp);
+
+    // The history outlives the ticket
+    store.remove(id).unwrap();
+    assert_eq!(store.history(id).len(), 2);
+}
+
+#[test]
+fn test_persistent_history_is_replayed() {
+    let dir = tempfile::tempdir().unwrap();
+    let bob = Actor::new("bob");
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(3);
+    let id = store.add_ticket(draft()).unwrap();
+    store.patch_by(patch(id, None, Some(Status::InProgress)), None, &bob).unwrap();
+    // Compacted here: the first entry is in the snapshot, the next one in the log
+    store.patch_by(patch(id, None, Some(Status::Done)), Some(2), &bob).unwrap();
+    store.patch_by(patch(id, Some("Renamed"), None), None, &bob).unwrap();
+    let history = store.history(id);
+    assert_eq!(history.len(), 3);
+    drop(store);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.history(id), history);
+}
+
+#[test]
+fn test_sqlite_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+    let carol = Actor::new("carol");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+    store.patch_by(patch(id, Some("Renamed"), Some(Status::InProgress)), Some(1), &carol).unwrap();
+    store.get_mut(patch(id, Some("Renamed"), None)).unwrap();
+    store.remove(id).unwrap();
+    drop(store);
+
+    let store = SqliteTicketStore::open(&path).unwrap();
+    let history = store.history(id).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].actor, carol);
+    assert_eq!(history[0].version, 2);
+    assert_eq!(
+        history[0].changes,
+        [
+            change("title", ticket_title().as_str(), "Renamed"),
+            change("status", "ToDo", "InProgress"),
+        ]
+    );
+    assert!(store.history(TicketId(42)).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_history_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let alice = TicketApiClient::new(url.clone()).actor(Actor::new("alice"));
+    let anonymous = TicketApiClient::new(url);
+
+    let id = alice.create(&draft()).await.unwrap();
+    assert!(alice.history(id).await.unwrap().is_empty());
+
+    alice.merge_patch(id, &json!({"status": "InProgress"})).await.unwrap();
+    anonymous.patch(&patch(id, Some("Renamed"), None)).await.unwrap();
+
+    let history = anonymous.history(id).await.unwrap();
+    let actors: Vec<_> = history.iter().map(|entry| entry.actor.as_str()).collect();
+    assert_eq!(actors, ["alice", "anonymous"]);
+    assert_eq!(history[1].changes, [change("title", ticket_title().as_str(), "Renamed")]);
+
+    // Still there once the ticket is deleted
+    alice.delete(id).await.unwrap();
+    assert_eq!(alice.history(id).await.unwrap(), history);
+
+    assert!(alice.history(TicketId(42)).await.unwrap_err().is_not_found());
+
+    server.shutdown().await.unwrap();
+}
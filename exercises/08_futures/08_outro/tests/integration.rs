@@ -1,110 +1,416 @@
-use reqwest;
-use tokio;
+use reqwest::StatusCode;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::{CommentBody, TicketDescription, TicketLabel};
 
-use outro_08::store::TicketId;
-use outro_08::data::{Ticket, TicketDraft, TicketPatch, Status};
-use outro_08::server::start_server;
+use outro_08::batch::{BatchOperation, BatchOutcome};
+use outro_08::bulk::{Format, ImportParams, ImportReport};
+use outro_08::comment::{CommentDraft, CommentId, TicketComment};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+use outro_08::client::{ClientError, TicketApiClient};
+use outro_08::data::{Ticket, TicketDraft, TicketPage, TicketPatch, TicketQuery, Status};
+use outro_08::error::ErrorBody;
+use outro_08::events::EventBus;
+use outro_08::history::{Actor, HistoryEntry};
+use outro_08::link::{DependencyTree, TicketLink, TicketLinks};
+use outro_08::repository::{RepositoryError, TicketRepository};
+use outro_08::retry::RetryPolicy;
+use outro_08::search::{SearchHit, SearchQuery};
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::user::{User, UserId};
 
-const LOCALHOST: &str = "127.0.0.1:3000";
-
-async fn test_add_ticket() {
-    let client = reqwest::Client::new();
-    let url = format!("http://{}{}", LOCALHOST, "/tickets");
+// Each test gets its own server, on a port picked by the OS, so that the tests can run in parallel
+fn config() -> ServerConfig {
+    ServerConfig::new().bind(([127, 0, 0, 1], 0))
+}
 
+async fn test_add_ticket(client: &TicketApiClient) {
     let draft = TicketDraft {
         title: ticket_title(),
         description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
     };
 
-    // Send a POST request to the API
-    let response = client
-        .post(url)
-        .json(&draft)  // Serialize and attach the JSON payload
-        .send()
-        .await
-        .unwrap();
+    let posted_ticket_id = client.create(&draft).await.unwrap();
+    println!("Deserialized POST response: {:#?}", posted_ticket_id);
+    assert_eq!(posted_ticket_id.0, 0);
+}
 
-    // Check if the request was successful
-    if response.status().is_success() {
-        // Deserialize the response body
-        let posted_ticket_id: TicketId = response.json().await.unwrap();
-        println!("Deserialized POST response: {:#?}", posted_ticket_id);
-        assert_eq!(posted_ticket_id.0, 0);
-    } else {
-        panic!("Failed to create a new Item.");
-    }
+async fn test_get_ticket(client: &TicketApiClient, ticket_expected: Ticket) {
+    let ticket_resp = client.get(TicketId(0)).await.unwrap();
+    println!("GET response json: {:#?}", ticket_resp);
+    assert_eq!(ticket_expected, ticket_resp);
 }
 
-async fn test_get_ticket(ticket_expected: Ticket) {    
-    // Send a GET request to the API
-    let url = format!("http://{}{}", LOCALHOST, "/tickets/0");
-    let response = reqwest::get(url)
-        .await
-        .unwrap();
+async fn test_patch_ticket(client: &TicketApiClient) {
+    let patch = TicketPatch {
+        id: TicketId(0),
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
 
-    // Ensure the request was successful
-    if response.status().is_success() {
-        // Parse the response as a vector of Item
-        let ticket_resp = response.json::<Ticket>().await.unwrap();
-        println!("GET response json: {:#?}", ticket_resp);
-        assert_eq!(ticket_expected, ticket_resp);
-    } else {
-        panic!("Failed to fetch data. Status: {}", response.status());
-    }
+    client.patch(&patch).await.unwrap();
 }
 
-async fn test_patch_ticket() {
-    let client = reqwest::Client::new();
-    let url = format!("http://{}{}", LOCALHOST, "/tickets/patch");
+async fn test_get_unknown_ticket(client: &TicketApiClient) {
+    let error = client.get(TicketId(999)).await.unwrap_err();
 
+    // An unknown ticket id is a 404, with a JSON error body
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+    assert_eq!(error.code(), Some("not_found"));
+    assert_eq!(error.to_string(), "Ticket 999 not found (404 Not Found)");
+}
+
+async fn test_patch_unknown_ticket(client: &TicketApiClient) {
     let patch = TicketPatch {
-        id: TicketId(0),
+        id: TicketId(999),
         title: None,
         description: None,
-        status: Some(Status::InProgress),
+        status: Some(Status::Done),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
     };
 
-    // Send a POST request to the API
+    let error = client.patch(&patch).await.unwrap_err();
+    assert!(error.is_not_found());
+    assert_eq!(error.code(), Some("not_found"));
+}
+
+async fn test_add_invalid_ticket(addr: &str) {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}{}", addr, "/tickets");
+
+    // An empty title is rejected by `TicketTitle::try_from`.
+    // `TicketApiClient` can't send it, since it only takes valid drafts.
     let response = client
         .post(url)
-        .json(&patch)  // Serialize and attach the JSON payload
+        .json(&serde_json::json!({"title": "", "description": "A description"}))
         .send()
         .await
         .unwrap();
 
-    // Check if the request was successful
-    if response.status().is_success() {
-        let _ = response.text().await.unwrap();  // Read the response body
-    } else {
-        panic!("Failed to patch the ticket. Status: {}", response.status());
-    }
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error = response.json::<ErrorBody>().await.unwrap();
+    assert_eq!(error.code, "validation_failed");
+    assert!(error.message.contains("The title cannot be empty"));
 }
 
-#[tokio::test]
-async fn test_integration() {
-    start_server(LOCALHOST).await;
+async fn test_list_tickets(client: &TicketApiClient, ticket_expected: Ticket) {
+    // Filter on the status
+    let query = TicketQuery {
+        status: Some(Status::InProgress),
+        limit: Some(10),
+        ..Default::default()
+    };
+    let page = client.list(&query).await.unwrap();
+    assert_eq!(page.tickets, vec![ticket_expected]);
+    assert_eq!(page.next_cursor, None);
+
+    // No ticket is done yet
+    let query = TicketQuery {
+        status: Some(Status::Done),
+        ..Default::default()
+    };
+    let page = client.list(&query).await.unwrap();
+    assert!(page.tickets.is_empty());
+}
 
-    test_add_ticket().await;
+async fn test_archive_and_delete_ticket(client: &TicketApiClient) {
+    // Archive the ticket: it's hidden from the listing...
+    let archived = client.archive(TicketId(0)).await.unwrap();
+    assert!(archived.archived);
+    let history = client.history(TicketId(0)).await.unwrap();
+    let last = history.last().unwrap();
+    assert_eq!((last.version, last.changes[0].field.as_str()), (archived.version, "archived"));
+
+    let page = client.list(&TicketQuery::default()).await.unwrap();
+    assert!(page.tickets.is_empty());
+
+    // ...but can still be retrieved by id
+    let ticket = client.get(TicketId(0)).await.unwrap();
+    assert_eq!(ticket, archived);
+
+    // Archiving twice is a conflict
+    let error = client.archive(TicketId(0)).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+
+    // Delete the ticket for good
+    client.delete(TicketId(0)).await.unwrap();
+
+    assert!(client.get(TicketId(0)).await.unwrap_err().is_not_found());
+    assert!(client.delete(TicketId(0)).await.unwrap_err().is_not_found());
+}
+
+// The same scenario runs against every `TicketRepository` implementation
+async fn test_scenario(addr: &str) {
+    let client = TicketApiClient::new(format!("http://{}", addr));
+
+    test_add_ticket(&client).await;
 
     let ticket_expected = Ticket {
         id: TicketId(0),
         title: ticket_title(),
         description: ticket_description(),
         status: Status::ToDo,
+        archived: false,
+        version: 1,
+        assignee: None,
+        reporter: None,
+        labels: Default::default(),
+        priority: Default::default(),
+        due_date: None,
     };
 
-    test_get_ticket(ticket_expected).await;
+    test_get_ticket(&client, ticket_expected).await;
 
-    test_patch_ticket().await;
+    test_patch_ticket(&client).await;
 
     let ticket_expected = Ticket {
         id: TicketId(0),
         title: ticket_title(),
         description: ticket_description(),
         status: Status::InProgress,
+        archived: false,
+        version: 2,
+        assignee: None,
+        reporter: None,
+        labels: Default::default(),
+        priority: Default::default(),
+        due_date: None,
+    };
+
+    test_get_ticket(&client, ticket_expected.clone()).await;
+
+    test_list_tickets(&client, ticket_expected).await;
+
+    test_get_unknown_ticket(&client).await;
+
+    test_patch_unknown_ticket(&client).await;
+
+    test_add_invalid_ticket(addr).await;
+
+    test_archive_and_delete_ticket(&client).await;
+}
+
+#[tokio::test]
+async fn test_integration_in_memory() {
+    let server = start_server(config(), Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+
+    test_scenario(&server.addr().to_string()).await;
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_integration_persistent() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    let server = start_server(config(), Arc::new(RwLock::new(store))).await.unwrap();
+
+    test_scenario(&server.addr().to_string()).await;
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_integration_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SqliteTicketStore::open(dir.path().join("tickets.db")).unwrap();
+    let server = start_server(config(), Arc::new(Mutex::new(store))).await.unwrap();
+
+    test_scenario(&server.addr().to_string()).await;
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_body_limit() {
+    let config = config().body_limit(128);
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: TicketDescription::try_from("a".repeat(256)).unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
     };
+    let error = client.create(&draft).await.unwrap_err();
+
+    assert_eq!(error.status(), Some(StatusCode::PAYLOAD_TOO_LARGE));
+    assert_eq!(error.code(), Some("payload_too_large"));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let server = start_server(config(), Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let addr = server.addr();
+    assert_ne!(addr.port(), 0);
+
+    let client = TicketApiClient::new(format!("http://{}", addr)).retry(RetryPolicy::none());
+    client.list(&TicketQuery::default()).await.unwrap();
+
+    server.shutdown().await.unwrap();
+
+    // The server doesn't accept connections anymore
+    let error = client.list(&TicketQuery::default()).await.unwrap_err();
+    assert!(matches!(error, ClientError::Http(_)));
+}
+
+// A repository that takes its time to list tickets
+#[derive(Clone)]
+struct SlowRepository(Arc<RwLock<TicketStore>>);
+
+impl TicketRepository for SlowRepository {
+    async fn insert(&self, draft: TicketDraft) -> Result<TicketId, RepositoryError> {
+        self.0.insert(draft).await
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        self.0.get(id).await
+    }
+
+    async fn patch(&self, patch: TicketPatch, actor: Actor) -> Result<Ticket, RepositoryError> {
+        self.0.patch(patch, actor).await
+    }
+
+    async fn patch_if_version(&self, patch: TicketPatch, expected: u64, actor: Actor) -> Result<Ticket, RepositoryError> {
+        self.0.patch_if_version(patch, expected, actor).await
+    }
+
+    async fn history(&self, id: TicketId) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        self.0.history(id).await
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<TicketPage, RepositoryError> {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        self.0.list(query).await
+    }
+
+    async fn search(&self, query: SearchQuery, limit: Option<usize>) -> Result<Vec<SearchHit>, RepositoryError> {
+        self.0.search(query, limit).await
+    }
+
+    async fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        actor: Actor,
+    ) -> Result<Vec<BatchOutcome>, RepositoryError> {
+        self.0.apply_batch(operations, actor).await
+    }
+
+    async fn import(&self, params: ImportParams, body: Vec<u8>) -> Result<ImportReport, RepositoryError> {
+        self.0.import(params, body).await
+    }
+
+    async fn export(&self, format: Format) -> Result<Vec<u8>, RepositoryError> {
+        self.0.export(format).await
+    }
+
+    async fn add_comment(&self, ticket_id: TicketId, draft: CommentDraft, actor: Actor) -> Result<TicketComment, RepositoryError> {
+        self.0.add_comment(ticket_id, draft, actor).await
+    }
+
+    async fn comments(&self, ticket_id: TicketId) -> Result<Vec<TicketComment>, RepositoryError> {
+        self.0.comments(ticket_id).await
+    }
+
+    async fn edit_comment(
+        &self,
+        ticket_id: TicketId,
+        comment_id: CommentId,
+        body: CommentBody,
+        actor: Actor,
+    ) -> Result<TicketComment, RepositoryError> {
+        self.0.edit_comment(ticket_id, comment_id, body, actor).await
+    }
+
+    async fn delete_comment(&self, ticket_id: TicketId, comment_id: CommentId, actor: Actor) -> Result<TicketComment, RepositoryError> {
+        self.0.delete_comment(ticket_id, comment_id, actor).await
+    }
+
+    async fn add_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        self.0.add_label(id, label, actor).await
+    }
+
+    async fn remove_label(&self, id: TicketId, label: TicketLabel, actor: Actor) -> Result<Ticket, RepositoryError> {
+        self.0.remove_label(id, label, actor).await
+    }
+
+    async fn add_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        self.0.add_link(link, actor).await
+    }
+
+    async fn remove_link(&self, link: TicketLink, actor: Actor) -> Result<TicketLinks, RepositoryError> {
+        self.0.remove_link(link, actor).await
+    }
+
+    async fn links(&self, id: TicketId) -> Result<TicketLinks, RepositoryError> {
+        self.0.links(id).await
+    }
+
+    async fn dependencies(&self, id: TicketId) -> Result<DependencyTree, RepositoryError> {
+        self.0.dependencies(id).await
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, RepositoryError> {
+        self.0.insert_user(user).await
+    }
+
+    async fn get_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        self.0.get_user(id).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, RepositoryError> {
+        self.0.list_users().await
+    }
+
+    async fn delete_user(&self, id: UserId) -> Result<User, RepositoryError> {
+        self.0.delete_user(id).await
+    }
+
+    async fn archive(&self, id: TicketId, actor: Actor) -> Result<Ticket, RepositoryError> {
+        self.0.archive(id, actor).await
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Ticket, RepositoryError> {
+        self.0.delete(id).await
+    }
+
+    async fn events(&self) -> Result<EventBus, RepositoryError> {
+        self.0.events().await
+    }
+}
+
+#[tokio::test]
+async fn test_request_timeout() {
+    let config = config().request_timeout(Duration::from_millis(100));
+    let repository = SlowRepository(Arc::new(RwLock::new(TicketStore::new())));
+    let server = start_server(config, repository).await.unwrap();
+
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    let error = client.list(&TicketQuery::default()).await.unwrap_err();
+
+    assert_eq!(error.status(), Some(StatusCode::REQUEST_TIMEOUT));
+    assert_eq!(error.code(), Some("timeout"));
 
-    test_get_ticket(ticket_expected).await;
+    server.shutdown().await.unwrap();
 }
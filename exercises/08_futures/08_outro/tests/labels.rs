@@ -0,0 +1,249 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketLabel;
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{SortOrder, Status, Ticket, TicketDraft, TicketQuery};
+use outro_08::history::{Actor, FieldChange};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn label(label: &str) -> TicketLabel {
+    label.try_into().unwrap()
+}
+
+fn labelled(name: &str) -> TicketQuery {
+    TicketQuery {
+        label: Some(label(name)),
+        ..Default::default()
+    }
+}
+
+fn ids(tickets: &[Ticket]) -> Vec<TicketId> {
+    tickets.iter().map(|ticket| ticket.id).collect()
+}
+
+fn labels(ticket: &Ticket) -> Vec<&str> {
+    ticket.labels.iter().map(TicketLabel::as_str).collect()
+}
+
+#[test]
+fn test_store_labels() {
+    let mut store = TicketStore::new();
+    let alice = Actor::new("alice");
+    let tickets: Vec<_> = (0..5).map(|_| store.add_ticket(draft()).unwrap()).collect();
+
+    for id in [tickets[0], tickets[2], tickets[3]] {
+        store.add_label(id, label("bug"), &alice).unwrap();
+    }
+    let ticket = store.add_label(tickets[2], label("area/billing"), &alice).unwrap();
+    assert_eq!(labels(&ticket), ["area/billing", "bug"]);
+    assert_eq!(ticket.version, 3);
+    // Adding a label twice changes nothing
+    let ticket = store.add_label(tickets[2], label("bug"), &alice).unwrap();
+    assert_eq!(ticket.version, 3);
+
+    assert_eq!(ids(&store.list(&labelled("bug")).unwrap().tickets), [tickets[0], tickets[2], tickets[3]]);
+    assert_eq!(ids(&store.list(&labelled("area/billing")).unwrap().tickets), [tickets[2]]);
+    assert!(store.list(&labelled("unknown")).unwrap().tickets.is_empty());
+
+    // Labels combine with the other filters, and with pagination
+    store.get_mut(outro_08::data::TicketPatch {
+        id: tickets[3],
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    })
+    .unwrap();
+    let query = TicketQuery {
+        status: Some(Status::ToDo),
+        ..labelled("bug")
+    };
+    assert_eq!(ids(&store.list(&query).unwrap().tickets), [tickets[0], tickets[2]]);
+    let query = TicketQuery {
+        sort: SortOrder::Desc,
+        limit: Some(2),
+        ..labelled("bug")
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), [tickets[3], tickets[2]]);
+    let query = TicketQuery {
+        cursor: page.next_cursor,
+        ..query
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), [tickets[0]]);
+    assert_eq!(page.next_cursor, None);
+
+    // Label changes are in the history
+    let ticket = store.remove_label(tickets[2], &label("bug"), &alice).unwrap();
+    assert_eq!(labels(&ticket), ["area/billing"]);
+    let entry = store.history(tickets[2]).last().unwrap();
+    assert_eq!((entry.version, &entry.actor), (4, &alice));
+    assert_eq!(
+        entry.changes,
+        [FieldChange {
+            field: "labels".to_string(),
+            before: json!(["area/billing", "bug"]),
+            after: json!(["area/billing"]),
+        }]
+    );
+    // Removing a label the ticket doesn't have changes nothing
+    assert_eq!(store.remove_label(tickets[2], &label("bug"), &alice).unwrap().version, 4);
+
+    // Removed tickets leave the index
+    store.remove(tickets[0]).unwrap();
+    assert_eq!(ids(&store.list(&labelled("bug")).unwrap().tickets), [tickets[3]]);
+    assert_eq!(
+        store.add_label(tickets[0], label("bug"), &alice),
+        Err(TicketStoreError::NotFound(tickets[0]))
+    );
+}
+
+#[test]
+fn test_persistent_labels() {
+    let dir = tempfile::tempdir().unwrap();
+    let bob = Actor::new("bob");
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(4);
+    let first = store.add_ticket(draft()).unwrap();
+    let second = store.add_ticket(draft()).unwrap();
+    store.add_label(first, label("bug"), &bob).unwrap();
+    store.add_label(second, label("bug"), &bob).unwrap();
+    // Compacted here: the index is rebuilt from the snapshot, then updated by the log
+    store.add_label(second, label("ui"), &bob).unwrap();
+    store.remove_label(first, &label("bug"), &bob).unwrap();
+    let history = store.history(second);
+    drop(store);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(ids(&store.list(&labelled("bug")).unwrap().tickets), [second]);
+    assert_eq!(ids(&store.list(&labelled("ui")).unwrap().tickets), [second]);
+    assert_eq!(labels(&store.get(second).unwrap()), ["bug", "ui"]);
+    assert!(store.get(first).unwrap().labels.is_empty());
+    assert_eq!(store.history(second), history);
+}
+
+#[test]
+fn test_sqlite_labels() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+    let carol = Actor::new("carol");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let first = store.add_ticket(draft()).unwrap();
+    let second = store.add_ticket(draft()).unwrap();
+    store.add_label(first, label("bug"), &carol).unwrap();
+    store.add_label(first, label("v1.2"), &carol).unwrap();
+    store.add_label(second, label("bug"), &carol).unwrap();
+    assert_eq!(store.add_label(second, label("bug"), &carol).unwrap().version, 2);
+    let ticket = store.remove_label(second, &label("bug"), &carol).unwrap();
+    assert_eq!(ticket.version, 3);
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let ticket = store.get(first).unwrap().unwrap();
+    assert_eq!(labels(&ticket), ["bug", "v1.2"]);
+    assert_eq!(ticket.version, 3);
+    assert_eq!(ids(&store.list(&labelled("bug")).unwrap().tickets), [first]);
+    assert!(store.list(&labelled("ui")).unwrap().tickets.is_empty());
+    assert_eq!(store.history(second).unwrap().len(), 2);
+
+    store.remove(first).unwrap();
+    assert!(store.list(&labelled("bug")).unwrap().tickets.is_empty());
+}
+
+#[test]
+fn test_serde_round_trip() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft()).unwrap();
+    store.add_label(id, label("bug"), &Actor::system()).unwrap();
+    let ticket = store.add_label(id, label("area/api"), &Actor::system()).unwrap();
+
+    let value = serde_json::to_value(&ticket).unwrap();
+    assert_eq!(value["labels"], json!(["area/api", "bug"]));
+    assert_eq!(serde_json::from_value::<Ticket>(value.clone()).unwrap(), ticket);
+
+    // Tickets serialized before labels existed have none
+    let mut old = value.clone();
+    old.as_object_mut().unwrap().remove("labels");
+    assert!(serde_json::from_value::<Ticket>(old).unwrap().labels.is_empty());
+    // Invalid labels are rejected
+    let mut invalid = value;
+    invalid["labels"] = json!(["not valid"]);
+    assert!(serde_json::from_value::<Ticket>(invalid).is_err());
+}
+
+#[tokio::test]
+async fn test_label_endpoints() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone()).actor(Actor::new("alice"));
+
+    let first = client.create(&draft()).await.unwrap();
+    let second = client.create(&draft()).await.unwrap();
+    client.add_label(first, &label("bug")).await.unwrap();
+    // Labels with a slash are a single path segment
+    let ticket = client.add_label(first, &label("area/api")).await.unwrap();
+    assert_eq!(labels(&ticket), ["area/api", "bug"]);
+    client.add_label(second, &label("bug")).await.unwrap();
+
+    let page = client.list(&labelled("area/api")).await.unwrap();
+    assert_eq!(ids(&page.tickets), [first]);
+    let page = client.list(&labelled("bug")).await.unwrap();
+    assert_eq!(ids(&page.tickets), [first, second]);
+
+    let ticket = client.remove_label(first, &label("bug")).await.unwrap();
+    assert_eq!(labels(&ticket), ["area/api"]);
+    assert_eq!(ids(&client.list(&labelled("bug")).await.unwrap().tickets), [second]);
+    let history = client.history(first).await.unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].actor, Actor::new("alice"));
+
+    // Labels can't be changed with a patch, but a patch can carry them unchanged
+    let error = client.merge_patch(first, &json!({"labels": ["bug"]})).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    let ticket = client
+        .merge_patch(first, &json!({"labels": ["area/api"], "status": "InProgress"}))
+        .await
+        .unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+
+    // Unknown tickets, invalid labels
+    assert!(client.add_label(TicketId(42), &label("bug")).await.unwrap_err().is_not_found());
+    let response = reqwest::Client::new()
+        .put(format!("{}/tickets/{}/labels/not%20valid", url, first))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = reqwest::Client::new()
+        .get(format!("{}/tickets?label=not%20valid", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    server.shutdown().await.unwrap();
+}
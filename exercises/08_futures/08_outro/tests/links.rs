@@ -0,0 +1,326 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::ticket_description;
+use ticket_fields::TicketTitle;
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch};
+use outro_08::history::{Actor, FieldChange};
+use outro_08::link::{DependencyTree, LinkKind, TicketLink, TicketLinks};
+use outro_08::persistent::{PersistenceError, PersistentTicketStore};
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::{SqliteError, SqliteTicketStore};
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn move_to(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn link(source: TicketId, kind: LinkKind, target: TicketId) -> TicketLink {
+    TicketLink { source, kind, target }
+}
+
+// The ids of a dependency tree, depth first
+fn tree_ids(tree: &DependencyTree) -> Vec<TicketId> {
+    let mut ids = vec![tree.id];
+    ids.extend(tree.blocked_by.iter().flat_map(tree_ids));
+    ids
+}
+
+#[test]
+fn test_link_kinds() {
+    assert_eq!(LinkKind::try_from("blocks").unwrap(), LinkKind::Blocks);
+    assert_eq!(LinkKind::try_from("Parent-Of").unwrap(), LinkKind::ParentOf);
+    assert_eq!(
+        LinkKind::try_from("relates_to").unwrap_err().to_string(),
+        "`relates_to` is not a kind of link. Use one of: parent_of, blocks, duplicates"
+    );
+    let serialized = serde_json::to_value(link(TicketId(1), LinkKind::ParentOf, TicketId(2))).unwrap();
+    assert_eq!(serialized, json!({"source": 1, "kind": "parent_of", "target": 2}));
+}
+
+#[test]
+fn test_store_links() {
+    let mut store = TicketStore::new();
+    let epic = store.add_ticket(draft("Epic")).unwrap();
+    let story = store.add_ticket(draft("Story")).unwrap();
+    let task = store.add_ticket(draft("Task")).unwrap();
+    let alice = Actor::new("alice");
+
+    store.add_link(link(epic, LinkKind::ParentOf, story), &alice).unwrap();
+    store.add_link(link(epic, LinkKind::ParentOf, task), &alice).unwrap();
+    let links = store.add_link(link(task, LinkKind::Blocks, story), &alice).unwrap();
+    assert_eq!(
+        links,
+        TicketLinks {
+            parent: Some(epic),
+            blocks: vec![story],
+            ..Default::default()
+        }
+    );
+    // Adding the same link twice changes nothing
+    store.add_link(link(task, LinkKind::Blocks, story), &alice).unwrap();
+    assert_eq!(
+        store.links(story).unwrap(),
+        TicketLinks {
+            parent: Some(epic),
+            blocked_by: vec![task],
+            ..Default::default()
+        }
+    );
+    assert_eq!(store.links(epic).unwrap().children, [story, task]);
+
+    // Links are recorded in the history of their source, without a new version
+    let history = store.history(task);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].actor, alice);
+    assert_eq!(
+        history[0].changes,
+        [FieldChange {
+            field: "links/blocks".to_string(),
+            before: serde_json::Value::Null,
+            after: json!(story),
+        }]
+    );
+    assert_eq!(store.get(task).unwrap().read().unwrap().version, 1);
+
+    // Removing a link, twice
+    store.remove_link(link(task, LinkKind::Blocks, story), &alice).unwrap();
+    let links = store.remove_link(link(task, LinkKind::Blocks, story), &alice).unwrap();
+    assert!(links.blocks.is_empty());
+    assert_eq!(store.history(task).len(), 2);
+    assert_eq!(store.history(task)[1].changes[0].before, json!(story));
+
+    // Removing a ticket removes its links, in both directions
+    store.remove(epic).unwrap();
+    assert_eq!(store.links(story).unwrap(), TicketLinks::default());
+    assert!(matches!(store.links(epic), Err(TicketStoreError::NotFound(id)) if id == epic));
+}
+
+#[test]
+fn test_invalid_links() {
+    let mut store = TicketStore::new();
+    let a = store.add_ticket(draft("A")).unwrap();
+    let b = store.add_ticket(draft("B")).unwrap();
+    let c = store.add_ticket(draft("C")).unwrap();
+    let actor = Actor::system();
+
+    store.add_link(link(a, LinkKind::Blocks, b), &actor).unwrap();
+    store.add_link(link(b, LinkKind::Blocks, c), &actor).unwrap();
+    let err = store.add_link(link(c, LinkKind::Blocks, a), &actor).unwrap_err();
+    assert_eq!(err.to_string(), "Linking tickets 2 and 0 would create a cycle of `blocks` links");
+
+    // Cycles are only checked within a kind, and duplicates may go both ways
+    store.add_link(link(c, LinkKind::ParentOf, a), &actor).unwrap();
+    store.add_link(link(a, LinkKind::Duplicates, b), &actor).unwrap();
+    store.add_link(link(b, LinkKind::Duplicates, a), &actor).unwrap();
+    assert!(matches!(
+        store.add_link(link(a, LinkKind::ParentOf, c), &actor),
+        Err(TicketStoreError::LinkCycle(_))
+    ));
+
+    let err = store.add_link(link(b, LinkKind::ParentOf, a), &actor).unwrap_err();
+    assert_eq!(err.to_string(), "Ticket 0 already has a parent: ticket 2");
+    assert!(matches!(
+        store.add_link(link(a, LinkKind::Duplicates, a), &actor),
+        Err(TicketStoreError::SelfLink(id)) if id == a
+    ));
+    assert!(matches!(
+        store.add_link(link(a, LinkKind::Blocks, TicketId(42)), &actor),
+        Err(TicketStoreError::NotFound(TicketId(42)))
+    ));
+}
+
+#[test]
+fn test_open_blockers() {
+    let mut store = TicketStore::new();
+    let blocker = store.add_ticket(draft("Blocker")).unwrap();
+    let blocked = store.add_ticket(draft("Blocked")).unwrap();
+    store.add_link(link(blocker, LinkKind::Blocks, blocked), &Actor::system()).unwrap();
+
+    store.get_mut(move_to(blocked, Status::InProgress)).unwrap();
+    let err = store.get_mut(move_to(blocked, Status::Done)).unwrap_err();
+    assert_eq!(err.to_string(), "Ticket 1 is blocked by ticket 0, which is still open");
+    assert_eq!(store.get(blocked).unwrap().read().unwrap().status, Status::InProgress);
+
+    store.get_mut(move_to(blocker, Status::InProgress)).unwrap();
+    store.get_mut(move_to(blocker, Status::Done)).unwrap();
+    store.get_mut(move_to(blocked, Status::Done)).unwrap();
+}
+
+#[test]
+fn test_store_dependencies() {
+    let mut store = TicketStore::new();
+    let release = store.add_ticket(draft("Release")).unwrap();
+    let docs = store.add_ticket(draft("Docs")).unwrap();
+    let fix = store.add_ticket(draft("Fix")).unwrap();
+    let review = store.add_ticket(draft("Review")).unwrap();
+    let actor = Actor::system();
+    store.add_link(link(docs, LinkKind::Blocks, release), &actor).unwrap();
+    store.add_link(link(fix, LinkKind::Blocks, release), &actor).unwrap();
+    store.add_link(link(review, LinkKind::Blocks, fix), &actor).unwrap();
+    store.add_link(link(review, LinkKind::Blocks, docs), &actor).unwrap();
+    // Other kinds of links aren't dependencies
+    store.add_link(link(release, LinkKind::ParentOf, docs), &actor).unwrap();
+
+    let tree = store.dependencies(release).unwrap();
+    assert_eq!(tree.title.as_str(), "Release");
+    assert_eq!(tree.status, Status::ToDo);
+    // A ticket that blocks two others appears twice
+    assert_eq!(tree_ids(&tree), [release, docs, review, fix, review]);
+    assert!(store.dependencies(review).unwrap().blocked_by.is_empty());
+}
+
+#[test]
+fn test_persistent_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(4);
+    let a = store.add_ticket(draft("A")).unwrap();
+    let b = store.add_ticket(draft("B")).unwrap();
+    let c = store.add_ticket(draft("C")).unwrap();
+    store.add_link(link(a, LinkKind::Blocks, b), &Actor::system()).unwrap();
+    // Compacted here: the next operations are only in the log
+    store.add_link(link(a, LinkKind::ParentOf, c), &Actor::system()).unwrap();
+    store.add_link(link(b, LinkKind::Blocks, c), &Actor::system()).unwrap();
+    store.remove_link(link(a, LinkKind::Blocks, b), &Actor::system()).unwrap();
+    let err = store.add_link(link(c, LinkKind::Blocks, b), &Actor::system()).unwrap_err();
+    assert!(matches!(err, PersistenceError::Store(TicketStoreError::LinkCycle(_))));
+    let history = store.history(a);
+    drop(store);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(
+        store.links(a).unwrap(),
+        TicketLinks {
+            children: vec![c],
+            ..Default::default()
+        }
+    );
+    assert_eq!(store.links(c).unwrap().blocked_by, [b]);
+    assert_eq!(tree_ids(&store.dependencies(c).unwrap()), [c, b]);
+    assert_eq!(store.history(a), history);
+}
+
+#[test]
+fn test_sqlite_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let a = store.add_ticket(draft("A")).unwrap();
+    let b = store.add_ticket(draft("B")).unwrap();
+    let c = store.add_ticket(draft("C")).unwrap();
+    store.add_link(link(a, LinkKind::Blocks, b), &Actor::system()).unwrap();
+    store.add_link(link(b, LinkKind::Blocks, c), &Actor::system()).unwrap();
+    store.add_link(link(b, LinkKind::Blocks, c), &Actor::system()).unwrap();
+    store.add_link(link(a, LinkKind::ParentOf, c), &Actor::system()).unwrap();
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(
+        store.links(b).unwrap(),
+        TicketLinks {
+            blocks: vec![c],
+            blocked_by: vec![a],
+            ..Default::default()
+        }
+    );
+    assert_eq!(tree_ids(&store.dependencies(c).unwrap()), [c, b, a]);
+    assert_eq!(store.history(b).unwrap().len(), 1);
+
+    let actor = Actor::system();
+    for (invalid, expected) in [
+        (link(c, LinkKind::Blocks, a), "Linking tickets 2 and 0 would create a cycle of `blocks` links"),
+        (link(b, LinkKind::ParentOf, c), "Ticket 2 already has a parent: ticket 0"),
+        (link(a, LinkKind::Blocks, a), "Ticket 0 can't be linked to itself"),
+    ] {
+        let err = store.add_link(invalid, &actor).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+    assert!(matches!(
+        store.add_link(link(a, LinkKind::Blocks, TicketId(42)), &actor),
+        Err(SqliteError::Store(TicketStoreError::NotFound(TicketId(42))))
+    ));
+
+    store.get_mut(move_to(c, Status::InProgress)).unwrap();
+    let err = store.get_mut(move_to(c, Status::Done)).unwrap_err();
+    assert!(matches!(err, SqliteError::Store(TicketStoreError::OpenBlocker { blocker, .. }) if blocker == b));
+
+    store.remove_link(link(b, LinkKind::Blocks, c), &actor).unwrap();
+    store.get_mut(move_to(c, Status::Done)).unwrap();
+    store.remove(a).unwrap();
+    assert_eq!(store.links(c).unwrap(), TicketLinks::default());
+    assert_eq!(store.links(b).unwrap(), TicketLinks::default());
+}
+
+#[tokio::test]
+async fn test_link_endpoints() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let a = client.create(&draft("A")).await.unwrap();
+    let b = client.create(&draft("B")).await.unwrap();
+    let links = client.add_link(&link(a, LinkKind::Blocks, b)).await.unwrap();
+    assert_eq!(links.blocks, [b]);
+    assert_eq!(client.links(b).await.unwrap().blocked_by, [a]);
+    let tree = client.dependencies(b).await.unwrap();
+    assert_eq!(tree_ids(&tree), [b, a]);
+
+    // The kind can be spelled with dashes
+    let response = reqwest::Client::new()
+        .put(format!("{}/tickets/{}/links/parent-of/{}", url, b.0, a.0))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let links: TicketLinks = response.json().await.unwrap();
+    assert_eq!(links.children, [a]);
+
+    // Errors
+    let error = client.add_link(&link(b, LinkKind::Blocks, a)).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+    let error = client.add_link(&link(a, LinkKind::Blocks, a)).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    let error = client.add_link(&link(a, LinkKind::Blocks, TicketId(42))).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+    let response = reqwest::Client::new()
+        .put(format!("{}/tickets/{}/links/relates_to/{}", url, a.0, b.0))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    client.patch(&move_to(b, Status::InProgress)).await.unwrap();
+    let error = client.patch(&move_to(b, Status::Done)).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+
+    let links = client.remove_link(&link(a, LinkKind::Blocks, b)).await.unwrap();
+    assert!(links.blocks.is_empty());
+    client.patch(&move_to(b, Status::Done)).await.unwrap();
+
+    server.shutdown().await.unwrap();
+}
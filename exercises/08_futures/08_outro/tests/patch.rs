@@ -0,0 +1,332 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Status, Ticket, TicketDraft};
+use outro_08::error::{ApiError, ErrorBody, FieldError};
+use outro_08::patch::PatchDocument;
+use outro_08::server::{start_server, ServerConfig, ServerHandle};
+use outro_08::store::{TicketId, TicketStore};
+use outro_08::workflow::Workflow;
+
+async fn server_with_a_ticket() -> (ServerHandle, TicketApiClient) {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    };
+    client.create(&draft).await.unwrap();
+    (server, client)
+}
+
+fn ticket() -> Ticket {
+    Ticket {
+        id: TicketId(0),
+        title: ticket_title(),
+        description: ticket_description(),
+        status: Status::ToDo,
+        archived: false,
+        version: 1,
+        assignee: None,
+        reporter: None,
+        labels: Default::default(),
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn json_patch(operations: serde_json::Value) -> json_patch::Patch {
+    serde_json::from_value(operations).unwrap()
+}
+
+fn field_errors(error: ApiError) -> Vec<FieldError> {
+    match error {
+        ApiError::InvalidFields(fields) => fields,
+        other => panic!("Expected invalid fields, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_merge_patch() {
+    let (server, client) = server_with_a_ticket().await;
+
+    let ticket = client
+        .merge_patch(TicketId(0), &json!({"status": "InProgress", "title": "New title"}))
+        .await
+        .unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.title.as_str(), "New title");
+    assert_eq!(ticket.description, ticket_description());
+    assert_eq!(client.get(TicketId(0)).await.unwrap(), ticket);
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_merge_patch_reports_every_invalid_field() {
+    let (server, client) = server_with_a_ticket().await;
+
+    let error = client
+        .merge_patch(TicketId(0), &json!({"title": "", "description": null, "status": "Nope"}))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    assert_eq!(error.code(), Some("validation_failed"));
+    let outro_08::client::ClientError::Api { body, .. } = error else {
+        panic!("Expected an error response");
+    };
+    let fields: Vec<_> = body.fields.iter().map(|error| error.field.as_str()).collect();
+    assert_eq!(fields, ["title", "description", "status"]);
+    assert_eq!(body.message, "Invalid fields: title, description, status");
+
+    // Nothing was changed
+    assert_eq!(client.get(TicketId(0)).await.unwrap(), ticket());
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_json_patch() {
+    let (server, client) = server_with_a_ticket().await;
+
+    let patch = json_patch(json!([
+        {"op": "test", "path": "/status", "value": "ToDo"},
+        {"op": "replace", "path": "/status", "value": "InProgress"},
+        {"op": "copy", "from": "/title", "path": "/description"},
+    ]));
+    let ticket = client.json_patch(TicketId(0), &patch).await.unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.description.as_str(), ticket_title().as_str());
+
+    // The `test` operation now fails: the ticket has been changed in the meantime
+    let error = client.json_patch(TicketId(0), &patch).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_etag_and_if_match() {
+    let (server, client) = server_with_a_ticket().await;
+    let url = format!("http://{}/tickets/0", server.addr());
+    let http = reqwest::Client::new();
+
+    let response = http.get(&url).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"1\"");
+
+    // Both clients read version 1: the second write is rejected
+    let ticket = client
+        .merge_patch_if_version(TicketId(0), 1, &json!({"status": "InProgress"}))
+        .await
+        .unwrap();
+    assert_eq!(ticket.version, 2);
+    let error = client
+        .merge_patch_if_version(TicketId(0), 1, &json!({"status": "Done"}))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::PRECONDITION_FAILED));
+    assert_eq!(error.code(), Some("precondition_failed"));
+    assert_eq!(client.get(TicketId(0)).await.unwrap().status, Status::InProgress);
+
+    // `If-Match` accepts a list of ETags, or `*`; weak ETags never match
+    let patch = |if_match: &'static str| {
+        http.patch(&url)
+            .header("content-type", "application/merge-patch+json")
+            .header("if-match", if_match)
+            .body(r#"{"status": "Done"}"#)
+            .send()
+    };
+    assert_eq!(patch("W/\"2\"").await.unwrap().status(), StatusCode::PRECONDITION_FAILED);
+    let response = patch("\"1\", \"2\"").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["etag"], "\"3\"");
+    // The ticket is already done: the ETag, and so every client's copy, stays valid
+    let response = patch("*").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["etag"], "\"3\"");
+    let ticket = client.get(TicketId(0)).await.unwrap();
+    let document = serde_json::to_value(&ticket).unwrap();
+    assert_eq!(client.merge_patch(TicketId(0), &document).await.unwrap().version, 3);
+
+    // A JSON Patch can test the version as well
+    let patch = json_patch(json!([
+        {"op": "test", "path": "/version", "value": 1},
+        {"op": "replace", "path": "/status", "value": "ToDo"},
+    ]));
+    let error = client.json_patch(TicketId(0), &patch).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_workflow_is_enforced() {
+    let (server, client) = server_with_a_ticket().await;
+
+    // A new ticket can't be done right away
+    let error = client.merge_patch(TicketId(0), &json!({"status": "Done"})).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+    assert_eq!(error.code(), Some("invalid_transition"));
+    assert_eq!(error.to_string(), "Ticket 0: A ticket can't go from ToDo to Done (409 Conflict)");
+
+    // Optional statuses are disabled by default
+    let error = client.merge_patch(TicketId(0), &json!({"status": "Blocked"})).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+
+    // The legacy endpoint goes through the same checks
+    let patch = outro_08::data::TicketPatch {
+        id: TicketId(0),
+        title: None,
+        description: None,
+        status: Some(Status::Done),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    assert_eq!(client.patch(&patch).await.unwrap_err().code(), Some("invalid_transition"));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_patch_ticket_in_a_disabled_status() {
+    // The ticket got blocked while Blocked was enabled
+    let mut store = TicketStore::new().with_workflow(Workflow::standard().enable(Status::Blocked));
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    };
+    let id = store.add_ticket(draft).unwrap();
+    let patch = outro_08::data::TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::Blocked),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    store.get_mut(patch.clone()).unwrap();
+    let store = store.with_workflow(Workflow::standard());
+
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(store))).await.unwrap();
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+
+    // Its other fields can still be edited, with the current status in the document or not
+    let ticket = client.merge_patch(id, &json!({"title": "Still blocked"})).await.unwrap();
+    assert_eq!((ticket.title.as_str(), ticket.status), ("Still blocked", Status::Blocked));
+    let operations = json!([{"op": "replace", "path": "/description", "value": "Waiting"}]);
+    let ticket = client.json_patch(id, &json_patch(operations)).await.unwrap();
+    assert_eq!(ticket.description.as_str(), "Waiting");
+    client.patch(&patch).await.unwrap();
+
+    // But it can't leave the status until it is enabled again
+    let error = client.merge_patch(id, &json!({"status": "InProgress"})).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_patch_unknown_ticket() {
+    let (server, client) = server_with_a_ticket().await;
+
+    let error = client.merge_patch(TicketId(42), &json!({"status": "Done"})).await.unwrap_err();
+    assert!(error.is_not_found());
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_patch_content_types() {
+    let (server, _) = server_with_a_ticket().await;
+    let url = format!("http://{}/tickets/0", server.addr());
+    let http = reqwest::Client::new();
+
+    // Plain JSON is a merge patch
+    let response = http
+        .patch(&url)
+        .header("content-type", "application/json; charset=utf-8")
+        .body(r#"{"status": "inprogress"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Ticket>().await.unwrap().status, Status::InProgress);
+
+    let response = http.patch(&url).header("content-type", "text/plain").body("done").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(response.json::<ErrorBody>().await.unwrap().code, "unsupported_media_type");
+
+    let response = http
+        .patch(&url)
+        .header("content-type", "application/merge-patch+json")
+        .body("{not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    server.shutdown().await.unwrap();
+}
+
+#[test]
+fn test_read_only_and_unknown_fields() {
+    let patch = PatchDocument::Json(json_patch(json!([
+        {"op": "replace", "path": "/id", "value": 3},
+        {"op": "replace", "path": "/archived", "value": true},
+        {"op": "add", "path": "/owner", "value": "bob"},
+    ])));
+    let fields = field_errors(patch.apply(&ticket()).unwrap_err());
+    let fields: Vec<_> = fields.iter().map(|error| (error.field.as_str(), error.message.as_str())).collect();
+    assert_eq!(
+        fields,
+        [
+            ("archived", "use POST /tickets/:id/archive to archive a ticket"),
+            ("id", "this field is read-only"),
+            ("owner", "unknown field"),
+        ]
+    );
+
+    // Removing a field is invalid too
+    let patch = PatchDocument::Json(json_patch(json!([{"op": "remove", "path": "/id"}])));
+    assert_eq!(field_errors(patch.apply(&ticket()).unwrap_err())[0].field, "id");
+
+    // Setting a read-only field to its current value is fine
+    let patch = PatchDocument::Merge(json!({"id": 0, "status": "inprogress"}));
+    let patch = patch.apply(&ticket()).unwrap();
+    assert_eq!(patch.status, Some(Status::InProgress));
+    assert_eq!(patch.title, Some(ticket_title()));
+}
+
+#[test]
+fn test_invalid_documents() {
+    // A merge patch must be an object
+    let patch = PatchDocument::Merge(json!(["status", "Done"]));
+    assert!(matches!(patch.apply(&ticket()), Err(ApiError::BadRequest(_))));
+
+    // A JSON Patch path must exist
+    let patch = PatchDocument::Json(json_patch(json!([{"op": "remove", "path": "/nope"}])));
+    assert!(matches!(patch.apply(&ticket()), Err(ApiError::Validation(_))));
+
+    // Non-string values are rejected
+    let patch = PatchDocument::Merge(json!({"title": 12}));
+    let fields = field_errors(patch.apply(&ticket()).unwrap_err());
+    assert_eq!(fields[0].message, "expected a string");
+}
@@ -0,0 +1,208 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use outro_08::data::{Status, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::history::Actor;
+use outro_08::persistent::{PersistenceError, PersistentTicketStore};
+use outro_08::store::{TicketId, TicketStoreError};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn in_progress(id: TicketId) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+#[test]
+fn test_restart_keeps_tickets() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    let id0 = store.add_ticket(draft()).unwrap();
+    let id1 = store.add_ticket(draft()).unwrap();
+    let id2 = store.add_ticket(draft()).unwrap();
+    store.get_mut(in_progress(id0)).unwrap();
+    store.archive(id1, &Actor::system()).unwrap();
+    store.remove(id2).unwrap();
+    // Simulate a restart
+    drop(store);
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.get(id0).unwrap().status, Status::InProgress);
+    assert!(store.get(id1).unwrap().archived);
+    assert!(store.get(id2).is_none());
+
+    // The counter is restored too: ids are not reused
+    let id3 = store.add_ticket(draft()).unwrap();
+    assert_eq!(id3, TicketId(3));
+}
+
+#[test]
+fn test_versions_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+    assert_eq!(store.patch_if_version(in_progress(id), 1).unwrap().version, 2);
+    // A rejected patch is not logged
+    assert!(matches!(
+        store.patch_if_version(in_progress(id), 1),
+        Err(PersistenceError::Store(TicketStoreError::VersionMismatch { .. }))
+    ));
+    store.archive(id, &Actor::new("alice")).unwrap();
+    let history = store.history(id);
+    drop(store);
+
+    // The archive is replayed along with its history entry
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.get(id).unwrap().version, 3);
+    assert_eq!(store.history(id), history);
+    assert_eq!(history.last().unwrap().actor.as_str(), "alice");
+}
+
+#[test]
+fn test_failed_operations_are_not_logged() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert!(matches!(
+        store.get_mut(in_progress(TicketId(42))),
+        Err(PersistenceError::Store(_))
+    ));
+    drop(store);
+
+    let wal = fs::read_to_string(dir.path().join("wal.jsonl")).unwrap();
+    assert!(wal.is_empty());
+}
+
+#[test]
+fn test_torn_write_is_discarded() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+    drop(store);
+
+    // Simulate a crash in the middle of appending an entry
+    let mut wal = OpenOptions::new()
+        .append(true)
+        .open(dir.path().join("wal.jsonl"))
+        .unwrap();
+    wal.write_all(br#"{"seq":2,"op":"patch","patch":{"id":0,"ti"#).unwrap();
+    drop(wal);
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.get(id).unwrap().status, Status::ToDo);
+
+    // New entries are appended after the last complete one
+    store.get_mut(in_progress(id)).unwrap();
+    drop(store);
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.get(id).unwrap().status, Status::InProgress);
+}
+
+#[test]
+fn test_corrupted_wal_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    store.add_ticket(draft()).unwrap();
+    drop(store);
+
+    // A complete but invalid line is not a torn write: we refuse to guess
+    let mut wal = OpenOptions::new()
+        .append(true)
+        .open(dir.path().join("wal.jsonl"))
+        .unwrap();
+    wal.write_all(b"not json\n").unwrap();
+    drop(wal);
+
+    assert!(matches!(
+        PersistentTicketStore::open(dir.path()),
+        Err(PersistenceError::CorruptWal { line: 2, .. })
+    ));
+}
+
+#[test]
+fn test_compaction() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(3);
+    for _ in 0..4 {
+        store.add_ticket(draft()).unwrap();
+    }
+    store.get_mut(in_progress(TicketId(1))).unwrap();
+    drop(store);
+
+    // The first 3 operations went into the snapshot, only the last 2 are in the log
+    assert!(dir.path().join("snapshot.json").exists());
+    let wal = fs::read_to_string(dir.path().join("wal.jsonl")).unwrap();
+    assert_eq!(wal.lines().count(), 2);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    let page = store.list(&TicketQuery::default()).unwrap();
+    assert_eq!(page.tickets.len(), 4);
+    assert_eq!(store.get(TicketId(1)).unwrap().status, Status::InProgress);
+}
+
+#[test]
+fn test_failed_compaction_is_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(2);
+    // The temporary snapshot can't be created where a directory stands
+    fs::create_dir(dir.path().join("snapshot.json.tmp")).unwrap();
+    store.add_ticket(draft()).unwrap();
+    assert!(store.last_compaction_error().is_none());
+    // The operation itself succeeds
+    store.add_ticket(draft()).unwrap();
+    assert!(matches!(store.last_compaction_error(), Some(PersistenceError::Io(_))));
+
+    // Retried on the next operation
+    fs::remove_dir(dir.path().join("snapshot.json.tmp")).unwrap();
+    store.add_ticket(draft()).unwrap();
+    assert!(store.last_compaction_error().is_none());
+    assert!(dir.path().join("snapshot.json").exists());
+    drop(store);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.list(&TicketQuery::default()).unwrap().tickets.len(), 3);
+}
+
+#[test]
+fn test_crash_between_snapshot_and_truncation() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    store.add_ticket(draft()).unwrap();
+    store.add_ticket(draft()).unwrap();
+    let wal_before_compaction = fs::read(dir.path().join("wal.jsonl")).unwrap();
+    store.compact().unwrap();
+    drop(store);
+
+    // Put the log back, as if we crashed right after writing the snapshot:
+    // the entries already in the snapshot must not be applied twice
+    fs::write(dir.path().join("wal.jsonl"), wal_before_compaction).unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.list(&TicketQuery::default()).unwrap().tickets.len(), 2);
+    assert_eq!(store.add_ticket(draft()).unwrap(), TicketId(2));
+}
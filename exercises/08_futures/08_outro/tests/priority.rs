@@ -0,0 +1,316 @@
+use chrono::NaiveDate;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::client::TicketApiClient;
+use outro_08::clock::{Clock, ManualClock};
+use outro_08::data::{OrderBy, Priority, SortOrder, Status, Ticket, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::history::{Actor, FieldChange};
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::{SqliteError, SqliteTicketStore};
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+
+fn date(date: &str) -> NaiveDate {
+    date.parse().unwrap()
+}
+
+fn draft(priority: Priority, due_date: Option<&str>) -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority,
+        due_date: due_date.map(date),
+    }
+}
+
+fn patch(id: TicketId, status: Option<Status>, due_date: Option<Option<&str>>) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status,
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: due_date.map(|due_date| due_date.map(date)),
+    }
+}
+
+fn overdue() -> TicketQuery {
+    TicketQuery {
+        overdue: true,
+        ..Default::default()
+    }
+}
+
+fn by_priority(sort: SortOrder, limit: Option<usize>) -> TicketQuery {
+    TicketQuery {
+        order_by: OrderBy::Priority,
+        sort,
+        limit,
+        ..Default::default()
+    }
+}
+
+fn ids(tickets: &[Ticket]) -> Vec<TicketId> {
+    tickets.iter().map(|ticket| ticket.id).collect()
+}
+
+#[test]
+fn test_priorities() {
+    assert_eq!(Priority::try_from("p0").unwrap(), Priority::P0);
+    assert_eq!(Priority::try_from("P3").unwrap(), Priority::P3);
+    assert_eq!(
+        Priority::try_from("urgent").unwrap_err().to_string(),
+        "`urgent` is not a valid priority. Use one of: P0, P1, P2, P3"
+    );
+    assert_eq!(Priority::default(), Priority::P2);
+    assert!(Priority::P0 < Priority::P1);
+
+    // Tickets serialized before priorities existed are P2, without a due date
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft(Priority::P1, Some("2024-06-30"))).unwrap();
+    let ticket = store.get(id).unwrap().read().unwrap().clone();
+    let mut value = serde_json::to_value(&ticket).unwrap();
+    assert_eq!((&value["priority"], &value["due_date"]), (&json!("P1"), &json!("2024-06-30")));
+    value.as_object_mut().unwrap().remove("priority");
+    value.as_object_mut().unwrap().remove("due_date");
+    let old: Ticket = serde_json::from_value(value).unwrap();
+    assert_eq!((old.priority, old.due_date), (Priority::P2, None));
+}
+
+#[test]
+fn test_store_overdue() {
+    let clock = ManualClock::at(date("2024-06-15"));
+    let mut store = TicketStore::new().with_clock(clock.clone());
+    let past = store.add_ticket(draft(Priority::P2, Some("2024-06-14"))).unwrap();
+    let today = store.add_ticket(draft(Priority::P2, Some("2024-06-15"))).unwrap();
+    let done = store.add_ticket(draft(Priority::P2, Some("2024-06-01"))).unwrap();
+    store.add_ticket(draft(Priority::P2, None)).unwrap();
+    store.get_mut(patch(done, Some(Status::InProgress), None)).unwrap();
+    store.get_mut(patch(done, Some(Status::Done), None)).unwrap();
+
+    // Due today isn't overdue yet, and closed tickets never are
+    assert_eq!(ids(&store.list(&overdue()).unwrap().tickets), [past]);
+
+    // Time flies
+    clock.advance(Duration::from_secs(24 * 60 * 60));
+    assert_eq!(ids(&store.list(&overdue()).unwrap().tickets), [past, today]);
+
+    // Moving the due date, or clearing it, takes a ticket out of the list
+    store.get_mut(patch(past, None, Some(Some("2024-07-01")))).unwrap();
+    store.get_mut(patch(today, None, Some(None))).unwrap();
+    assert!(store.list(&overdue()).unwrap().tickets.is_empty());
+    assert_eq!(store.get(today).unwrap().read().unwrap().due_date, None);
+}
+
+#[test]
+fn test_store_priority_order() {
+    let mut store = TicketStore::new();
+    let p2_late = store.add_ticket(draft(Priority::P2, Some("2024-07-01"))).unwrap();
+    let p0_undated = store.add_ticket(draft(Priority::P0, None)).unwrap();
+    let p2_soon = store.add_ticket(draft(Priority::P2, Some("2024-06-20"))).unwrap();
+    let p3 = store.add_ticket(draft(Priority::P3, Some("2024-06-01"))).unwrap();
+    let p0_dated = store.add_ticket(draft(Priority::P0, Some("2024-12-31"))).unwrap();
+    let p2_undated = store.add_ticket(draft(Priority::P2, None)).unwrap();
+    let urgency = [p0_dated, p0_undated, p2_soon, p2_late, p2_undated, p3];
+
+    // By priority, then by due date, the tickets without one last
+    let page = store.list(&by_priority(SortOrder::Asc, None)).unwrap();
+    assert_eq!(ids(&page.tickets), urgency);
+    let page = store.list(&by_priority(SortOrder::Desc, None)).unwrap();
+    let mut reversed = urgency;
+    reversed.reverse();
+    assert_eq!(ids(&page.tickets), reversed);
+
+    // Pages follow the same order
+    let mut query = by_priority(SortOrder::Asc, Some(4));
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), urgency[..4]);
+    assert_eq!(page.next_cursor, Some(p2_late));
+    query.cursor = page.next_cursor;
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), urgency[4..]);
+    assert_eq!(page.next_cursor, None);
+
+    // Combined with the other filters
+    let query = TicketQuery {
+        status: Some(Status::ToDo),
+        ..by_priority(SortOrder::Asc, Some(1))
+    };
+    store.get_mut(patch(p0_dated, Some(Status::InProgress), None)).unwrap();
+    assert_eq!(ids(&store.list(&query).unwrap().tickets), [p0_undated]);
+
+    // The position of a removed cursor is unknown: the listing fails, rather than skip tickets
+    store.remove(p2_late).unwrap();
+    let query = TicketQuery {
+        cursor: Some(p2_late),
+        ..by_priority(SortOrder::Asc, None)
+    };
+    assert_eq!(store.list(&query).unwrap_err(), TicketStoreError::StaleCursor(p2_late));
+    // Listing by id doesn't need the cursor's ticket
+    let query = TicketQuery {
+        cursor: Some(p2_late),
+        ..Default::default()
+    };
+    assert_eq!(ids(&store.list(&query).unwrap().tickets), [p0_undated, p2_soon, p3, p0_dated, p2_undated]);
+}
+
+#[test]
+fn test_history_uses_the_clock() {
+    let clock = ManualClock::new(1_000);
+    let mut store = TicketStore::new().with_clock(clock.clone());
+    let id = store.add_ticket(draft(Priority::P2, None)).unwrap();
+    let mut raise = patch(id, None, Some(Some("2024-06-30")));
+    raise.priority = Some(Priority::P0);
+    store.patch_by(raise, None, &Actor::new("alice")).unwrap();
+    clock.advance(Duration::from_millis(500));
+    store.get_mut(patch(id, None, Some(None))).unwrap();
+
+    let history = store.history(id);
+    let timestamps: Vec<_> = history.iter().map(|entry| entry.timestamp).collect();
+    assert_eq!(timestamps, [1_000, 1_500]);
+    assert_eq!(
+        history[0].changes,
+        [
+            FieldChange {
+                field: "priority".to_string(),
+                before: "P2".into(),
+                after: "P0".into(),
+            },
+            FieldChange {
+                field: "due_date".to_string(),
+                before: serde_json::Value::Null,
+                after: "2024-06-30".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_persistent_priorities() {
+    let dir = tempfile::tempdir().unwrap();
+    let clock = ManualClock::at(date("2024-06-15"));
+
+    let mut store = PersistentTicketStore::open(dir.path())
+        .unwrap()
+        .with_clock(clock.clone())
+        .compact_every(2);
+    let late = store.add_ticket(draft(Priority::P1, Some("2024-06-10"))).unwrap();
+    let urgent = store.add_ticket(draft(Priority::P0, Some("2024-06-20"))).unwrap();
+    // Compacted here: the next operation is only in the log
+    clock.advance(Duration::from_millis(42));
+    store.get_mut(patch(urgent, None, Some(Some("2024-06-12")))).unwrap();
+    let history = store.history(urgent);
+    drop(store);
+
+    let store = PersistentTicketStore::open(dir.path()).unwrap().with_clock(clock.clone());
+    assert_eq!(ids(&store.list(&overdue()).unwrap().tickets), [late, urgent]);
+    assert_eq!(ids(&store.list(&by_priority(SortOrder::Asc, None)).unwrap().tickets), [urgent, late]);
+    assert_eq!(store.history(urgent), history);
+    assert_eq!(history[0].timestamp, clock.now_millis());
+}
+
+#[test]
+fn test_sqlite_priorities() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+    let clock = ManualClock::at(date("2024-06-15"));
+
+    let mut store = SqliteTicketStore::open(&path).unwrap().with_clock(clock.clone());
+    let p1_late = store.add_ticket(draft(Priority::P1, Some("2024-06-10"))).unwrap();
+    let p3 = store.add_ticket(draft(Priority::P3, Some("2024-06-01"))).unwrap();
+    let p0_undated = store.add_ticket(draft(Priority::P0, None)).unwrap();
+    let p1_soon = store.add_ticket(draft(Priority::P1, Some("2024-06-01"))).unwrap();
+    let p1_undated = store.add_ticket(draft(Priority::P1, None)).unwrap();
+    store.get_mut(patch(p3, Some(Status::InProgress), None)).unwrap();
+    store.get_mut(patch(p3, Some(Status::Done), None)).unwrap();
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap().with_clock(clock.clone());
+    let ticket = store.get(p1_late).unwrap().unwrap();
+    assert_eq!((ticket.priority, ticket.due_date), (Priority::P1, Some(date("2024-06-10"))));
+    assert_eq!(ids(&store.list(&overdue()).unwrap().tickets), [p1_late, p1_soon]);
+
+    let urgency = [p0_undated, p1_soon, p1_late, p1_undated, p3];
+    assert_eq!(ids(&store.list(&by_priority(SortOrder::Asc, None)).unwrap().tickets), urgency);
+    let mut query = by_priority(SortOrder::Desc, Some(2));
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), [p3, p1_undated]);
+    query.cursor = page.next_cursor;
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), [p1_late, p1_soon]);
+    query.cursor = page.next_cursor;
+    let page = store.list(&query).unwrap();
+    assert_eq!(ids(&page.tickets), [p0_undated]);
+    assert_eq!(page.next_cursor, None);
+    store.remove(p1_undated).unwrap();
+    query.cursor = Some(p1_undated);
+    assert!(matches!(
+        store.list(&query),
+        Err(SqliteError::Store(TicketStoreError::StaleCursor(id))) if id == p1_undated
+    ));
+
+    // The history is stamped by the clock
+    clock.set(7);
+    store.get_mut(patch(p1_late, None, Some(None))).unwrap();
+    assert_eq!(store.history(p1_late).unwrap().last().unwrap().timestamp, 7);
+    assert_eq!(ids(&store.list(&overdue()).unwrap().tickets), []);
+}
+
+#[tokio::test]
+async fn test_priority_endpoints() {
+    let clock = ManualClock::at(date("2024-06-15"));
+    let store = TicketStore::new().with_clock(clock.clone());
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(store))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let low = client.create(&draft(Priority::P3, Some("2024-06-01"))).await.unwrap();
+    let high = client.create(&draft(Priority::P0, Some("2024-06-14"))).await.unwrap();
+    let later = client.create(&draft(Priority::P0, Some("2024-07-01"))).await.unwrap();
+
+    let query = TicketQuery {
+        overdue: true,
+        ..by_priority(SortOrder::Asc, None)
+    };
+    assert_eq!(ids(&client.list(&query).await.unwrap().tickets), [high, low]);
+    let response = reqwest::get(format!("{}/tickets?overdue=true&order_by=priority", url)).await.unwrap();
+    let page: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(page["tickets"][0]["id"], json!(high));
+
+    // Priorities and due dates can be patched, and the due date cleared
+    let ticket = client
+        .merge_patch(later, &json!({"priority": "p1", "due_date": "2024-06-01"}))
+        .await
+        .unwrap();
+    assert_eq!((ticket.priority, ticket.due_date), (Priority::P1, Some(date("2024-06-01"))));
+    let ticket = client.merge_patch(low, &json!({"due_date": null})).await.unwrap();
+    assert_eq!(ticket.due_date, None);
+    assert_eq!(ids(&client.list(&overdue()).await.unwrap().tickets), [high, later]);
+
+    // A cursor whose ticket was deleted can't be resumed from
+    client.delete(low).await.unwrap();
+    let response = reqwest::get(format!("{}/tickets?order_by=priority&cursor={}", url, low)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::GONE);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "gone");
+
+    // Invalid values
+    for body in [json!({"priority": "P9"}), json!({"priority": null}), json!({"due_date": "tomorrow"})] {
+        let error = client.merge_patch(high, &body).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY), "{}", body);
+    }
+    let response = reqwest::get(format!("{}/tickets?order_by=urgency", url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    server.shutdown().await.unwrap();
+}
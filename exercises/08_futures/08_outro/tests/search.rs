@@ -0,0 +1,177 @@
+use reqwest::StatusCode;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Priority, TicketDraft, TicketPatch};
+use outro_08::history::Actor;
+use outro_08::persistent::PersistentTicketStore;
+use outro_08::search::{ParseSearchError, SearchHit, SearchParams, SearchQuery};
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::SqliteTicketStore;
+use outro_08::store::{TicketId, TicketStore};
+
+fn draft(title: &str, description: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from(description).unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn retitle(id: TicketId, title: &str) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: Some(TicketTitle::try_from(title).unwrap()),
+        description: None,
+        status: None,
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn query(query: &str) -> SearchQuery {
+    SearchQuery::try_from(query).unwrap()
+}
+
+fn ids(hits: &[SearchHit]) -> Vec<TicketId> {
+    hits.iter().map(|hit| hit.ticket.id).collect()
+}
+
+// Three tickets: the first and the last mention errors, the last one a lot more
+fn add_tickets(add: &mut impl FnMut(TicketDraft) -> TicketId) -> [TicketId; 3] {
+    [
+        add(draft("Login error", "The login page shows an error")),
+        add(draft("Crash on startup", "The app crashes when it starts")),
+        add(draft("Error in the logs", "Error, error, error")),
+    ]
+}
+
+#[test]
+fn test_parse_queries() {
+    for valid in ["login", "Login error", "login AND error", "login OR crash*", "log-in", "a OR b c OR d*"] {
+        assert!(SearchQuery::try_from(valid).is_ok(), "{}", valid);
+    }
+    assert_eq!(query("log-in"), query("log AND in"));
+    assert_eq!(query("LOGIN Error"), query("login error"));
+
+    assert_eq!(SearchQuery::try_from("  "), Err(ParseSearchError::Empty));
+    for (invalid, operator) in [("OR login", "OR"), ("login OR", "OR"), ("login AND OR crash", "OR"), ("AND", "AND")] {
+        assert_eq!(
+            SearchQuery::try_from(invalid),
+            Err(ParseSearchError::DanglingOperator(operator.to_string())),
+            "{}",
+            invalid
+        );
+    }
+    let err = SearchQuery::try_from("login --").unwrap_err();
+    assert_eq!(err.to_string(), "`--` is not a term: terms are made of letters and digits");
+}
+
+#[test]
+fn test_store_search() {
+    let mut store = TicketStore::new();
+    let [login, crash, logs] = add_tickets(&mut |draft| store.add_ticket(draft).unwrap());
+
+    // Case-insensitive, over titles and descriptions
+    assert_eq!(ids(&store.search(&query("LOGIN"), None)), [login]);
+    assert_eq!(ids(&store.search(&query("login error"), None)), [login]);
+    assert_eq!(ids(&store.search(&query("login AND crash"), None)), []);
+    assert_eq!(ids(&store.search(&query("login OR crash"), None)), [login, crash]);
+    // "crash" and "crashes"
+    assert_eq!(ids(&store.search(&query("crash*"), None)), [crash]);
+    assert_eq!(ids(&store.search(&query("crashed"), None)), []);
+
+    // The ticket made of errors comes first
+    let hits = store.search(&query("error"), None);
+    assert_eq!(ids(&hits), [logs, login]);
+    assert!(hits[0].score > hits[1].score);
+    let hits = store.search(&query("error OR startup"), None);
+    assert_eq!(ids(&hits), [logs, login, crash]);
+    assert_eq!(ids(&store.search(&query("error"), Some(1))), [logs]);
+
+    // The index follows the changes to the tickets
+    store.get_mut(retitle(login, "Sign-in failure")).unwrap();
+    assert_eq!(ids(&store.search(&query("login"), None)), [login]);
+    assert_eq!(ids(&store.search(&query("sign"), None)), [login]);
+    assert_eq!(ids(&store.search(&query("error"), None)), [logs, login]);
+    store.remove(logs).unwrap();
+    assert_eq!(ids(&store.search(&query("error"), None)), [login]);
+    store.archive(login, &Actor::system()).unwrap();
+    assert_eq!(ids(&store.search(&query("error"), None)), []);
+}
+
+#[test]
+fn test_persistent_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(2);
+    let [login, crash, logs] = add_tickets(&mut |draft| store.add_ticket(draft).unwrap());
+    store.get_mut(retitle(crash, "Login crash")).unwrap();
+    drop(store);
+
+    // Rebuilt from the snapshot and the log
+    let store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(ids(&store.search(&query("error"), None)), [logs, login]);
+    assert_eq!(ids(&store.search(&query("login crash"), None)), [crash]);
+    assert_eq!(ids(&store.search(&query("startup"), None)), []);
+}
+
+#[test]
+fn test_sqlite_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let [login, crash, logs] = add_tickets(&mut |draft| store.add_ticket(draft).unwrap());
+    store.get_mut(retitle(crash, "Login crash")).unwrap();
+    assert_eq!(ids(&store.search(&query("login"), None).unwrap()), [login, crash]);
+    drop(store);
+
+    // Rebuilt from the database
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(ids(&store.search(&query("error"), None).unwrap()), [logs, login]);
+    assert_eq!(ids(&store.search(&query("login crash"), None).unwrap()), [crash]);
+    assert_eq!(ids(&store.search(&query("startup"), None).unwrap()), []);
+
+    store.remove(logs).unwrap();
+    store.archive(login, &Actor::system()).unwrap();
+    assert_eq!(ids(&store.search(&query("error OR crash"), None).unwrap()), [crash]);
+}
+
+#[tokio::test]
+async fn test_search_endpoint() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let login = client.create(&draft("Login error", "The login page shows an error")).await.unwrap();
+    let logs = client.create(&draft("Error in the logs", "Errors everywhere")).await.unwrap();
+
+    let params = SearchParams {
+        q: "error*".to_string(),
+        limit: None,
+    };
+    let hits = client.search(&params).await.unwrap();
+    assert_eq!(ids(&hits), [logs, login]);
+    let response = reqwest::get(format!("{}/tickets/search?q=login%20page&limit=5", url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let hits: Vec<SearchHit> = response.json().await.unwrap();
+    assert_eq!(ids(&hits), [login]);
+
+    // The id routes are still there
+    assert_eq!(client.get(login).await.unwrap().id, login);
+
+    for url in [format!("{}/tickets/search?q=OR", url), format!("{}/tickets/search", url)] {
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", url);
+    }
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,251 @@
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use outro_08::data::{SortOrder, Status, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::history::Actor;
+use outro_08::sqlite::{SqliteError, SqliteTicketStore, SCHEMA_VERSION};
+use outro_08::store::{TicketId, TicketStoreError};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn in_progress(id: TicketId) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+#[test]
+fn test_restart_keeps_tickets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let id0 = store.add_ticket(draft()).unwrap();
+    let id1 = store.add_ticket(draft()).unwrap();
+    let patch = TicketPatch {
+        id: id0,
+        title: Some("A new title".try_into().unwrap()),
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    store.get_mut(patch).unwrap();
+    store.remove(id1).unwrap();
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let ticket = store.get(id0).unwrap().unwrap();
+    assert_eq!(ticket.title.as_str(), "A new title");
+    assert_eq!(ticket.description, ticket_description());
+    assert_eq!(ticket.status, Status::InProgress);
+    assert!(store.get(id1).unwrap().is_none());
+
+    // Ids of removed tickets are not reused
+    assert_eq!(store.add_ticket(draft()).unwrap(), TicketId(2));
+}
+
+#[test]
+fn test_migrations_are_applied_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+    drop(store);
+
+    // Opening an up-to-date database doesn't run the migrations again
+    let store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+}
+
+#[test]
+fn test_versions_are_added_to_existing_databases() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    // A database created before ticket versions existed
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE tickets (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            description TEXT NOT NULL,
+            status TEXT NOT NULL,
+            archived INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX tickets_by_status ON tickets (status, id);
+        CREATE TABLE ticket_counter (next_id INTEGER NOT NULL);
+        INSERT INTO ticket_counter (next_id) VALUES (1);
+        INSERT INTO tickets (id, title, description, status) VALUES (0, 'Old', 'An old ticket', 'ToDo');
+        PRAGMA user_version = 1;",
+    )
+    .unwrap();
+    drop(conn);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+    assert_eq!(store.get(TicketId(0)).unwrap().unwrap().version, 0);
+    let ticket = store.patch_if_version(in_progress(TicketId(0)), 0).unwrap();
+    assert_eq!(ticket.version, 1);
+    assert_eq!(store.add_ticket(draft()).unwrap(), TicketId(1));
+}
+
+#[test]
+fn test_patch_if_version() {
+    let mut store = SqliteTicketStore::open_in_memory().unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+    assert_eq!(store.get(id).unwrap().unwrap().version, 1);
+
+    let ticket = store.patch_if_version(in_progress(id), 1).unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.version, 2);
+
+    // A stale version is rejected, and the ticket is left untouched
+    let err = store.patch_if_version(in_progress(id), 1).unwrap_err();
+    assert!(matches!(
+        err,
+        SqliteError::Store(TicketStoreError::VersionMismatch { expected: 1, actual: 2, .. })
+    ));
+    assert_eq!(store.get(id).unwrap().unwrap().version, 2);
+
+    let err = store.patch_if_version(in_progress(TicketId(42)), 1).unwrap_err();
+    assert!(matches!(err, SqliteError::Store(TicketStoreError::NotFound(_))));
+
+    // A patch that changes nothing keeps the version, and leaves no history entry
+    store.get_mut(in_progress(id)).unwrap();
+    assert_eq!(store.get(id).unwrap().unwrap().version, 2);
+    assert_eq!(store.history(id).unwrap().len(), 1);
+    // Archiving bumps the version too, and is recorded
+    assert_eq!(store.archive(id, &Actor::new("alice")).unwrap().version, 3);
+    let history = store.history(id).unwrap();
+    assert_eq!((history[1].version, history[1].actor.as_str()), (3, "alice"));
+    assert_eq!(history[1].changes[0].field, "archived");
+}
+
+#[test]
+fn test_errors() {
+    let mut store = SqliteTicketStore::open_in_memory().unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+
+    store.archive(id, &Actor::system()).unwrap();
+    assert!(matches!(
+        store.archive(id, &Actor::system()),
+        Err(SqliteError::Store(TicketStoreError::AlreadyArchived(_)))
+    ));
+
+    let patch = TicketPatch {
+        id: TicketId(42),
+        title: None,
+        description: None,
+        status: Some(Status::Done),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    assert!(matches!(
+        store.get_mut(patch),
+        Err(SqliteError::Store(TicketStoreError::NotFound(TicketId(42))))
+    ));
+    assert!(matches!(
+        store.remove(TicketId(42)),
+        Err(SqliteError::Store(TicketStoreError::NotFound(TicketId(42))))
+    ));
+}
+
+#[test]
+fn test_list_tickets() {
+    let mut store = SqliteTicketStore::open_in_memory().unwrap();
+    for _ in 0..5 {
+        store.add_ticket(draft()).unwrap();
+    }
+    let patch = TicketPatch {
+        id: TicketId(3),
+        title: Some("Fix the LOGIN page".try_into().unwrap()),
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    store.get_mut(patch).unwrap();
+    store.archive(TicketId(4), &Actor::system()).unwrap();
+
+    let query = TicketQuery {
+        limit: Some(2),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    let ids: Vec<TicketId> = page.tickets.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![TicketId(0), TicketId(1)]);
+    assert_eq!(page.next_cursor, Some(TicketId(1)));
+
+    // Archived tickets are hidden by default
+    let query = TicketQuery {
+        cursor: page.next_cursor,
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    let ids: Vec<TicketId> = page.tickets.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![TicketId(2), TicketId(3)]);
+    assert_eq!(page.next_cursor, None);
+
+    let query = TicketQuery {
+        sort: SortOrder::Desc,
+        include_archived: true,
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(page.tickets[0].id, TicketId(4));
+    assert_eq!(page.next_cursor, Some(TicketId(4)));
+
+    let query = TicketQuery {
+        status: Some(Status::InProgress),
+        title: Some("login".to_string()),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert_eq!(page.tickets[0].id, TicketId(3));
+}
+
+#[test]
+fn test_corrupted_row_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    let id = store.add_ticket(draft()).unwrap();
+
+    // Another tool writes an empty title, which `TicketTitle` doesn't allow
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("UPDATE tickets SET title = '' WHERE id = ?1", [id.0]).unwrap();
+
+    match store.get(id) {
+        Err(SqliteError::CorruptRow { id: row_id, reason }) => {
+            assert_eq!(row_id, id.0);
+            assert_eq!(reason, "The title cannot be empty");
+        }
+        other => panic!("Expected a corrupted row, got {:?}", other),
+    }
+    assert!(store.list(&TicketQuery::default()).is_err());
+}
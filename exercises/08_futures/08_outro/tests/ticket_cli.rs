@@ -0,0 +1,92 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+use outro_08::data::{Status, Ticket, TicketPage};
+use outro_08::server::{start_server, ServerConfig, ServerHandle};
+use outro_08::store::TicketStore;
+
+async fn server() -> ServerHandle {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap()
+}
+
+// Run the `ticket` binary against the server, returning its exit code and stdout
+async fn ticket(addr: SocketAddr, args: &[&str]) -> (i32, String) {
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_ticket"))
+        .arg("--server")
+        .arg(format!("http://{}", addr))
+        .args(args)
+        .output()
+        .await
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (output.status.code().unwrap(), stdout)
+}
+
+#[tokio::test]
+async fn test_ticket_lifecycle() {
+    let server = server().await;
+
+    let (code, stdout) = ticket(
+        server.addr(),
+        &["--output", "json", "create", "--title", "A title", "--description", "A description"],
+    )
+    .await;
+    assert_eq!(code, 0);
+    let ticket_created: Ticket = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(ticket_created.status, Status::ToDo);
+
+    let (code, stdout) = ticket(server.addr(), &["--output", "json", "transition", "0", "inprogress"]).await;
+    assert_eq!(code, 0);
+    let ticket_moved: Ticket = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(ticket_moved.status, Status::InProgress);
+
+    let (code, stdout) = ticket(server.addr(), &["--output", "json", "edit", "0", "--title", "Another title"]).await;
+    assert_eq!(code, 0);
+    let ticket_edited: Ticket = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(ticket_edited.title.as_str(), "Another title");
+    assert_eq!(ticket_edited.status, Status::InProgress);
+
+    let (code, stdout) = ticket(server.addr(), &["--output", "json", "list", "--status", "InProgress"]).await;
+    assert_eq!(code, 0);
+    let page: TicketPage = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(page.tickets, vec![ticket_edited]);
+
+    let (code, stdout) = ticket(server.addr(), &["list"]).await;
+    assert_eq!(code, 0);
+    assert!(stdout.lines().nth(1).unwrap().contains("Another title"));
+
+    let (code, stdout) = ticket(server.addr(), &["show", "0"]).await;
+    assert_eq!(code, 0);
+    assert!(stdout.contains("Status:      InProgress"));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_exit_codes() {
+    let server = server().await;
+
+    // Unknown ticket
+    let (code, _) = ticket(server.addr(), &["show", "42"]).await;
+    assert_eq!(code, 3);
+    let (code, _) = ticket(server.addr(), &["transition", "42", "done"]).await;
+    assert_eq!(code, 3);
+
+    // Invalid input, caught before sending the request
+    let (code, _) = ticket(server.addr(), &["create", "--title", "", "--description", "A description"]).await;
+    assert_eq!(code, 4);
+    let (code, _) = ticket(server.addr(), &["list", "--status", "finished"]).await;
+    assert_eq!(code, 4);
+
+    // Invalid usage
+    let (code, _) = ticket(server.addr(), &["show"]).await;
+    assert_eq!(code, 2);
+
+    let addr = server.addr();
+    server.shutdown().await.unwrap();
+
+    // No server
+    let (code, _) = ticket(addr, &["show", "0"]).await;
+    assert_eq!(code, 1);
+}
@@ -2,8 +2,9 @@ use std::sync::{Arc, RwLock};
 use std::thread::spawn;
 
 use ticket_fields::test_helpers::{ticket_description, ticket_title};
-use outro_08::data::{TicketDraft,TicketPatch,Status};
-use outro_08::store::{TicketStore,TicketId};
+use outro_08::data::{TicketDraft,TicketPatch,TicketQuery,SortOrder,Status};
+use outro_08::history::Actor;
+use outro_08::store::{TicketStore,TicketStoreError,TicketId};
 
 // Unit tests should be run in multi thread:
 // cargo test --test unit -- --nocapture 
@@ -28,12 +29,17 @@ fn test_multithread() {
         let draft = TicketDraft {
             title: ticket_title(),
             description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
         };
         // write returns a guard that allows to modify the data
         store1
             .write()
             .unwrap()
             .add_ticket(draft)
+            .unwrap()
     });
 
     let store2 = store.clone();
@@ -41,12 +47,17 @@ fn test_multithread() {
         let draft = TicketDraft {
             title: ticket_title(),
             description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
         };
         // write returns a guard that allows to modify the data
         store2
             .write()
             .unwrap()
             .add_ticket(draft)
+            .unwrap()
     });
 
     let ticket_id1 = client1.join().unwrap();
@@ -71,12 +82,17 @@ fn test_add_ticket() {
         let draft = TicketDraft {
             title: ticket_title(),
             description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
         };
         // write returns a guard that allows to modify the data
         store
             .write()
             .unwrap()
             .add_ticket(draft)
+            .unwrap()
     });
 
     let ticket_id = client.join().unwrap();
@@ -95,12 +111,17 @@ fn test_get_ticket() {
         let draft = TicketDraft {
             title: ticket_title(),
             description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
         };
         // write returns a guard that allows to modify the data
         store_cloned
             .write()
             .unwrap()
             .add_ticket(draft)
+            .unwrap()
     });
 
     let ticket_id = client.join().unwrap();
@@ -124,6 +145,10 @@ fn test_patch_ticket() {
         let draft = TicketDraft {
             title: ticket_title(),
             description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
         };
 
         store1
@@ -131,6 +156,7 @@ fn test_patch_ticket() {
             .write()
             .unwrap()
             .add_ticket(draft)
+            .unwrap()
     });
 
     let ticket_id = client1.join().unwrap();
@@ -148,6 +174,10 @@ fn test_patch_ticket() {
             title: None,
             description: None,
             status: Some(Status::InProgress),
+            assignee: None,
+            reporter: None,
+            priority: None,
+            due_date: None,
         };
         
         store2  
@@ -157,7 +187,7 @@ fn test_patch_ticket() {
             .get_mut(patch)
     });
 
-    let _ = client2.join().unwrap();
+    client2.join().unwrap().unwrap();
 
     let reader = store.read().unwrap();
     let ticket_patched = reader.get(ticket_id).unwrap();
@@ -166,3 +196,190 @@ fn test_patch_ticket() {
     assert_eq!(ticket.read().unwrap().id, ticket_patched.read().unwrap().id);
     assert_eq!(ticket_patched.read().unwrap().status, Status::InProgress);
 }
+
+#[test]
+fn test_patch_unknown_ticket() {
+    let mut store = TicketStore::new();
+
+    let patch = TicketPatch {
+        id: TicketId(999),
+        title: None,
+        description: None,
+        status: Some(Status::Done),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+
+    // Patching a ticket that does not exist is an error, not a panic
+    assert_eq!(store.get_mut(patch), Err(TicketStoreError::NotFound(TicketId(999))));
+}
+
+#[test]
+fn test_list_tickets() {
+    let mut store = TicketStore::new();
+    for _ in 0..5 {
+        let draft = TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
+        };
+        store.add_ticket(draft).unwrap();
+    }
+    let patch = TicketPatch {
+        id: TicketId(3),
+        title: Some("Fix the LOGIN page".try_into().unwrap()),
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    store.get_mut(patch).unwrap();
+
+    // First page, in ascending order
+    let query = TicketQuery {
+        limit: Some(2),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    let ids: Vec<TicketId> = page.tickets.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![TicketId(0), TicketId(1)]);
+    assert_eq!(page.next_cursor, Some(TicketId(1)));
+
+    // Following the cursor until the last page
+    let query = TicketQuery {
+        limit: Some(2),
+        cursor: Some(TicketId(3)),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert_eq!(page.tickets[0].id, TicketId(4));
+    assert_eq!(page.next_cursor, None);
+
+    // Descending order starts from the highest id
+    let query = TicketQuery {
+        sort: SortOrder::Desc,
+        cursor: Some(TicketId(2)),
+        ..Default::default()
+    };
+    let ids: Vec<TicketId> = store.list(&query).unwrap().tickets.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![TicketId(1), TicketId(0)]);
+
+    // Filters on status and (case-insensitive) title
+    let query = TicketQuery {
+        status: Some(Status::InProgress),
+        ..Default::default()
+    };
+    let page = store.list(&query).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert_eq!(page.tickets[0].id, TicketId(3));
+
+    let query = TicketQuery {
+        title: Some("login".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(store.list(&query).unwrap().tickets[0].id, TicketId(3));
+}
+
+#[test]
+fn test_archive_and_remove_ticket() {
+    let mut store = TicketStore::new();
+    for _ in 0..2 {
+        let draft = TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+            assignee: None,
+            reporter: None,
+            priority: Default::default(),
+            due_date: None,
+        };
+        store.add_ticket(draft).unwrap();
+    }
+
+    // Archived tickets are excluded from listings, unless asked for
+    let archived = store.archive(TicketId(0), &Actor::new("alice")).unwrap();
+    assert!(archived.archived);
+    // Recorded in the history, like any other change
+    let history = store.history(TicketId(0));
+    assert_eq!(history.len(), 1);
+    assert_eq!((history[0].version, history[0].actor.as_str()), (archived.version, "alice"));
+    assert_eq!(history[0].changes[0].field, "archived");
+    assert_eq!(
+        store.archive(TicketId(0), &Actor::system()),
+        Err(TicketStoreError::AlreadyArchived(TicketId(0)))
+    );
+
+    let page = store.list(&TicketQuery::default()).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert_eq!(page.tickets[0].id, TicketId(1));
+
+    let query = TicketQuery {
+        include_archived: true,
+        ..Default::default()
+    };
+    assert_eq!(store.list(&query).unwrap().tickets.len(), 2);
+    assert!(store.get(TicketId(0)).is_some());
+
+    // Removed tickets are gone for good
+    let removed = store.remove(TicketId(0)).unwrap();
+    assert_eq!(removed, archived);
+    assert!(store.get(TicketId(0)).is_none());
+    assert_eq!(store.remove(TicketId(0)), Err(TicketStoreError::NotFound(TicketId(0))));
+}
+
+#[test]
+fn test_patch_if_version() {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    })
+    .unwrap();
+    let started = TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    };
+    assert_eq!(store.get(id).unwrap().read().unwrap().version, 1);
+
+    // Two clients read version 1: the first one to write wins
+    let ticket = store.patch_if_version(started.clone(), 1).unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.version, 2);
+    assert_eq!(
+        store.patch_if_version(started.clone(), 1),
+        Err(TicketStoreError::VersionMismatch { id, expected: 1, actual: 2 })
+    );
+
+    assert_eq!(
+        store.patch_if_version(TicketPatch { id: TicketId(9), ..started }, 1),
+        Err(TicketStoreError::NotFound(TicketId(9)))
+    );
+
+    // Every change bumps the version
+    assert_eq!(store.archive(id, &Actor::system()).unwrap().version, 3);
+}
+
+#[test]
+fn test_parse_status() {
+    // The parsing is case-insensitive
+    assert_eq!(Status::try_from("inproGress").unwrap(), Status::InProgress);
+    assert_eq!(Status::try_from(Status::Done.as_str()).unwrap(), Status::Done);
+    assert!(Status::try_from("Invalid").is_err());
+}
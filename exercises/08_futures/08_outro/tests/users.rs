@@ -0,0 +1,258 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Status, TicketDraft, TicketPatch, TicketQuery};
+use outro_08::history::{Actor, FieldChange};
+use outro_08::persistent::{PersistenceError, PersistentTicketStore};
+use outro_08::server::{start_server, ServerConfig};
+use outro_08::sqlite::{SqliteError, SqliteTicketStore};
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+use outro_08::user::{User, UserId, UserIdError, UserName, UserNameError};
+
+fn id(id: &str) -> UserId {
+    id.try_into().unwrap()
+}
+
+fn user(handle: &str, name: &str) -> User {
+    User {
+        id: id(handle),
+        name: UserName::try_from(name).unwrap(),
+    }
+}
+
+fn draft(assignee: Option<&str>, reporter: Option<&str>) -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: assignee.map(id),
+        reporter: reporter.map(id),
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn assign(ticket_id: TicketId, assignee: Option<&str>, status: Option<Status>) -> TicketPatch {
+    TicketPatch {
+        id: ticket_id,
+        title: None,
+        description: None,
+        status,
+        assignee: Some(assignee.map(id)),
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+fn assigned_to(assignee: &str, status: Option<Status>) -> TicketQuery {
+    TicketQuery {
+        assignee: Some(id(assignee)),
+        status,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_user_ids() {
+    assert_eq!(id("alice.smith-2_b").as_str(), "alice.smith-2_b");
+    assert_eq!(UserId::try_from(""), Err(UserIdError::Empty));
+    assert_eq!(UserId::try_from("a".repeat(33)), Err(UserIdError::TooLong));
+    assert_eq!(UserId::try_from("Alice"), Err(UserIdError::InvalidCharacter('A')));
+    assert_eq!(UserId::try_from("al ice"), Err(UserIdError::InvalidCharacter(' ')));
+    assert!(serde_json::from_value::<UserId>(json!("no/slash")).is_err());
+}
+
+#[test]
+fn test_user_names() {
+    assert_eq!(UserName::try_from("Alice Smith").unwrap().as_str(), "Alice Smith");
+    assert_eq!(UserName::try_from(""), Err(UserNameError::Empty));
+    assert_eq!(UserName::try_from(" \t"), Err(UserNameError::Empty));
+    // Whatever the user comes from: a request, an import, the log of a store...
+    assert!(serde_json::from_value::<User>(json!({"id": "alice", "name": "  "})).is_err());
+}
+
+#[test]
+fn test_store_users() {
+    let mut store = TicketStore::new();
+    let alice = store.add_user(user("alice", "Alice")).unwrap();
+    store.add_user(user("bob", "Bob")).unwrap();
+    assert_eq!(
+        store.add_user(user("alice", "Someone else")),
+        Err(TicketStoreError::UserAlreadyExists(id("alice")))
+    );
+    assert_eq!(store.get_user(&id("alice")), Some(alice));
+    assert_eq!(store.users().len(), 2);
+
+    // Tickets can only refer to registered users
+    assert_eq!(
+        store.add_ticket(draft(Some("carol"), None)),
+        Err(TicketStoreError::UnknownUser(id("carol")))
+    );
+    let first = store.add_ticket(draft(Some("alice"), Some("bob"))).unwrap();
+    let second = store.add_ticket(draft(None, Some("bob"))).unwrap();
+    store.patch_by(assign(second, Some("alice"), Some(Status::InProgress)), None, &Actor::new("bob")).unwrap();
+    assert_eq!(
+        store.get_mut(assign(first, Some("carol"), None)),
+        Err(TicketStoreError::UnknownUser(id("carol")))
+    );
+
+    // "Tickets assigned to alice in InProgress"
+    let page = store.list(&assigned_to("alice", Some(Status::InProgress))).unwrap();
+    let ids: Vec<_> = page.tickets.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(ids, [second]);
+    assert_eq!(store.list(&assigned_to("alice", None)).unwrap().tickets.len(), 2);
+    let reported = TicketQuery {
+        reporter: Some(id("bob")),
+        ..Default::default()
+    };
+    assert_eq!(store.list(&reported).unwrap().tickets.len(), 2);
+
+    let change = &store.history(second)[0].changes;
+    assert!(change.contains(&FieldChange {
+        field: "assignee".to_string(),
+        before: serde_json::Value::Null,
+        after: "alice".into(),
+    }));
+
+    // Users can't be removed while tickets refer to them
+    assert!(matches!(
+        store.remove_user(&id("alice")),
+        Err(TicketStoreError::UserInUse { .. })
+    ));
+    store.get_mut(assign(first, None, None)).unwrap();
+    store.get_mut(assign(second, None, None)).unwrap();
+    assert_eq!(store.get(first).unwrap().read().unwrap().assignee, None);
+    assert_eq!(store.remove_user(&id("alice")).unwrap().name.as_str(), "Alice");
+    assert_eq!(
+        store.remove_user(&id("alice")),
+        Err(TicketStoreError::UserNotFound(id("alice")))
+    );
+}
+
+#[test]
+fn test_persistent_users() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap().compact_every(3);
+    store.add_user(user("alice", "Alice")).unwrap();
+    store.add_user(user("bob", "Bob")).unwrap();
+    let ticket_id = store.add_ticket(draft(Some("alice"), Some("bob"))).unwrap();
+    // Compacted here: the next operations are only in the log
+    store.add_user(user("carol", "Carol")).unwrap();
+    store.get_mut(assign(ticket_id, Some("carol"), None)).unwrap();
+    store.remove_user(&id("alice")).unwrap();
+    drop(store);
+
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    let ids: Vec<_> = store.users().into_iter().map(|user| user.id).collect();
+    assert_eq!(ids, [id("bob"), id("carol")]);
+    assert_eq!(store.list(&assigned_to("carol", None)).unwrap().tickets.len(), 1);
+    assert!(matches!(
+        store.remove_user(&id("carol")),
+        Err(PersistenceError::Store(TicketStoreError::UserInUse { .. }))
+    ));
+    assert!(matches!(
+        store.add_ticket(draft(Some("alice"), None)),
+        Err(PersistenceError::Store(TicketStoreError::UnknownUser(_)))
+    ));
+}
+
+#[test]
+fn test_sqlite_users() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.db");
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    store.add_user(user("alice", "Alice")).unwrap();
+    store.add_user(user("bob", "Bob")).unwrap();
+    assert!(matches!(
+        store.add_user(user("bob", "Bob")),
+        Err(SqliteError::Store(TicketStoreError::UserAlreadyExists(_)))
+    ));
+    let first = store.add_ticket(draft(Some("alice"), Some("bob"))).unwrap();
+    let second = store.add_ticket(draft(Some("bob"), None)).unwrap();
+    store.get_mut(assign(second, Some("alice"), Some(Status::InProgress))).unwrap();
+    assert!(matches!(
+        store.add_ticket(draft(None, Some("carol"))),
+        Err(SqliteError::Store(TicketStoreError::UnknownUser(_)))
+    ));
+    drop(store);
+
+    let mut store = SqliteTicketStore::open(&path).unwrap();
+    assert_eq!(store.get_user(&id("alice")).unwrap(), Some(user("alice", "Alice")));
+    assert_eq!(store.users().unwrap().len(), 2);
+    let page = store.list(&assigned_to("alice", Some(Status::InProgress))).unwrap();
+    let ids: Vec<_> = page.tickets.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(ids, [second]);
+    assert_eq!(page.tickets[0].reporter, None);
+    assert_eq!(store.list(&assigned_to("alice", None)).unwrap().tickets.len(), 2);
+
+    assert!(matches!(
+        store.remove_user(&id("bob")),
+        Err(SqliteError::Store(TicketStoreError::UserInUse { ticket_id, .. })) if ticket_id == first
+    ));
+    store.remove(first).unwrap();
+    store.remove_user(&id("bob")).unwrap();
+    assert_eq!(store.get_user(&id("bob")).unwrap(), None);
+
+    // A blank name written by another tool is reported, not loaded
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("UPDATE users SET name = '' WHERE id = 'alice'", []).unwrap();
+    match store.get_user(&id("alice")) {
+        Err(SqliteError::CorruptUser { id, reason }) => {
+            assert_eq!((id.as_str(), reason.as_str()), ("alice", "The name cannot be empty"));
+        }
+        other => panic!("Expected a corrupted user, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_user_endpoints() {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let url = format!("http://{}", server.addr());
+    let client = TicketApiClient::new(url.clone());
+
+    let alice = client.create_user(&user("alice", "Alice")).await.unwrap();
+    client.create_user(&user("bob", "Bob")).await.unwrap();
+    assert_eq!(client.user(&id("alice")).await.unwrap(), alice);
+    assert_eq!(client.users().await.unwrap().len(), 2);
+    let error = client.create_user(&user("alice", "Alice")).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+    assert!(client.user(&id("carol")).await.unwrap_err().is_not_found());
+
+    // Assignees must be registered
+    let error = client.create(&draft(Some("carol"), None)).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    let ticket_id = client.create(&draft(Some("alice"), Some("bob"))).await.unwrap();
+    client.merge_patch(ticket_id, &json!({"status": "InProgress"})).await.unwrap();
+
+    let page = client.list(&assigned_to("alice", Some(Status::InProgress))).await.unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert_eq!(page.tickets[0].reporter, Some(id("bob")));
+    assert!(client.list(&assigned_to("bob", None)).await.unwrap().tickets.is_empty());
+
+    // Users can't be deleted while tickets are assigned to them
+    let error = client.delete_user(&id("alice")).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+    let ticket = client.merge_patch(ticket_id, &json!({"assignee": null})).await.unwrap();
+    assert_eq!(ticket.assignee, None);
+    assert_eq!(ticket.reporter, Some(id("bob")));
+    client.delete_user(&id("alice")).await.unwrap();
+    assert!(client.delete_user(&id("alice")).await.unwrap_err().is_not_found());
+
+    // Invalid users
+    let http = reqwest::Client::new();
+    for body in [json!({"id": "Not Valid", "name": "Nope"}), json!({"id": "nope", "name": " "})] {
+        let response = http.post(format!("{}/users", url)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    }
+    let response = http.get(format!("{}/users/Not%20Valid", url)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,387 @@
+use axum::body::Bytes;
+use axum::extract::{Extension, OriginalUri};
+use axum::http::HeaderMap;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use ticket_fields::{TicketDescription, TicketLabel, TicketTitle};
+
+use outro_08::client::TicketApiClient;
+use outro_08::data::{Priority, Status, TicketDraft, TicketPatch};
+use outro_08::events::EventBus;
+use outro_08::retry::RetryPolicy;
+use outro_08::server::{start_server, ServerConfig, ServerHandle};
+use outro_08::store::{TicketId, TicketStore};
+use outro_08::webhook::{
+    sign, verify, DeliveryId, WebhookDraft, WebhookEvent, WebhookPayload, DELIVERY_HEADER, EVENT_HEADER,
+    SIGNATURE_HEADER,
+};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("Found while triaging").unwrap(),
+        assignee: None,
+        reporter: None,
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn move_to(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+// Fails the test instead of hanging if the delivery never comes
+async fn within<T>(future: impl Future<Output = T>) -> T {
+    tokio::time::timeout(Duration::from_secs(5), future)
+        .await
+        .expect("timed out")
+}
+
+// A request received by the stand-in receiver
+#[derive(Debug)]
+struct Received {
+    path: String,
+    event: String,
+    delivery: String,
+    signature: String,
+    body: Vec<u8>,
+}
+
+impl Received {
+    fn payload(&self) -> WebhookPayload {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+// A stand-in for the services that receive the webhooks, on any path: it records every request,
+// and answers the next `failures` of them with a 500
+#[derive(Clone)]
+struct Receiver {
+    requests: mpsc::UnboundedSender<Received>,
+    failures: Arc<Mutex<usize>>,
+}
+
+async fn receive(
+    Extension(receiver): Extension<Receiver>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let header = |name: &str| headers[name].to_str().unwrap().to_string();
+    let received = Received {
+        path: uri.path().to_string(),
+        event: header(EVENT_HEADER),
+        delivery: header(DELIVERY_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body: body.to_vec(),
+    };
+    receiver.requests.send(received).unwrap();
+    let mut failures = receiver.failures.lock().unwrap();
+    if *failures > 0 {
+        *failures -= 1;
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    }
+}
+
+// Bound the same way as the ticket server, on a free port
+async fn start_receiver() -> (SocketAddr, mpsc::UnboundedReceiver<Received>, Arc<Mutex<usize>>) {
+    let (requests, received) = mpsc::unbounded_channel();
+    let failures = Arc::new(Mutex::new(0));
+    let receiver = Receiver {
+        requests,
+        failures: failures.clone(),
+    };
+    let app = axum::Router::new()
+        .fallback(receive)
+        .layer(axum::extract::Extension(receiver));
+    let server = axum::Server::bind(&([127, 0, 0, 1], 0).into()).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, received, failures)
+}
+
+async fn start(retry: RetryPolicy) -> (ServerHandle, TicketApiClient) {
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0)).webhook_retry(retry);
+    let server = start_server(config, Arc::new(RwLock::new(TicketStore::new()))).await.unwrap();
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    (server, client)
+}
+
+fn webhook(url: String, secret: &str, events: Vec<WebhookEvent>) -> WebhookDraft {
+    WebhookDraft {
+        url,
+        secret: secret.to_string(),
+        events,
+    }
+}
+
+// Nothing else arrives for a while
+async fn assert_quiet(received: &mut mpsc::UnboundedReceiver<Received>) {
+    let next = tokio::time::timeout(Duration::from_millis(200), received.recv()).await;
+    assert!(next.is_err(), "unexpected delivery: {:?}", next);
+}
+
+#[test]
+fn test_signatures() {
+    // RFC 4231, test case 2
+    let signature = sign("Jefe", b"what do ya want for nothing?");
+    assert_eq!(
+        signature,
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert!(verify("Jefe", b"what do ya want for nothing?", &signature));
+    assert!(!verify("Jefe", b"what do ya want for something?", &signature));
+    assert!(!verify("jefe", b"what do ya want for nothing?", &signature));
+    assert!(!verify("Jefe", b"what do ya want for nothing?", signature.trim_start_matches("sha256=")));
+    assert!(!verify("Jefe", b"what do ya want for nothing?", "sha256=5bdcc1"));
+    assert!(!verify("Jefe", b"what do ya want for nothing?", "sha256=not hex"));
+}
+
+#[tokio::test]
+async fn test_webhook_deliveries() {
+    let (receiver, mut received, _) = start_receiver().await;
+    let (server, client) = start(RetryPolicy::none()).await;
+    let all = client
+        .register_webhook(&webhook(format!("http://{}/all", receiver), "s3cr3t", Vec::new()))
+        .await
+        .unwrap();
+    assert_eq!(all.events, WebhookEvent::ALL);
+    let transitions = client
+        .register_webhook(&webhook(
+            format!("http://{}/transitions", receiver),
+            "other",
+            vec![WebhookEvent::StatusChanged],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(client.webhooks().await.unwrap(), [all.clone(), transitions.clone()]);
+    // The secrets are never sent back
+    let listed = reqwest::get(format!("http://{}/webhooks", server.addr()))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!listed.contains("s3cr3t"), "{}", listed);
+
+    let id = client.create(&draft("Flaky login test")).await.unwrap();
+    client.add_label(id, &TicketLabel::try_from("backend").unwrap()).await.unwrap();
+    client.patch(&move_to(id, Status::InProgress)).await.unwrap();
+    // Deletions aren't delivered
+    client.delete(id).await.unwrap();
+
+    let mut deliveries = Vec::new();
+    for _ in 0..5 {
+        deliveries.push(within(received.recv()).await.unwrap());
+    }
+    assert_quiet(&mut received).await;
+    // Deliveries are concurrent: their order isn't guaranteed
+    deliveries.sort_by_key(|received| (received.path.clone(), received.payload().seq, received.event.clone()));
+    let summary: Vec<(&str, &str, u64)> = deliveries
+        .iter()
+        .map(|received| (received.path.as_str(), received.event.as_str(), received.payload().seq))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("/all", "created", 1),
+            ("/all", "patched", 2),
+            ("/all", "patched", 3),
+            ("/all", "status_changed", 3),
+            ("/transitions", "status_changed", 3),
+        ]
+    );
+    for received in &deliveries {
+        let secret = if received.path == "/all" { "s3cr3t" } else { "other" };
+        assert!(verify(secret, &received.body, &received.signature));
+        assert_eq!(received.delivery, received.payload().delivery.to_string());
+        assert_eq!(received.event, received.payload().event.as_str());
+    }
+    let mut ids: Vec<DeliveryId> = deliveries.iter().map(|received| received.payload().delivery).collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 5);
+    let transition = deliveries[4].payload();
+    assert_eq!(transition.ticket.status, Status::InProgress);
+    assert_eq!(transition.changes.len(), 1);
+    assert_eq!(transition.changes[0].field, "status");
+    assert_eq!(transition.changes[0].before, "ToDo");
+    assert!(deliveries[0].payload().changes.is_empty());
+
+    // Once unregistered, a webhook gets nothing more
+    client.delete_webhook(all.id).await.unwrap();
+    let id = client.create(&draft("Checkout crash")).await.unwrap();
+    client.patch(&move_to(id, Status::InProgress)).await.unwrap();
+    let delivery = within(received.recv()).await.unwrap();
+    assert_eq!((delivery.path.as_str(), delivery.event.as_str()), ("/transitions", "status_changed"));
+    assert_quiet(&mut received).await;
+    assert_eq!(client.delete_webhook(all.id).await.unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+
+    for invalid in [
+        webhook("not a url".to_string(), "s3cr3t", Vec::new()),
+        webhook("ftp://example.com/hooks".to_string(), "s3cr3t", Vec::new()),
+        webhook(format!("http://{}/all", receiver), "", Vec::new()),
+    ] {
+        let err = client.register_webhook(&invalid).await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::UNPROCESSABLE_ENTITY), "{:?}", invalid);
+    }
+
+    within(server.shutdown()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_retries_and_dead_letters() {
+    let (receiver, mut received, failures) = start_receiver().await;
+    let retry = RetryPolicy {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(20),
+    };
+    let (server, client) = start(retry).await;
+    let created = vec![WebhookEvent::Created];
+    let hook = client
+        .register_webhook(&webhook(format!("http://{}/hook", receiver), "s3cr3t", created.clone()))
+        .await
+        .unwrap();
+
+    // Delivered on the last retry: the same delivery, three times
+    *failures.lock().unwrap() = 2;
+    let first = client.create(&draft("Flaky login test")).await.unwrap();
+    let attempts: Vec<Received> = within(async {
+        let mut attempts = Vec::new();
+        for _ in 0..3 {
+            attempts.push(received.recv().await.unwrap());
+        }
+        attempts
+    })
+    .await;
+    assert!(attempts.iter().all(|attempt| attempt.body == attempts[0].body));
+    assert_eq!(attempts[0].payload().ticket.id, first);
+    assert_quiet(&mut received).await;
+    assert!(client.dead_letters().await.unwrap().is_empty());
+
+    // Out of retries
+    *failures.lock().unwrap() = 3;
+    let second = client.create(&draft("Checkout crash")).await.unwrap();
+    for _ in 0..3 {
+        within(received.recv()).await.unwrap();
+    }
+    let dead_letters = within(async {
+        loop {
+            let dead_letters = client.dead_letters().await.unwrap();
+            if !dead_letters.is_empty() {
+                return dead_letters;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert_eq!(dead_letters.len(), 1);
+    let dead_letter = &dead_letters[0];
+    assert_eq!(dead_letter.webhook, hook.id);
+    assert_eq!(dead_letter.attempts, 3);
+    assert_eq!(dead_letter.payload.ticket.id, second);
+    assert!(dead_letter.error.contains("500"), "{}", dead_letter.error);
+
+    // The receiver is back: the dead letter goes through, with the same delivery id
+    let delivery = dead_letter.payload.delivery;
+    client.redeliver(delivery).await.unwrap();
+    let redelivered = within(received.recv()).await.unwrap();
+    assert_eq!(redelivered.payload(), dead_letter.payload);
+    assert!(verify("s3cr3t", &redelivered.body, &redelivered.signature));
+    assert!(client.dead_letters().await.unwrap().is_empty());
+    assert_eq!(client.redeliver(delivery).await.unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+
+    // Nobody listening at all
+    client.delete_webhook(hook.id).await.unwrap();
+    client
+        .register_webhook(&webhook("http://127.0.0.1:1/hook".to_string(), "s3cr3t", created))
+        .await
+        .unwrap();
+    client.create(&draft("Update the docs")).await.unwrap();
+    let dead_letter = within(async {
+        loop {
+            if let Some(dead_letter) = client.dead_letters().await.unwrap().pop() {
+                return dead_letter;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert!(dead_letter.error.starts_with("Request failed"), "{}", dead_letter.error);
+    // A dead letter can only go to a registered webhook
+    client.delete_webhook(dead_letter.webhook).await.unwrap();
+    let err = client.redeliver(dead_letter.payload.delivery).await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+
+    within(server.shutdown()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_abandons_pending_retries() {
+    let (receiver, mut received, failures) = start_receiver().await;
+    let retry = RetryPolicy {
+        max_retries: 5,
+        initial_backoff: Duration::from_secs(60),
+        max_backoff: Duration::from_secs(60),
+    };
+    let (server, client) = start(retry).await;
+    client
+        .register_webhook(&webhook(format!("http://{}/hook", receiver), "s3cr3t", Vec::new()))
+        .await
+        .unwrap();
+    *failures.lock().unwrap() = 1;
+    client.create(&draft("Flaky login test")).await.unwrap();
+    within(received.recv()).await.unwrap();
+
+    // The retry would come in a minute: the server doesn't wait for it
+    within(server.shutdown()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_skipped_events_are_counted() {
+    let (receiver, mut received, _) = start_receiver().await;
+    // The store keeps only its last two events for the subscribers that fall behind
+    let store = TicketStore::new().with_events(EventBus::with_capacity(2));
+    let store = Arc::new(RwLock::new(store));
+    let config = ServerConfig::new().bind(([127, 0, 0, 1], 0));
+    let server = start_server(config, store.clone()).await.unwrap();
+    let client = TicketApiClient::new(format!("http://{}", server.addr()));
+    // Gives the dispatcher the time to subscribe as well
+    client
+        .register_webhook(&webhook(format!("http://{}/hook", receiver), "s3cr3t", Vec::new()))
+        .await
+        .unwrap();
+    assert_eq!(server.webhooks().skipped_events(), 0);
+
+    // Ten tickets created without giving the dispatcher a chance to keep up
+    for i in 0..10 {
+        store.write().unwrap().add_ticket(draft(&format!("Ticket {}", i))).unwrap();
+    }
+    // Only the last two are delivered, the other ones are counted
+    for id in [8, 9] {
+        let payload = within(received.recv()).await.unwrap().payload();
+        assert_eq!(payload.ticket.id, TicketId(id));
+    }
+    assert_eq!(server.webhooks().skipped_events(), 8);
+    assert_quiet(&mut received).await;
+
+    within(server.shutdown()).await.unwrap();
+}
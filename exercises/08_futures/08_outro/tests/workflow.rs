@@ -0,0 +1,173 @@
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+use outro_08::config::TicketServerConfig;
+use outro_08::data::{Status, TicketDraft, TicketPatch};
+use outro_08::persistent::{PersistenceError, PersistentTicketStore};
+use outro_08::sqlite::{SqliteError, SqliteTicketStore};
+use outro_08::store::{TicketId, TicketStore, TicketStoreError};
+use outro_08::workflow::{Workflow, WorkflowError};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+        assignee: None,
+        reporter: None,
+        priority: Default::default(),
+        due_date: None,
+    }
+}
+
+fn move_to(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+        assignee: None,
+        reporter: None,
+        priority: None,
+        due_date: None,
+    }
+}
+
+#[test]
+fn test_standard_workflow() {
+    let workflow = Workflow::standard();
+
+    assert_eq!(workflow.check(Status::ToDo, Status::InProgress), Ok(()));
+    assert_eq!(workflow.check(Status::InProgress, Status::Done), Ok(()));
+    assert_eq!(workflow.check(Status::Done, Status::InProgress), Ok(()));
+    assert_eq!(workflow.check(Status::Done, Status::Done), Ok(()));
+    // Staying in a disabled status is no transition
+    assert_eq!(workflow.check(Status::Blocked, Status::Blocked), Ok(()));
+    assert_eq!(
+        workflow.check(Status::ToDo, Status::Done),
+        Err(WorkflowError::InvalidTransition {
+            from: Status::ToDo,
+            to: Status::Done
+        })
+    );
+    assert_eq!(
+        workflow.check(Status::Done, Status::ToDo),
+        Err(WorkflowError::InvalidTransition {
+            from: Status::Done,
+            to: Status::ToDo
+        })
+    );
+    assert_eq!(
+        workflow.check(Status::InProgress, Status::Blocked),
+        Err(WorkflowError::StatusNotEnabled(Status::Blocked))
+    );
+    assert_eq!(workflow.statuses().collect::<Vec<_>>(), Status::CORE);
+}
+
+#[test]
+fn test_custom_workflow() {
+    let workflow = Workflow::standard()
+        .enable(Status::InReview)
+        .forbid(Status::InProgress, Status::Done)
+        .allow(Status::Done, Status::ToDo);
+
+    assert_eq!(workflow.check(Status::InProgress, Status::InReview), Ok(()));
+    assert_eq!(workflow.check(Status::InReview, Status::Done), Ok(()));
+    assert!(workflow.check(Status::InProgress, Status::Done).is_err());
+    assert_eq!(workflow.check(Status::Done, Status::ToDo), Ok(()));
+    // Only the enabled optional statuses are available
+    assert!(workflow.check(Status::InProgress, Status::Cancelled).is_err());
+
+    // Everything goes, except what is forbidden
+    let workflow = Workflow::unrestricted().forbid(Status::Cancelled, Status::Done);
+    assert_eq!(workflow.check(Status::ToDo, Status::Done), Ok(()));
+    assert_eq!(workflow.check(Status::Blocked, Status::InReview), Ok(()));
+    assert!(workflow.check(Status::Cancelled, Status::Done).is_err());
+}
+
+#[test]
+fn test_store_rejects_invalid_transitions() {
+    let mut store = TicketStore::new().with_workflow(Workflow::standard().enable(Status::Blocked));
+    let id = store.add_ticket(draft()).unwrap();
+
+    assert_eq!(
+        store.get_mut(move_to(id, Status::Done)),
+        Err(TicketStoreError::Workflow {
+            id,
+            source: WorkflowError::InvalidTransition {
+                from: Status::ToDo,
+                to: Status::Done
+            }
+        })
+    );
+    // The rejected patch didn't change anything, not even the version
+    let ticket = store.get(id).unwrap().read().unwrap().clone();
+    assert_eq!((ticket.status, ticket.version), (Status::ToDo, 1));
+
+    store.get_mut(move_to(id, Status::Blocked)).unwrap();
+    store.get_mut(move_to(id, Status::InProgress)).unwrap();
+    store.get_mut(move_to(id, Status::Done)).unwrap();
+}
+
+#[test]
+fn test_persistent_store_replays_past_transitions() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut store = PersistentTicketStore::open(dir.path())
+        .unwrap()
+        .with_workflow(Workflow::standard().allow(Status::ToDo, Status::Done));
+    let id = store.add_ticket(draft()).unwrap();
+    store.get_mut(move_to(id, Status::Done)).unwrap();
+    drop(store);
+
+    // ToDo -> Done isn't allowed anymore, but it's in the log: it is replayed
+    let mut store = PersistentTicketStore::open(dir.path()).unwrap();
+    assert_eq!(store.get(id).unwrap().status, Status::Done);
+    assert!(matches!(
+        store.get_mut(move_to(id, Status::ToDo)),
+        Err(PersistenceError::Store(TicketStoreError::Workflow { .. }))
+    ));
+}
+
+#[test]
+fn test_sqlite_store_rejects_invalid_transitions() {
+    let mut store = SqliteTicketStore::open_in_memory()
+        .unwrap()
+        .with_workflow(Workflow::standard().enable(Status::Cancelled));
+    let id = store.add_ticket(draft()).unwrap();
+
+    assert!(matches!(
+        store.get_mut(move_to(id, Status::Done)),
+        Err(SqliteError::Store(TicketStoreError::Workflow { .. }))
+    ));
+    assert!(matches!(
+        store.patch_if_version(move_to(id, Status::Done), 1),
+        Err(SqliteError::Store(TicketStoreError::Workflow { .. }))
+    ));
+    assert_eq!(store.get(id).unwrap().unwrap().version, 1);
+
+    store.patch_if_version(move_to(id, Status::Cancelled), 1).unwrap();
+    assert!(store.get_mut(move_to(id, Status::InProgress)).is_err());
+    store.get_mut(move_to(id, Status::ToDo)).unwrap();
+}
+
+#[test]
+fn test_workflow_config() {
+    let config = TicketServerConfig::from_toml(
+        r#"
+        [workflow]
+        extra_statuses = ["Blocked", "InReview"]
+        allow = [["Done", "ToDo"]]
+        forbid = [["InProgress", "Done"]]
+        "#,
+    )
+    .unwrap();
+    let workflow = config.workflow.workflow();
+    assert_eq!(
+        workflow.statuses().collect::<Vec<_>>(),
+        [Status::ToDo, Status::InProgress, Status::Done, Status::Blocked, Status::InReview]
+    );
+    assert_eq!(workflow.check(Status::Done, Status::ToDo), Ok(()));
+    assert!(workflow.check(Status::InProgress, Status::Done).is_err());
+
+    assert_eq!(TicketServerConfig::default().workflow.workflow(), Workflow::standard());
+    assert!(TicketServerConfig::from_toml("[workflow]\nextra_statuses = [\"Paused\"]").is_err());
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+// The text of a comment on a ticket: same rules as a ticket description
+#[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+pub struct CommentBody(String);
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommentBodyError {
+    #[error("The comment cannot be empty")]
+    Empty,
+    #[error("The comment cannot be longer than 500 bytes")]
+    TooLong,
+}
+
+impl CommentBody {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for CommentBody {
+    type Error = CommentBodyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for CommentBody {
+    type Error = CommentBodyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        validate(value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+fn validate(body: &str) -> Result<(), CommentBodyError> {
+    if body.is_empty() {
+        Err(CommentBodyError::Empty)
+    } else if body.len() > 500 {
+        Err(CommentBodyError::TooLong)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{overly_long_description, valid_description};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_try_from_string() {
+        let input = valid_description();
+        let body = CommentBody::try_from(input.clone()).unwrap();
+        assert_eq!(body.0, input);
+    }
+
+    #[test]
+    fn test_try_from_empty_string() {
+        let err = CommentBody::try_from("".to_string()).unwrap_err();
+        assert_eq!(err.to_string(), "The comment cannot be empty");
+    }
+
+    #[test]
+    fn test_try_from_long_string() {
+        let err = CommentBody::try_from(overly_long_description()).unwrap_err();
+        assert_eq!(err.to_string(), "The comment cannot be longer than 500 bytes");
+    }
+}
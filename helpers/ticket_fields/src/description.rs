@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String")]
 pub struct TicketDescription(String);
 
 #[derive(Debug, thiserror::Error)]
@@ -11,6 +12,12 @@ pub enum TicketDescriptionError {
     TooLong,
 }
 
+impl TicketDescription {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl TryFrom<String> for TicketDescription {
     type Error = TicketDescriptionError;
 
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+// A free-form tag on a ticket, e.g. "bug", "area/billing" or "v1.2".
+// Labels are compared as they are written: "Bug" and "bug" are two different labels.
+#[derive(Debug, PartialEq, Clone, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+pub struct TicketLabel(String);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TicketLabelError {
+    #[error("The label cannot be empty")]
+    Empty,
+    #[error("The label cannot be longer than 30 bytes")]
+    TooLong,
+    #[error("The label must start with an ASCII letter or digit")]
+    InvalidStart,
+    #[error("The label can only contain ASCII letters, digits, '-', '_', '.', ':' and '/', not {0:?}")]
+    InvalidCharacter(char),
+}
+
+impl TicketLabel {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for TicketLabel {
+    type Error = TicketLabelError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for TicketLabel {
+    type Error = TicketLabelError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        validate(value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+fn validate(label: &str) -> Result<(), TicketLabelError> {
+    if label.is_empty() {
+        return Err(TicketLabelError::Empty);
+    }
+    if label.len() > 30 {
+        return Err(TicketLabelError::TooLong);
+    }
+    // "." and ".." would be special as URL path segments
+    if !label.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err(TicketLabelError::InvalidStart);
+    }
+    // No whitespace nor commas: labels can be listed in a query string or a CSV cell
+    match label
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/')))
+    {
+        Some(c) => Err(TicketLabelError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_try_from_string() {
+        let label = TicketLabel::try_from("area/billing".to_string()).unwrap();
+        assert_eq!(label.0, "area/billing");
+    }
+
+    #[test]
+    fn test_try_from_empty_string() {
+        let err = TicketLabel::try_from("".to_string()).unwrap_err();
+        assert_eq!(err.to_string(), "The label cannot be empty");
+    }
+
+    #[test]
+    fn test_try_from_long_string() {
+        let err = TicketLabel::try_from("a".repeat(31)).unwrap_err();
+        assert_eq!(err.to_string(), "The label cannot be longer than 30 bytes");
+    }
+
+    #[test]
+    fn test_try_from_invalid_characters() {
+        for (input, invalid) in [("good first issue", ' '), ("bug,ui", ','), ("café", 'é')] {
+            let err = TicketLabel::try_from(input).unwrap_err();
+            assert!(matches!(err, TicketLabelError::InvalidCharacter(c) if c == invalid), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_try_from_invalid_start() {
+        for input in ["..", "-wip", "/area"] {
+            let err = TicketLabel::try_from(input).unwrap_err();
+            assert_eq!(err.to_string(), "The label must start with an ASCII letter or digit");
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let label = TicketLabel::try_from("v1.2").unwrap();
+        let json = serde_json::to_string(&label).unwrap();
+        assert_eq!(json, "\"v1.2\"");
+        assert_eq!(serde_json::from_str::<TicketLabel>(&json).unwrap(), label);
+        // Deserialization goes through the same validation
+        assert!(serde_json::from_str::<TicketLabel>("\"not valid\"").is_err());
+    }
+}
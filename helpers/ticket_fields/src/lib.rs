@@ -1,6 +1,10 @@
+mod comment;
 mod description;
+mod label;
 pub mod test_helpers;
 mod title;
 
-pub use description::TicketDescription;
-pub use title::TicketTitle;
+pub use comment::{CommentBody, CommentBodyError};
+pub use description::{TicketDescription, TicketDescriptionError};
+pub use label::{TicketLabel, TicketLabelError};
+pub use title::{TicketTitle, TicketTitleError};
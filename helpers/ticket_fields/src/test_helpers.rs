@@ -1,4 +1,4 @@
-use crate::{TicketDescription, TicketTitle};
+use crate::{CommentBody, TicketDescription, TicketLabel, TicketTitle};
 use common::{valid_description, valid_title};
 
 /// A function to generate a valid ticket title,
@@ -12,3 +12,15 @@ pub fn ticket_title() -> TicketTitle {
 pub fn ticket_description() -> TicketDescription {
     valid_description().try_into().unwrap()
 }
+
+/// A function to generate a valid comment body,
+/// for test purposes.
+pub fn comment_body() -> CommentBody {
+    valid_description().try_into().unwrap()
+}
+
+/// A function to generate a valid ticket label,
+/// for test purposes.
+pub fn ticket_label() -> TicketLabel {
+    "bug".try_into().unwrap()
+}
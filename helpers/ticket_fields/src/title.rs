@@ -3,6 +3,7 @@ use std::convert::TryFrom;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String")]
 pub struct TicketTitle(String);
 
 #[derive(Debug, thiserror::Error)]
@@ -13,6 +14,12 @@ pub enum TicketTitleError {
     TooLong,
 }
 
+impl TicketTitle {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl TryFrom<String> for TicketTitle {
     type Error = TicketTitleError;
 